@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::*;
+use image::{Rgba, RgbaImage};
+
+/// Side of the white tile of the atlas when there are no textures to take
+/// the size from
+const DEFAULT_TILE_SIZE: u32 = 16;
+
+/// Area of a texture on the atlas, in texture coordinates from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct UvRect {
+    pub min: [f32; 2],
+    pub size: [f32; 2],
+}
+
+impl UvRect {
+    /// As the `[u, v, width, height]` the shader reads
+    pub fn to_array(self) -> [f32; 4] {
+        [self.min[0], self.min[1], self.size[0], self.size[1]]
+    }
+}
+
+/// All the block textures packed on a single image, so a chunk is drawn
+/// with only one texture bound.
+///
+/// A white tile is always on the top left corner, the faces without a
+/// texture sample it so only their color is left
+pub struct TextureAtlas {
+    image: RgbaImage,
+    rects: HashMap<String, UvRect>,
+    white: UvRect,
+    mip_levels: u32,
+}
+
+impl TextureAtlas {
+    /// An atlas with only the white tile
+    pub fn empty() -> Self {
+        Self::pack(Vec::new())
+    }
+
+    /// Pack every png of `dir`, named by their file name without the
+    /// extension
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut textures = Vec::new();
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "png") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let image = image::open(&path)
+                .with_context(|| format!("Failed to load {}", path.display()))?;
+            textures.push((name.to_owned(), image.to_rgba8()));
+        }
+
+        Ok(Self::pack(textures))
+    }
+
+    /// Pack the textures on the smallest power of two square that fits them.
+    ///
+    /// Each texture goes on the middle of a square cell twice its size, a
+    /// power of two, and the gutter around it is filled repeating the
+    /// texture. Placing the biggest cells first keeps every cell aligned to
+    /// its size, so the mip levels down to a texel per cell never mix two
+    /// textures and the filtering on the edges wraps around like the faces
+    pub fn pack(mut textures: Vec<(String, RgbaImage)>) -> Self {
+        textures.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let cell_size = |image: &RgbaImage| {
+            image.width().max(image.height()).next_power_of_two() * 2
+        };
+
+        // The white tile takes the biggest cell so it can go first without
+        // breaking the alignment of the rest
+        let tile = textures.iter()
+            .map(|(_, image)| cell_size(image) / 2)
+            .max()
+            .unwrap_or(DEFAULT_TILE_SIZE);
+        let white = RgbaImage::from_pixel(tile, tile, Rgba([255; 4]));
+        let mut images: Vec<(Option<&str>, &RgbaImage)> = vec![(None, &white)];
+        images.extend(textures.iter().map(|(name, image)| (Some(name.as_str()), image)));
+        images.sort_by_key(|(_, image)| std::cmp::Reverse(cell_size(image)));
+
+        let cells: Vec<u32> = images.iter().map(|(_, image)| cell_size(image)).collect();
+        let area: u32 = cells.iter().map(|cell| cell * cell).sum();
+        let mut size = cells[0].max((area as f32).sqrt().ceil() as u32).next_power_of_two();
+        let positions = loop {
+            match Self::place(&cells, size) {
+                Some(positions) => break positions,
+                None => size *= 2,
+            }
+        };
+
+        let mut atlas = RgbaImage::new(size, size);
+        let mut rects = HashMap::new();
+        let mut white_rect = UvRect::default();
+        for (((name, image), cell), (x, y)) in images.iter().zip(cells).zip(positions) {
+            let (width, height) = image.dimensions();
+            let (gutter_x, gutter_y) = ((cell - width) / 2, (cell - height) / 2);
+            for cell_y in 0..cell {
+                for cell_x in 0..cell {
+                    let texel = image.get_pixel(
+                        (cell_x + width - gutter_x % width) % width,
+                        (cell_y + height - gutter_y % height) % height
+                    );
+                    atlas.put_pixel(x + cell_x, y + cell_y, *texel);
+                }
+            }
+
+            let rect = UvRect {
+                min: [
+                    (x + gutter_x) as f32 / size as f32,
+                    (y + gutter_y) as f32 / size as f32
+                ],
+                size: [width as f32 / size as f32, height as f32 / size as f32],
+            };
+            match name {
+                Some(name) => {
+                    rects.insert(name.to_string(), rect);
+                }
+                None => white_rect = rect,
+            }
+        }
+
+        let smallest_cell = images.iter().map(|(_, image)| cell_size(image)).min().unwrap();
+        Self {
+            image: atlas,
+            rects,
+            white: white_rect,
+            mip_levels: smallest_cell.trailing_zeros() + 1,
+        }
+    }
+
+    /// Top left corner of each cell on a `size` square, in rows, `None` if
+    /// they don't fit
+    fn place(cells: &[u32], size: u32) -> Option<Vec<(u32, u32)>> {
+        let mut positions = Vec::new();
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+        for cell in cells {
+            if x + cell > size {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+            if y + cell > size {
+                return None;
+            }
+            positions.push((x, y));
+            x += cell;
+            row_height = row_height.max(*cell);
+        }
+
+        Some(positions)
+    }
+
+    /// Mip levels the atlas can have before the cells get mixed together
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    pub fn get(&self, name: &str) -> Option<UvRect> {
+        self.rects.get(name).copied()
+    }
+
+    /// The tile without texture
+    pub fn white(&self) -> UvRect {
+        self.white
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn packing() {
+        let atlas = TextureAtlas::pack(vec![
+            ("red".to_owned(), RgbaImage::from_pixel(16, 16, Rgba([255, 0, 0, 255]))),
+            ("blue".to_owned(), RgbaImage::from_pixel(16, 16, Rgba([0, 0, 255, 255]))),
+            ("wide".to_owned(), RgbaImage::from_pixel(32, 8, Rgba([0, 255, 0, 255]))),
+        ]);
+        assert_eq!(atlas.image().dimensions(), (128, 128));
+        assert_eq!(atlas.white(), UvRect { min: [0.125, 0.125], size: [0.25, 0.25] });
+        assert_eq!(atlas.get("wide"), Some(UvRect { min: [0.625, 0.21875], size: [0.25, 0.0625] }));
+        assert_eq!(atlas.get("blue"), Some(UvRect { min: [0.0625, 0.5625], size: [0.125, 0.125] }));
+        assert_eq!(atlas.get("red"), Some(UvRect { min: [0.3125, 0.5625], size: [0.125, 0.125] }));
+        assert_eq!(atlas.get("green"), None);
+        // Down to a texel per cell of the 16x16 textures
+        assert_eq!(atlas.mip_levels(), 6);
+
+        // The whole cells are filled with their texture
+        for (name, color) in [("red", [255, 0, 0, 255]), ("wide", [0, 255, 0, 255])] {
+            let rect = atlas.get(name).unwrap();
+            let x = ((rect.min[0] + rect.size[0]) * 128.0) as u32 - 1;
+            let y = ((rect.min[1] + rect.size[1]) * 128.0) as u32 - 1;
+            assert_eq!(atlas.image().get_pixel(x, y), &Rgba(color));
+        }
+        assert_eq!(atlas.image().get_pixel(0, 0), &Rgba([255; 4]));
+        assert_eq!(atlas.image().get_pixel(31, 64), &Rgba([0, 0, 255, 255]));
+        assert_eq!(atlas.image().get_pixel(63, 95), &Rgba([255, 0, 0, 255]));
+
+        let empty = TextureAtlas::empty();
+        assert_eq!(empty.image().dimensions(), (32, 32));
+        assert_eq!(empty.white().size, [0.5, 0.5]);
+    }
+
+    #[test]
+    fn gutters_repeat_the_texture() {
+        let mut image = RgbaImage::new(4, 4);
+        for (x, y, texel) in image.enumerate_pixels_mut() {
+            *texel = Rgba([x as u8, y as u8, 0, 255]);
+        }
+        let atlas = TextureAtlas::pack(vec![("gradient".to_owned(), image)]);
+        let rect = atlas.get("gradient").unwrap();
+        let size = atlas.image().width() as f32;
+        let (x, y) = ((rect.min[0] * size) as u32, (rect.min[1] * size) as u32);
+
+        // Left of the texture is its last column, above it its last row
+        assert_eq!(atlas.image().get_pixel(x, y), &Rgba([0, 0, 0, 255]));
+        assert_eq!(atlas.image().get_pixel(x - 1, y), &Rgba([3, 0, 0, 255]));
+        assert_eq!(atlas.image().get_pixel(x + 1, y - 2), &Rgba([1, 2, 0, 255]));
+        assert_eq!(atlas.image().get_pixel(x + 4, y + 5), &Rgba([0, 1, 0, 255]));
+    }
+}
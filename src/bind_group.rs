@@ -25,25 +25,6 @@ pub trait GPUDataType: Sized {
         }
     }
 
-    fn create_storage(
-        device: &wgpu::Device
-    ) -> Storage {
-        let data = Self::initial_value();
-        let debug_name = Self::debug_name();
-
-        let buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some(debug_name),
-                contents: data.as_slice(),
-                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST
-            }
-        );
-
-        Storage {
-            buffer: Rc::new(buffer)
-        }
-    }
-
     fn as_slice<'a>(&self) -> &'a [u8] {
         unsafe {
             std::slice::from_raw_parts(
@@ -87,16 +68,6 @@ impl<const N: usize> GPUDataType for [Matrix4<f32>; N] {
     }
 }
 
-impl<const N: usize> GPUDataType for [u32; N] {
-    fn initial_value() -> Self {
-        [0; N]
-    }
-
-    fn debug_name() -> &'static str {
-        "GPU u32 3D array"
-    }
-}
-
 impl GPUDataType for &[u32] {
     fn initial_value() -> Self {
         unreachable!()
@@ -110,7 +81,7 @@ impl GPUDataType for &[u32] {
         unsafe {
             std::slice::from_raw_parts(
                 self.as_ptr() as *const u8,
-                self.len() * std::mem::size_of::<u32>()
+                std::mem::size_of_val(*self)
             )
         }
     }
@@ -121,9 +92,29 @@ pub struct Storage {
 }
 
 impl Storage {
+    /// A zeroed buffer of `size` bytes, the data written to it can't be
+    /// longer
+    fn new(device: &wgpu::Device, size: u64) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU storage"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer: Rc::new(buffer)
+        }
+    }
+
     pub fn buffer(&self) -> Rc<wgpu::Buffer> {
         self.buffer.clone()
     }
+
+    /// Bytes that fit on the buffer
+    pub fn size(&self) -> u64 {
+        self.buffer.size()
+    }
 }
 
 pub struct Uniform {
@@ -163,11 +154,11 @@ pub struct BindGroup {
 }
 
 impl BindGroup {
-    pub fn bind_group_layout<'a>(&'a self) -> &'a wgpu::BindGroupLayout {
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
         &self.bind_group_layout
     }
     
-    pub fn bind_group<'a>(&'a self) -> &'a wgpu::BindGroup {
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
 }
@@ -269,7 +260,7 @@ impl<'a> BindGroupBuilder<'a> {
         self.layout_entries.push(
             wgpu::BindGroupLayoutEntry {
                 binding,
-                visibility: visibility,
+                visibility,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -295,25 +286,24 @@ impl<'a> BindGroupBuilder<'a> {
         uniform
     }
     
-    pub fn create_storage<DT>(
+    /// Create a read only storage buffer of `size` bytes
+    pub fn create_storage(
         &mut self,
-        visibility: wgpu::ShaderStages
-    ) -> Storage
-    where
-        DT: GPUDataType + 'static
-    { 
+        visibility: wgpu::ShaderStages,
+        size: u64
+    ) -> Storage {
         // Get its associated binding id
         let binding = self.get_binding();
 
-        // Instantiate the uniform and save it
-        let storage = DT::create_storage(self.device);
+        // Instantiate the storage and save it
+        let storage = Storage::new(self.device, size);
         let buffer = storage.buffer();
         
         // Generate the information to later instantiate the full bind group
         self.layout_entries.push(
             wgpu::BindGroupLayoutEntry {
                 binding,
-                visibility: visibility,
+                visibility,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Storage { 
                         read_only: true 
@@ -0,0 +1,149 @@
+/// Kind of land of a column, picked by the generator from the climate and
+/// kept on the column for the tints and the saves
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Biome {
+    #[default]
+    Plains = 0,
+    Desert = 1,
+    Forest = 2,
+    Mountains = 3,
+    Ocean = 4,
+}
+
+/// How a biome shapes the terrain, the heights are blended with the biomes
+/// around so the borders don't turn into cliffs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomeTerrain {
+    /// Height the surface goes around
+    pub base_height: f64,
+
+    /// How far the surface gets above and below `base_height`
+    pub height_variation: f64,
+
+    /// Top block of the ground and the ones right under it
+    pub surface_block: &'static str,
+    pub soil_block: &'static str,
+}
+
+/// How many features of each kind a column of the biome gets on average
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomeFeatures {
+    pub trees: f64,
+    pub boulders: f64,
+    pub structures: f64,
+}
+
+/// Which of the biome colors a face is multiplied by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tint {
+    Grass,
+    Foliage,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [
+        Biome::Plains,
+        Biome::Desert,
+        Biome::Forest,
+        Biome::Mountains,
+        Biome::Ocean,
+    ];
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Temperature and humidity, from -1 to 1, the biome is the closest one
+    /// to the climate of the column
+    pub fn climate(self) -> [f64; 2] {
+        match self {
+            Biome::Plains => [0.1, -0.1],
+            Biome::Desert => [0.7, -0.6],
+            Biome::Forest => [0.0, 0.4],
+            Biome::Mountains => [-0.6, -0.2],
+            Biome::Ocean => [0.2, 0.8],
+        }
+    }
+
+    /// The biome with the closest climate
+    pub fn from_climate(temperature: f64, humidity: f64) -> Self {
+        let distance = |biome: &Biome| {
+            let [t, h] = biome.climate();
+            (t - temperature).powi(2) + (h - humidity).powi(2)
+        };
+        Self::ALL.into_iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap()
+    }
+
+    pub fn terrain(self) -> BiomeTerrain {
+        let (base_height, height_variation, surface_block, soil_block) = match self {
+            Biome::Plains => (66.0, 8.0, "grass", "dirt"),
+            Biome::Desert => (67.0, 6.0, "sand", "sand"),
+            Biome::Forest => (68.0, 14.0, "grass", "dirt"),
+            Biome::Mountains => (96.0, 60.0, "stone", "stone"),
+            Biome::Ocean => (44.0, 12.0, "sand", "sand"),
+        };
+
+        BiomeTerrain {
+            base_height,
+            height_variation,
+            surface_block,
+            soil_block,
+        }
+    }
+
+    pub fn features(self) -> BiomeFeatures {
+        let (trees, boulders, structures) = match self {
+            Biome::Plains => (0.3, 0.05, 0.01),
+            Biome::Desert => (0.0, 0.2, 0.01),
+            Biome::Forest => (5.0, 0.1, 0.005),
+            Biome::Mountains => (0.4, 1.0, 0.0),
+            Biome::Ocean => (0.0, 0.0, 0.0),
+        };
+
+        BiomeFeatures {
+            trees,
+            boulders,
+            structures,
+        }
+    }
+
+    pub fn tint(self, tint: Tint) -> [f32; 3] {
+        match (self, tint) {
+            (Biome::Plains, Tint::Grass) => [0.45, 0.75, 0.3],
+            (Biome::Plains, Tint::Foliage) => [0.35, 0.65, 0.2],
+            (Biome::Desert, Tint::Grass) => [0.75, 0.7, 0.35],
+            (Biome::Desert, Tint::Foliage) => [0.65, 0.6, 0.3],
+            (Biome::Forest, Tint::Grass) => [0.3, 0.6, 0.2],
+            (Biome::Forest, Tint::Foliage) => [0.2, 0.5, 0.15],
+            (Biome::Mountains, Tint::Grass) => [0.5, 0.65, 0.45],
+            (Biome::Mountains, Tint::Foliage) => [0.4, 0.55, 0.4],
+            (Biome::Ocean, Tint::Grass) => [0.4, 0.7, 0.35],
+            (Biome::Ocean, Tint::Foliage) => [0.3, 0.6, 0.25],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn biome_ids_and_climates() {
+        for biome in Biome::ALL {
+            assert_eq!(Biome::from_id(biome.id()), Some(biome));
+            let [temperature, humidity] = biome.climate();
+            assert_eq!(Biome::from_climate(temperature, humidity), biome);
+        }
+        assert_eq!(Biome::from_id(5), None);
+        assert_eq!(Biome::from_climate(1.0, -1.0), Biome::Desert);
+        assert_eq!(Biome::from_climate(-1.0, 0.0), Biome::Mountains);
+    }
+}
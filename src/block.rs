@@ -0,0 +1,829 @@
+use std::collections::HashMap;
+
+use anyhow::*;
+
+use crate::atlas::{TextureAtlas, UvRect};
+use crate::biome::Tint;
+use crate::block_model::BlockModel;
+use crate::chunk::Face;
+
+/// Property that turns the block so its `Up` face looks along the axis
+pub const AXIS: &str = "axis";
+
+/// Property that turns the block around the vertical axis so its `Front`
+/// face looks at the named face
+pub const FACING: &str = "facing";
+
+/// The horizontal faces in the order a quarter turn around the vertical axis
+/// moves them, and the values of `FACING` that name them
+const HORIZONTAL_FACES: [Face; 4] = [Face::Front, Face::Right, Face::Back, Face::Left];
+const FACING_VALUES: [&str; 4] = ["front", "right", "back", "left"];
+
+/// Compact identifier of a block state, the type of block together with the
+/// value of each of its properties. Only meaningful when resolved through the
+/// `BlockRegistry` that handed it out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Block(u16);
+
+impl Block {
+    /// Air is always registered first, so its id is fixed
+    pub const AIR: Self = Block(0);
+
+    pub const fn from_id(id: u16) -> Self {
+        Block(id)
+    }
+
+    pub const fn id(&self) -> u16 {
+        self.0
+    }
+}
+
+/// How a block interacts with the faces of its neighbors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opacity {
+    /// Not rendered at all, never hides a neighbor
+    Invisible,
+
+    /// Rendered, but the neighbors can be seen through it
+    Transparent,
+
+    /// Rendered and hides the faces of the neighbors touching it
+    Opaque,
+}
+
+/// What a single face of a block looks like
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceAppearance {
+    pub color: [f32; 3],
+    pub texture: Option<String>,
+
+    /// Color of the biome the color is multiplied by, if any
+    pub tint: Option<Tint>,
+}
+
+impl FaceAppearance {
+    const fn new(color: [f32; 3]) -> Self {
+        Self {
+            color,
+            texture: None,
+            tint: None,
+        }
+    }
+}
+
+/// A named property of a block type that can take one of a fixed list of
+/// values, the first one is the default
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+/// The description of a kind of block, registered once at startup
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockType {
+    pub name: String,
+    pub opacity: Opacity,
+    pub solid: bool,
+
+    /// Whether placing a block on it replaces it, like air, water or the
+    /// grass
+    pub replaceable: bool,
+
+    pub light_emission: u8,
+
+    /// How much the faces hide what's behind them, only used by the
+    /// transparent blocks
+    pub alpha: f32,
+
+    /// Shape of the block, described as if its front looked at `Face::Front`
+    pub model: BlockModel,
+
+    faces: [FaceAppearance; 6],
+
+    /// Every combination of values of the properties is a different state
+    properties: Vec<Property>,
+}
+
+impl BlockType {
+    /// Create an opaque, solid, white block type, tweak it with the `with_*`
+    /// methods
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            opacity: Opacity::Opaque,
+            solid: true,
+            replaceable: false,
+            light_emission: 0,
+            alpha: 1.0,
+            model: BlockModel::Cube,
+            faces: std::array::from_fn(|_| FaceAppearance::new([1.0; 3])),
+            properties: Vec::new(),
+        }
+    }
+
+    /// Add a property, the first value is the one the block has by default
+    pub fn with_property(mut self, name: &str, values: &[&str]) -> Self {
+        assert!(!values.is_empty(), "Property {} without values", name);
+        assert!(
+            self.property(name).is_none(),
+            "Property {} added twice to {}", name, self.name
+        );
+        self.properties.push(Property {
+            name: name.to_owned(),
+            values: values.iter().map(|value| value.to_string()).collect(),
+        });
+        self
+    }
+
+    /// Let the block be placed along any axis, like a log, its faces are
+    /// described as if standing along `y`
+    pub fn with_axis(self) -> Self {
+        self.with_property(AXIS, &["y", "x", "z"])
+    }
+
+    /// Let the block be turned to face any horizontal direction, its faces
+    /// are described as if its front looked at `Face::Front`
+    pub fn with_facing(self) -> Self {
+        self.with_property(FACING, &FACING_VALUES)
+    }
+
+    pub fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|property| property.name == name)
+    }
+
+    /// Number of different states of this type
+    fn state_count(&self) -> usize {
+        self.properties.iter()
+            .map(|property| property.values.len())
+            .product()
+    }
+
+    pub fn with_opacity(mut self, opacity: Opacity) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn with_solid(mut self, solid: bool) -> Self {
+        self.solid = solid;
+        self
+    }
+
+    pub fn with_replaceable(mut self, replaceable: bool) -> Self {
+        self.replaceable = replaceable;
+        self
+    }
+
+    pub fn with_light_emission(mut self, light_emission: u8) -> Self {
+        self.light_emission = light_emission;
+        self
+    }
+
+    pub fn with_alpha(mut self, alpha: f32) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn with_model(mut self, model: BlockModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Set the same color on all the faces
+    pub fn with_color(mut self, color: [f32; 3]) -> Self {
+        for face in self.faces.iter_mut() {
+            face.color = color;
+        }
+        self
+    }
+
+    pub fn with_face_color(mut self, face: Face, color: [f32; 3]) -> Self {
+        self.faces[face as usize].color = color;
+        self
+    }
+
+    /// Set the same texture on all the faces
+    pub fn with_texture(mut self, texture: &str) -> Self {
+        for face in self.faces.iter_mut() {
+            face.texture = Some(texture.to_owned());
+        }
+        self
+    }
+
+    pub fn with_face_texture(mut self, face: Face, texture: &str) -> Self {
+        self.faces[face as usize].texture = Some(texture.to_owned());
+        self
+    }
+
+    /// Tint all the faces with a color of the biome
+    pub fn with_tint(mut self, tint: Tint) -> Self {
+        for face in self.faces.iter_mut() {
+            face.tint = Some(tint);
+        }
+        self
+    }
+
+    pub fn with_face_tint(mut self, face: Face, tint: Tint) -> Self {
+        self.faces[face as usize].tint = Some(tint);
+        self
+    }
+
+    pub fn face(&self, face: Face) -> &FaceAppearance {
+        &self.faces[face as usize]
+    }
+}
+
+/// Owner of all the block types, translates the compact `Block` ids stored on
+/// the chunks to their full description.
+///
+/// Each type gets a contiguous range of state ids, the offset of a state on
+/// its range packs the index of the value of every property, with the last
+/// property changing the fastest
+#[derive(Debug)]
+pub struct BlockRegistry {
+    types: Vec<BlockType>,
+
+    /// First state of each type
+    first_states: Vec<u16>,
+
+    /// Index of the type of each state
+    state_types: Vec<u16>,
+
+    /// Model of each state, already turned to where the state faces
+    state_models: Vec<BlockModel>,
+
+    /// Where the texture of each face of each type is on the atlas
+    face_uvs: Vec<[UvRect; 6]>,
+
+    by_name: HashMap<String, usize>,
+}
+
+impl BlockRegistry {
+    /// Create a registry that only knows about air
+    pub fn new() -> Self {
+        let mut registry = Self {
+            types: Vec::new(),
+            first_states: Vec::new(),
+            state_types: Vec::new(),
+            state_models: Vec::new(),
+            face_uvs: Vec::new(),
+            by_name: HashMap::new(),
+        };
+        registry.register(
+            BlockType::new("air")
+                .with_opacity(Opacity::Invisible)
+                .with_solid(false)
+                .with_replaceable(true)
+        );
+
+        registry
+    }
+
+    /// Create a registry with the blocks the engine ships with
+    pub fn with_default_blocks() -> Self {
+        let mut registry = Self::new();
+        registry.register(
+            BlockType::new("dirt")
+                .with_color([0.5, 0.35, 0.2])
+                .with_texture("dirt")
+        );
+        registry.register(
+            BlockType::new("stone")
+                .with_color([0.5, 0.5, 0.5])
+                .with_texture("stone")
+        );
+        registry.register(
+            BlockType::new("grass")
+                .with_color([0.5, 0.35, 0.2])
+                .with_face_color(Face::Up, [1.0; 3])
+                .with_face_tint(Face::Up, Tint::Grass)
+                .with_texture("dirt")
+                .with_face_texture(Face::Up, "grass_top")
+        );
+        registry.register(
+            BlockType::new("glass")
+                .with_opacity(Opacity::Transparent)
+                .with_color([0.8, 0.9, 1.0])
+                .with_texture("glass")
+                .with_alpha(0.4)
+        );
+        registry.register(
+            BlockType::new("log")
+                .with_axis()
+                .with_color([0.4, 0.3, 0.15])
+                .with_face_color(Face::Up, [0.65, 0.5, 0.3])
+                .with_face_color(Face::Down, [0.65, 0.5, 0.3])
+                .with_texture("log_side")
+                .with_face_texture(Face::Up, "log_top")
+                .with_face_texture(Face::Down, "log_top")
+        );
+        registry.register(
+            BlockType::new("furnace")
+                .with_facing()
+                .with_color([0.45, 0.45, 0.45])
+                .with_face_color(Face::Front, [0.35, 0.35, 0.35])
+                .with_texture("stone")
+                .with_face_texture(Face::Front, "furnace_front")
+        );
+        registry.register(
+            BlockType::new("glowstone")
+                .with_color([1.0, 0.85, 0.5])
+                .with_texture("glowstone")
+                .with_light_emission(15)
+        );
+        registry.register(
+            BlockType::new("slab")
+                .with_model(BlockModel::slab())
+                .with_color([0.55, 0.55, 0.55])
+                .with_texture("stone")
+        );
+        registry.register(
+            BlockType::new("stairs")
+                .with_facing()
+                .with_model(BlockModel::stairs())
+                .with_color([0.6, 0.45, 0.25])
+                .with_texture("planks")
+        );
+        registry.register(
+            BlockType::new("tall_grass")
+                .with_model(BlockModel::Cross)
+                .with_opacity(Opacity::Transparent)
+                .with_solid(false)
+                .with_replaceable(true)
+                .with_tint(Tint::Grass)
+                .with_texture("tall_grass")
+        );
+        registry.register(
+            BlockType::new("flower")
+                .with_model(BlockModel::Cross)
+                .with_opacity(Opacity::Transparent)
+                .with_solid(false)
+                .with_color([1.0, 0.3, 0.3])
+                .with_texture("flower")
+        );
+        registry.register(
+            BlockType::new("water")
+                .with_opacity(Opacity::Transparent)
+                .with_solid(false)
+                .with_replaceable(true)
+                .with_color([0.2, 0.4, 0.9])
+                .with_texture("water")
+                .with_alpha(0.6)
+        );
+        registry.register(
+            BlockType::new("sand")
+                .with_color([0.9, 0.85, 0.6])
+                .with_texture("sand")
+        );
+        registry.register(
+            BlockType::new("leaves")
+                .with_opacity(Opacity::Transparent)
+                .with_tint(Tint::Foliage)
+                .with_texture("leaves")
+        );
+
+        registry
+    }
+
+    /// Add a new block type and return the id of its default state,
+    /// registering twice the same name is a programming error
+    pub fn register(&mut self, block_type: BlockType) -> Block {
+        assert!(
+            !self.by_name.contains_key(&block_type.name),
+            "Block {} registered twice", block_type.name
+        );
+        let first_state = self.state_types.len();
+        let type_index = self.types.len();
+        assert!(
+            first_state + block_type.state_count() <= u16::MAX as usize + 1,
+            "Too many block states"
+        );
+
+        self.state_types.resize(
+            first_state + block_type.state_count(),
+            type_index as u16
+        );
+        self.first_states.push(first_state as u16);
+        self.by_name.insert(block_type.name.clone(), type_index);
+        self.types.push(block_type);
+        self.face_uvs.push([UvRect::default(); 6]);
+
+        for state in first_state..self.state_types.len() {
+            let turns = self.facing_turns(Block(state as u16));
+            let model = self.types[type_index].model.turned(turns);
+            self.state_models.push(model);
+        }
+
+        Block(first_state as u16)
+    }
+
+    /// Resolve the type of a block state, the ids can only come from this
+    /// registry so an unknown one is a bug
+    pub fn get(&self, block: Block) -> &BlockType {
+        &self.types[self.state_types[block.0 as usize] as usize]
+    }
+
+    /// The default state of a block type
+    pub fn by_name(&self, name: &str) -> Option<Block> {
+        self.by_name.get(name).map(|index| Block(self.first_states[*index]))
+    }
+
+    /// Index of the value of each property of the block type
+    fn state_values(&self, block: Block) -> Vec<usize> {
+        let type_index = self.state_types[block.0 as usize] as usize;
+        let mut offset = (block.0 - self.first_states[type_index]) as usize;
+        let mut values: Vec<usize> = self.types[type_index].properties.iter()
+            .rev()
+            .map(|property| {
+                let value = offset % property.values.len();
+                offset /= property.values.len();
+                value
+            })
+            .collect();
+        values.reverse();
+
+        values
+    }
+
+    /// Index of the value of the `index`th property of the block type
+    fn value_index(&self, block: Block, index: usize) -> usize {
+        let type_index = self.state_types[block.0 as usize] as usize;
+        let offset = (block.0 - self.first_states[type_index]) as usize;
+        let properties = &self.types[type_index].properties;
+        let stride: usize = properties[index + 1..].iter()
+            .map(|property| property.values.len())
+            .product();
+
+        offset / stride % properties[index].values.len()
+    }
+
+    /// Build the state of the type of `block` with the given value indices
+    fn state_from_values(&self, block: Block, values: &[usize]) -> Block {
+        let type_index = self.state_types[block.0 as usize] as usize;
+        let offset = self.types[type_index].properties.iter()
+            .zip(values)
+            .fold(0, |offset, (property, value)| {
+                offset * property.values.len() + value
+            });
+
+        Block(self.first_states[type_index] + offset as u16)
+    }
+
+    /// The value of a property of a block state
+    pub fn property<'a>(&'a self, block: Block, name: &str) -> Option<&'a str> {
+        let block_type = self.get(block);
+        let index = block_type.properties.iter()
+            .position(|property| property.name == name)?;
+        let value = self.value_index(block, index);
+
+        Some(&block_type.properties[index].values[value])
+    }
+
+    /// The same block state with a property changed, `None` if the type
+    /// doesn't have that property or value
+    pub fn with_property(&self, block: Block, name: &str, value: &str) -> Option<Block> {
+        let block_type = self.get(block);
+        let index = block_type.properties.iter()
+            .position(|property| property.name == name)?;
+        let mut values = self.state_values(block);
+        values[index] = block_type.properties[index].values.iter()
+            .position(|v| v == value)?;
+
+        Some(self.state_from_values(block, &values))
+    }
+
+    /// Full name of a block state, like `log[axis=x]`, the inverse of `parse`
+    pub fn state_name(&self, block: Block) -> String {
+        let block_type = self.get(block);
+        if block_type.properties.is_empty() {
+            return block_type.name.clone();
+        }
+
+        let properties: Vec<String> = block_type.properties.iter()
+            .zip(self.state_values(block))
+            .map(|(property, value)| {
+                format!("{}={}", property.name, property.values[value])
+            })
+            .collect();
+
+        format!("{}[{}]", block_type.name, properties.join(","))
+    }
+
+    /// Parse a block state like `log[axis=x]`, the properties not given keep
+    /// their default value
+    pub fn parse(&self, state: &str) -> Result<Block> {
+        let state = state.trim();
+        let (name, properties) = match state.split_once('[') {
+            Some((name, properties)) => {
+                let properties = properties.strip_suffix(']')
+                    .with_context(|| format!("Missing ']' on {:?}", state))?;
+                (name.trim(), Some(properties))
+            }
+            None => (state, None),
+        };
+        let block = self.by_name(name)
+            .with_context(|| format!("Unknown block {:?}", name))?;
+
+        let mut values = self.state_values(block);
+        let mut given = Vec::new();
+        for property in properties.into_iter().flat_map(|p| p.split(',')) {
+            if property.trim().is_empty() {
+                continue;
+            }
+            let (key, value) = property.split_once('=')
+                .with_context(|| format!("Expected key=value, got {:?}", property))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            let block_type = self.get(block);
+            let index = block_type.properties.iter()
+                .position(|property| property.name == key)
+                .with_context(|| format!("{} has no property {:?}", name, key))?;
+            ensure!(!given.contains(&index), "Property {:?} given twice", key);
+            given.push(index);
+            values[index] = block_type.properties[index].values.iter()
+                .position(|v| v == value)
+                .with_context(|| {
+                    format!("{:?} isn't a value of {}.{}", value, name, key)
+                })?;
+        }
+
+        Ok(self.state_from_values(block, &values))
+    }
+
+    /// What the `face` of a block state looks like, oriented blocks show the
+    /// face of their type that was turned towards it
+    pub fn face(&self, block: Block, face: Face) -> &FaceAppearance {
+        self.get(block).face(self.model_face(block, face))
+    }
+
+    /// Find the textures of the faces of every registered type on the atlas,
+    /// the faces without a texture or with a missing one are left white
+    pub fn set_atlas(&mut self, atlas: &TextureAtlas) {
+        for (block_type, uvs) in self.types.iter().zip(self.face_uvs.iter_mut()) {
+            for face in Face::ALL {
+                let texture = block_type.face(face).texture.as_deref();
+                uvs[face as usize] = match texture.map(|name| (name, atlas.get(name))) {
+                    Some((_, Some(uv))) => uv,
+                    Some((name, None)) => {
+                        log::warn!("Missing texture {:?} of {}", name, block_type.name);
+                        atlas.white()
+                    }
+                    None => atlas.white(),
+                };
+            }
+        }
+    }
+
+    /// Where the texture of the `face` of a block state is on the atlas given
+    /// to `set_atlas`, turned like `face`
+    pub fn face_uv(&self, block: Block, face: Face) -> UvRect {
+        let type_index = self.state_types[block.0 as usize] as usize;
+        self.face_uvs[type_index][self.model_face(block, face) as usize]
+    }
+
+    /// Undo the rotation of a block state, the face of the type that ends up
+    /// looking towards `face`
+    fn model_face(&self, block: Block, face: Face) -> Face {
+        if self.get(block).properties.is_empty() {
+            return face;
+        }
+
+        let turns = self.facing_turns(block);
+        let face = match HORIZONTAL_FACES.iter().position(|f| *f == face) {
+            Some(index) => HORIZONTAL_FACES[(index + 4 - turns) % 4],
+            None => face,
+        };
+
+        match (self.property(block, AXIS), face) {
+            (Some("x"), Face::Right) => Face::Up,
+            (Some("x"), Face::Left) => Face::Down,
+            (Some("x"), Face::Up) => Face::Left,
+            (Some("x"), Face::Down) => Face::Right,
+            (Some("z"), Face::Back) => Face::Up,
+            (Some("z"), Face::Front) => Face::Down,
+            (Some("z"), Face::Up) => Face::Front,
+            (Some("z"), Face::Down) => Face::Back,
+            (_, face) => face,
+        }
+    }
+
+    /// Quarter turns from `Face::Front` to where the block state faces, 0
+    /// for the types that can't be turned
+    fn facing_turns(&self, block: Block) -> usize {
+        self.property(block, FACING)
+            .map(|facing| {
+                FACING_VALUES.iter()
+                    .position(|value| *value == facing)
+                    .unwrap()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Shape of a block state, turned to where it faces
+    pub fn model(&self, block: Block) -> &BlockModel {
+        &self.state_models[block.0 as usize]
+    }
+
+    /// Whether the block fills its whole space with opaque faces, only those
+    /// stop the light and darken the corners of the faces around them
+    pub fn is_full_cube(&self, block: Block) -> bool {
+        self.is_opaque(block) && *self.model(block) == BlockModel::Cube
+    }
+
+    pub fn opacity(&self, block: Block) -> Opacity {
+        self.get(block).opacity
+    }
+
+    pub fn is_opaque(&self, block: Block) -> bool {
+        self.opacity(block) == Opacity::Opaque
+    }
+
+    /// Whether the block is drawn, so it can be targeted. Only air isn't
+    pub fn is_visible(&self, block: Block) -> bool {
+        self.opacity(block) != Opacity::Invisible
+    }
+
+    pub fn is_replaceable(&self, block: Block) -> bool {
+        self.get(block).replaceable
+    }
+
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Iterate all the registered blocks with their default state, air
+    /// included
+    pub fn iter(&self) -> impl Iterator<Item = (Block, &BlockType)> {
+        self.types.iter()
+            .zip(self.first_states.iter())
+            .map(|(block_type, first_state)| (Block(*first_state), block_type))
+    }
+
+    /// Iterate every state of every registered block, air included
+    pub fn states(&self) -> impl Iterator<Item = Block> {
+        (0..self.state_types.len() as u16).map(Block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn registration_and_lookup() {
+        let mut registry = BlockRegistry::new();
+        assert_eq!(registry.by_name("air"), Some(Block::AIR));
+        assert_eq!(registry.opacity(Block::AIR), Opacity::Invisible);
+        assert!(registry.is_replaceable(Block::AIR));
+
+        let lamp = registry.register(
+            BlockType::new("lamp")
+                .with_light_emission(15)
+                .with_face_color(Face::Up, [1.0, 1.0, 0.0])
+        );
+        assert_eq!(lamp, Block::from_id(1));
+        assert_eq!(registry.by_name("lamp"), Some(lamp));
+        assert_eq!(registry.get(lamp).light_emission, 15);
+        assert_eq!(registry.get(lamp).face(Face::Up).color, [1.0, 1.0, 0.0]);
+        assert_eq!(registry.get(lamp).face(Face::Down).color, [1.0, 1.0, 1.0]);
+        assert!(registry.is_opaque(lamp));
+        assert!(registry.is_visible(lamp) && !registry.is_replaceable(lamp));
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn block_states() {
+        let mut registry = BlockRegistry::new();
+        let stairs = registry.register(
+            BlockType::new("stairs")
+                .with_facing()
+                .with_property("half", &["bottom", "top"])
+        );
+        let dirt = registry.register(BlockType::new("dirt"));
+        assert_eq!(stairs, Block::from_id(1));
+        // 4 facings by 2 halves
+        assert_eq!(dirt, Block::from_id(9));
+        assert_eq!(registry.get(Block::from_id(8)).name, "stairs");
+
+        assert_eq!(registry.property(stairs, FACING), Some("front"));
+        assert_eq!(registry.property(stairs, "half"), Some("bottom"));
+        assert_eq!(registry.property(stairs, AXIS), None);
+        let top = registry.with_property(stairs, "half", "top").unwrap();
+        let back_top = registry.with_property(top, FACING, "back").unwrap();
+        assert_eq!(registry.property(back_top, FACING), Some("back"));
+        assert_eq!(registry.property(back_top, "half"), Some("top"));
+        assert_eq!(registry.with_property(stairs, "half", "middle"), None);
+        assert_eq!(registry.with_property(dirt, "half", "top"), None);
+
+        // Every state has its own id and a name that parses back to it
+        let mut seen = Vec::new();
+        for facing in ["front", "right", "back", "left"] {
+            for half in ["bottom", "top"] {
+                let state = registry.parse(
+                    &format!("stairs[half={},facing={}]", half, facing)
+                ).unwrap();
+                assert!(!seen.contains(&state));
+                seen.push(state);
+                assert_eq!(registry.parse(&registry.state_name(state)).unwrap(), state);
+            }
+        }
+        assert_eq!(registry.state_name(back_top), "stairs[facing=back,half=top]");
+        assert_eq!(registry.state_name(dirt), "dirt");
+    }
+
+    #[test]
+    fn parse_states() {
+        let registry = BlockRegistry::with_default_blocks();
+        let log = registry.by_name("log").unwrap();
+        let x_log = registry.parse("log[axis=x]").unwrap();
+        assert_eq!(registry.property(x_log, AXIS), Some("x"));
+        assert_eq!(registry.parse("log").unwrap(), log);
+        assert_eq!(registry.parse(" log[ axis = y ] ").unwrap(), log);
+        assert_eq!(registry.parse("log[]").unwrap(), log);
+        assert_eq!(registry.parse("dirt").unwrap(), registry.by_name("dirt").unwrap());
+
+        assert!(registry.parse("planks").is_err());
+        assert!(registry.parse("log[axis=w]").is_err());
+        assert!(registry.parse("log[color=red]").is_err());
+        assert!(registry.parse("log[axis=x").is_err());
+        assert!(registry.parse("log[axis]").is_err());
+        assert!(registry.parse("log[axis=x,axis=z]").is_err());
+    }
+
+    #[test]
+    fn oriented_faces() {
+        let registry = BlockRegistry::with_default_blocks();
+        let log = registry.by_name("log").unwrap();
+        let rings = registry.get(log).face(Face::Up).color;
+        let bark = registry.get(log).face(Face::Front).color;
+
+        let x_log = registry.parse("log[axis=x]").unwrap();
+        assert_eq!(registry.face(x_log, Face::Left).color, rings);
+        assert_eq!(registry.face(x_log, Face::Right).color, rings);
+        assert_eq!(registry.face(x_log, Face::Up).color, bark);
+        let z_log = registry.parse("log[axis=z]").unwrap();
+        assert_eq!(registry.face(z_log, Face::Front).color, rings);
+        assert_eq!(registry.face(z_log, Face::Up).color, bark);
+        assert_eq!(registry.face(log, Face::Up).color, rings);
+
+        let furnace = registry.by_name("furnace").unwrap();
+        let front = registry.get(furnace).face(Face::Front).color;
+        for face in Face::ALL {
+            let facing = format!("{:?}", face).to_lowercase();
+            let Some(turned) = registry.with_property(furnace, FACING, &facing) else {
+                continue;
+            };
+            for other in Face::ALL {
+                assert_eq!(registry.face(turned, other).color == front, other == face);
+            }
+        }
+    }
+
+    #[test]
+    fn state_models() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stairs = registry.by_name("stairs").unwrap();
+        assert!(registry.model(stairs).covers(Face::Back));
+        let left = registry.with_property(stairs, FACING, "left").unwrap();
+        assert!(registry.model(left).covers(Face::Right));
+        assert!(!registry.model(left).covers(Face::Back));
+
+        assert!(registry.is_full_cube(registry.by_name("stone").unwrap()));
+        assert!(!registry.is_full_cube(registry.by_name("slab").unwrap()));
+        assert!(!registry.is_full_cube(registry.by_name("glass").unwrap()));
+        assert!(!registry.is_full_cube(Block::AIR));
+    }
+
+    #[test]
+    fn face_textures() {
+        let mut registry = BlockRegistry::with_default_blocks();
+        let log = registry.by_name("log").unwrap();
+        let x_log = registry.parse("log[axis=x]").unwrap();
+        let dirt = registry.by_name("dirt").unwrap();
+        assert_eq!(registry.face_uv(log, Face::Up), UvRect::default());
+
+        let tile = || image::RgbaImage::new(16, 16);
+        let atlas = TextureAtlas::pack(vec![
+            ("log_side".to_owned(), tile()),
+            ("log_top".to_owned(), tile()),
+        ]);
+        registry.set_atlas(&atlas);
+        let rings = atlas.get("log_top").unwrap();
+        assert_eq!(registry.face_uv(log, Face::Up), rings);
+        assert_eq!(registry.face_uv(log, Face::Left), atlas.get("log_side").unwrap());
+        assert_eq!(registry.face_uv(x_log, Face::Left), rings);
+
+        // Missing textures and faces without one are left white
+        assert_eq!(registry.face_uv(dirt, Face::Up), atlas.white());
+        assert_eq!(registry.face_uv(Block::AIR, Face::Up), atlas.white());
+    }
+
+    #[test]
+    #[should_panic]
+    fn duplicated_names() {
+        let mut registry = BlockRegistry::new();
+        registry.register(BlockType::new("dirt"));
+        registry.register(BlockType::new("dirt"));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::chunk::Face;
+
+/// Part of a side of the block, as the min and max corners on the two axes
+/// of its plane
+pub type SideArea = ([f32; 2], [f32; 2]);
+
+/// The whole side of a block
+pub const FULL_SIDE: SideArea = ([0.0; 2], [1.0; 2]);
+
+/// An axis aligned box of a block model, in blocks from the lowest corner of
+/// the block, a full block goes from 0 to 1 on every axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl ModelBox {
+    pub const fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+        Self {
+            min,
+            max,
+        }
+    }
+
+    /// Whether the `face` of the box lies on that side of the block, only
+    /// those faces can be hidden by the neighbor
+    pub fn touches_side(&self, face: Face) -> bool {
+        let (axis, positive) = face_axis(face);
+        if positive {
+            self.max[axis] == 1.0
+        } else {
+            self.min[axis] == 0.0
+        }
+    }
+
+    /// The box turned a quarter around the vertical axis, the same turn that
+    /// takes `Face::Front` to `Face::Right`
+    fn turned(&self) -> Self {
+        Self {
+            min: [1.0 - self.max[2], self.min[1], self.min[0]],
+            max: [1.0 - self.min[2], self.max[1], self.max[0]],
+        }
+    }
+
+    /// Area the box covers when looked at through `face`
+    pub fn area(&self, face: Face) -> SideArea {
+        let (axis, _) = face_axis(face);
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        ([self.min[u], self.min[v]], [self.max[u], self.max[v]])
+    }
+}
+
+/// Axis the face looks along, and whether it looks to the positive side
+fn face_axis(face: Face) -> (usize, bool) {
+    let normal = face.normal();
+    let axis = normal.iter().position(|n| *n != 0).unwrap();
+    (axis, normal[axis] > 0)
+}
+
+/// Shape of a block, what the mesher emits for it
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockModel {
+    /// The whole block, its faces can be merged by the greedy mesher
+    Cube,
+
+    /// A list of boxes, the faces of a box on a side of the block are hidden
+    /// by a neighbor that fully covers them, the rest are always drawn
+    Boxes(Vec<ModelBox>),
+
+    /// Two quads crossing along the diagonals of the block, like the plants,
+    /// never hidden by the neighbors
+    Cross,
+}
+
+impl BlockModel {
+    /// The lower half of a block
+    pub fn slab() -> Self {
+        BlockModel::Boxes(vec![
+            ModelBox::new([0.0; 3], [1.0, 0.5, 1.0]),
+        ])
+    }
+
+    /// A slab with a step on the back half, so the stairs are climbed
+    /// walking towards `Face::Back`
+    pub fn stairs() -> Self {
+        BlockModel::Boxes(vec![
+            ModelBox::new([0.0; 3], [1.0, 0.5, 1.0]),
+            ModelBox::new([0.0, 0.5, 0.5], [1.0; 3]),
+        ])
+    }
+
+    /// The model turned `turns` quarters around the vertical axis, each turn
+    /// takes `Face::Front` to `Face::Right`
+    pub fn turned(&self, turns: usize) -> Self {
+        match self {
+            BlockModel::Boxes(boxes) => BlockModel::Boxes(
+                boxes.iter()
+                    .map(|model_box| {
+                        (0..turns % 4).fold(*model_box, |model_box, _| model_box.turned())
+                    })
+                    .collect()
+            ),
+            model => model.clone(),
+        }
+    }
+
+    /// Whether the model fills the whole side of the block at `face`
+    pub fn covers(&self, face: Face) -> bool {
+        self.covers_area(face, FULL_SIDE)
+    }
+
+    /// Whether the model fills `area` of the side of the block at `face`, so
+    /// it hides the face of the neighbor touching it there
+    pub fn covers_area(&self, face: Face, area: SideArea) -> bool {
+        let boxes = match self {
+            BlockModel::Cube => return true,
+            BlockModel::Cross => return false,
+            BlockModel::Boxes(boxes) => boxes,
+        };
+        let rects: Vec<_> = boxes.iter()
+            .filter(|model_box| model_box.touches_side(face))
+            .map(|model_box| model_box.area(face))
+            .collect();
+
+        // Split the area on every edge of the rectangles, it is covered when
+        // every piece is inside of one of them
+        let (area_min, area_max) = area;
+        let cuts = |axis: usize| {
+            let mut cuts = vec![area_min[axis], area_max[axis]];
+            for (min, max) in rects.iter() {
+                cuts.extend([min[axis], max[axis]]);
+            }
+            cuts.retain(|cut| (area_min[axis]..=area_max[axis]).contains(cut));
+            cuts.sort_by(f32::total_cmp);
+            cuts.dedup();
+            cuts
+        };
+        let (cuts_u, cuts_v) = (cuts(0), cuts(1));
+        cuts_u.windows(2).all(|u| {
+            cuts_v.windows(2).all(|v| {
+                let center = [(u[0] + u[1]) / 2.0, (v[0] + v[1]) / 2.0];
+                rects.iter().any(|(min, max)| {
+                    (0..2).all(|i| min[i] <= center[i] && center[i] <= max[i])
+                })
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn covered_sides() {
+        let slab = BlockModel::slab();
+        assert!(slab.covers(Face::Down));
+        assert!(!slab.covers(Face::Up));
+        assert!(!slab.covers(Face::Left));
+
+        // The back of the stairs is covered by the two boxes together
+        let stairs = BlockModel::stairs();
+        assert!(stairs.covers(Face::Back));
+        assert!(stairs.covers(Face::Down));
+        assert!(!stairs.covers(Face::Front));
+        assert!(!stairs.covers(Face::Up));
+        assert!(!stairs.covers(Face::Right));
+
+        assert!(BlockModel::Cube.covers(Face::Up));
+        assert!(!BlockModel::Cross.covers(Face::Up));
+
+        // Two slabs side by side hide each other
+        let side = ModelBox::new([0.0; 3], [1.0, 0.5, 1.0]).area(Face::Right);
+        assert!(slab.covers_area(Face::Left, side));
+        assert!(!slab.covers_area(Face::Left, FULL_SIDE));
+        assert!(stairs.covers_area(Face::Right, side));
+    }
+
+    #[test]
+    fn turned_models() {
+        let stairs = BlockModel::stairs();
+        let faces = [Face::Front, Face::Right, Face::Back, Face::Left];
+        for turns in 0..4 {
+            let turned = stairs.turned(turns);
+            for (i, face) in faces.iter().enumerate() {
+                assert_eq!(turned.covers(*face), i == (2 + turns) % 4);
+            }
+        }
+        assert_eq!(stairs.turned(4), stairs);
+        assert_eq!(
+            BlockModel::Boxes(vec![ModelBox::new([0.0; 3], [1.0, 1.0, 0.25])]).turned(1),
+            BlockModel::Boxes(vec![ModelBox::new([0.75, 0.0, 0.0], [1.0; 3])])
+        );
+    }
+}
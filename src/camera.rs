@@ -3,7 +3,7 @@ use cgmath::{
 };
 use winit::event::*;
 
-use crate::mouse_input::{MouseInput, self};
+use crate::mouse_input::MouseInput;
 use crate::bind_group::{GPUWrite, Uniform};
 
 const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
@@ -49,6 +49,15 @@ impl Camera {
         self.view.calc_dirs()
     }
 
+    pub fn position(&self) -> Point3<f32> {
+        self.view.position
+    }
+
+    /// Direction the camera is looking at
+    pub fn forward(&self) -> Vector3<f32> {
+        self.calc_dirs().0
+    }
+
     fn update_position<F: Fn(&mut Point3<f32>)>(&mut self, f: F) {
         f(&mut self.view.position);
     }
@@ -199,12 +208,28 @@ impl CameraController {
                     }
                 };
 
-                return true;
+                true
+            },
+            WindowEvent::MouseInput { .. } => {
+                self.mouse_input.process_window_event(event)
             },
             _ => false
         }
     }
 
+    /// Take the clicks made since the last call, as `(left, right)`, they
+    /// only count while the camera is controlled by the mouse
+    pub fn take_clicks(&mut self) -> (bool, bool) {
+        let clicks = (self.mouse_input.left_button, self.mouse_input.right_button);
+        self.mouse_input.left_button = false;
+        self.mouse_input.right_button = false;
+        if !self.grab_mouse {
+            return (false, false);
+        }
+
+        clicks
+    }
+
     pub fn process_device_event(
         &mut self,
         device_event: &DeviceEvent,
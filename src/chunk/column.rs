@@ -0,0 +1,256 @@
+use crate::biome::Biome;
+use crate::block::Block;
+
+use super::{BlockPos, Chunk, ChunkPos, Light, MAX_LIGHT};
+
+/// Side of the cubic sections a column is made of
+pub const SECTION_SIZE: usize = 16;
+
+/// Number of sections stacked on a column
+pub const COLUMN_SECTIONS: usize = 16;
+
+/// Height of the world in blocks
+pub const COLUMN_HEIGHT: usize = SECTION_SIZE * COLUMN_SECTIONS;
+
+/// A cubic piece of a column, the unit that is meshed and rendered
+pub type Section = Chunk<SECTION_SIZE, SECTION_SIZE>;
+
+/// Every block of the world with a certain `x` and `z` chunk coordinate, as a
+/// stack of sections going from `y = 0` to `y = COLUMN_HEIGHT`
+#[derive(Debug)]
+pub struct ChunkColumn {
+    chunk_pos: ChunkPos,
+    sections: Vec<Section>,
+
+    /// For each `x`, `z` the lowest `y` with only blocks that let the sky
+    /// light through above it, indexed by `z * SECTION_SIZE + x`
+    heightmap: Vec<u16>,
+
+    /// Biome of each `x`, `z`, indexed like the heightmap
+    biomes: Vec<Biome>,
+}
+
+impl ChunkColumn {
+    /// A column filled with air
+    pub fn new(chunk_pos: ChunkPos) -> Self {
+        Self {
+            chunk_pos,
+            sections: (0..COLUMN_SECTIONS as i32)
+                .map(|y| Section::new(chunk_pos.section(y)))
+                .collect(),
+            heightmap: vec![0; SECTION_SIZE * SECTION_SIZE],
+            biomes: vec![Biome::default(); SECTION_SIZE * SECTION_SIZE],
+        }
+    }
+
+    /// A column from its sections ordered from the bottom, like the ones read
+    /// from disk
+    pub fn from_sections(chunk_pos: ChunkPos, sections: Vec<Section>) -> Self {
+        assert_eq!(sections.len(), COLUMN_SECTIONS, "Wrong amount of sections");
+        for (y, section) in sections.iter().enumerate() {
+            assert_eq!(section.pos(), chunk_pos.section(y as i32), "Misplaced section");
+        }
+
+        Self {
+            chunk_pos,
+            sections,
+            heightmap: vec![0; SECTION_SIZE * SECTION_SIZE],
+            biomes: vec![Biome::default(); SECTION_SIZE * SECTION_SIZE],
+        }
+    }
+
+    pub fn pos(&self) -> ChunkPos {
+        self.chunk_pos
+    }
+
+    /// The `y`th section counting from the bottom
+    pub fn section(&self, y: i32) -> Option<&Section> {
+        self.sections.get(usize::try_from(y).ok()?)
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &Section> + '_ {
+        self.sections.iter()
+    }
+
+    /// Split a position inside the column in the section index and the
+    /// position inside that section
+    fn locate(block_pos: BlockPos) -> Option<(usize, BlockPos)> {
+        if block_pos.x >= SECTION_SIZE || block_pos.z >= SECTION_SIZE
+            || block_pos.y >= COLUMN_HEIGHT
+        {
+            return None;
+        }
+
+        Some((
+            block_pos.y / SECTION_SIZE,
+            BlockPos::new(block_pos.x, block_pos.y % SECTION_SIZE, block_pos.z)
+        ))
+    }
+
+    /// Get a block, `block_pos.y` goes over the whole height of the column
+    pub fn get_block(&self, block_pos: BlockPos) -> Option<Block> {
+        let (section, block_pos) = Self::locate(block_pos)?;
+        self.sections[section].get_block(block_pos)
+    }
+
+    /// Replace a block, `block_pos.y` goes over the whole height of the column
+    pub fn place_block(
+        &mut self,
+        block_pos: BlockPos,
+        block: Block
+    ) -> Option<()> {
+        let (section, block_pos) = Self::locate(block_pos)?;
+        self.sections[section].place_block(block_pos, block)
+    }
+
+    pub fn biome(&self, x: usize, z: usize) -> Biome {
+        self.biomes[z * SECTION_SIZE + x]
+    }
+
+    pub fn set_biome(&mut self, x: usize, z: usize, biome: Biome) {
+        self.biomes[z * SECTION_SIZE + x] = biome;
+    }
+
+    /// Biome of every `x`, `z`, indexed by `z * SECTION_SIZE + x`
+    pub fn biomes(&self) -> &[Biome] {
+        &self.biomes
+    }
+
+    /// Height of the sky light blocking blocks at `x`, `z` as computed by the
+    /// last `reset_light` or `update_height`
+    pub fn height(&self, x: usize, z: usize) -> usize {
+        self.heightmap[z * SECTION_SIZE + x] as usize
+    }
+
+    /// Recompute the height at `x`, `z` looking down from `top`, every block
+    /// above it must be known to let the light through
+    pub fn update_height(
+        &mut self,
+        x: usize,
+        z: usize,
+        top: usize,
+        blocks_light: impl Fn(Block) -> bool
+    ) {
+        let height = (0..top.min(COLUMN_HEIGHT))
+            .rev()
+            .find(|y| {
+                self.get_block(BlockPos::new(x, *y, z)).is_some_and(&blocks_light)
+            })
+            .map_or(0, |y| y + 1);
+        self.heightmap[z * SECTION_SIZE + x] = height as u16;
+    }
+
+    /// Recompute the whole heightmap and reset the light to just the direct
+    /// sky light, everything under the heightmap is left dark
+    pub fn reset_light(&mut self, blocks_light: impl Fn(Block) -> bool) {
+        // Sections of a single block that lets the light through are skipped
+        // whole
+        let top = self.sections.iter()
+            .rposition(|section| {
+                section.single_block().is_none_or(&blocks_light)
+            })
+            .map_or(0, |y| (y + 1) * SECTION_SIZE);
+        for x in 0..SECTION_SIZE {
+            for z in 0..SECTION_SIZE {
+                self.update_height(x, z, top, &blocks_light);
+            }
+        }
+
+        let heightmap = &self.heightmap;
+        for section in self.sections.iter_mut() {
+            let bottom = section.pos().y as usize * SECTION_SIZE;
+            if heightmap.iter().all(|height| *height as usize <= bottom) {
+                section.fill_light(Light::SKY);
+                continue;
+            }
+
+            section.fill_light(Light::new(0, 0));
+            for x in 0..SECTION_SIZE {
+                for z in 0..SECTION_SIZE {
+                    let height = heightmap[z * SECTION_SIZE + x] as usize;
+                    for y in height.max(bottom)..bottom + SECTION_SIZE {
+                        section.set_sky_light(BlockPos::new(x, y - bottom, z), MAX_LIGHT);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Get the light at a block, `block_pos.y` goes over the whole height of
+    /// the column
+    pub fn light(&self, block_pos: BlockPos) -> Option<Light> {
+        let (section, block_pos) = Self::locate(block_pos)?;
+        self.sections[section].light(block_pos)
+    }
+
+    pub fn set_sky_light(&mut self, block_pos: BlockPos, level: u8) -> Option<()> {
+        let (section, block_pos) = Self::locate(block_pos)?;
+        self.sections[section].set_sky_light(block_pos, level)
+    }
+
+    pub fn set_block_light(&mut self, block_pos: BlockPos, level: u8) -> Option<()> {
+        let (section, block_pos) = Self::locate(block_pos)?;
+        self.sections[section].set_block_light(block_pos, level)
+    }
+
+    /// Shrink the block storage of every section after a batch of edits
+    pub fn compact(&mut self) {
+        for section in self.sections.iter_mut() {
+            section.compact();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+    use crate::chunk::SectionPos;
+
+    #[test]
+    fn blocks_go_to_their_section() {
+        let mut column = ChunkColumn::new(ChunkPos::new(2, -3));
+        let stone = Block::from_id(2);
+        column.place_block(BlockPos::new(1, 0, 1), stone).unwrap();
+        column.place_block(BlockPos::new(1, 40, 1), stone).unwrap();
+        column.place_block(BlockPos::new(15, COLUMN_HEIGHT - 1, 15), stone).unwrap();
+        assert_eq!(column.place_block(BlockPos::new(0, COLUMN_HEIGHT, 0), stone), None);
+
+        assert_eq!(column.get_block(BlockPos::new(1, 40, 1)), Some(stone));
+        assert_eq!(column.get_block(BlockPos::new(1, 41, 1)), Some(Block::AIR));
+        assert_eq!(
+            column.section(2).unwrap().get_block(BlockPos::new(1, 8, 1)),
+            Some(stone)
+        );
+        assert_eq!(column.section(15).unwrap().pos(), SectionPos::new(2, 15, -3));
+
+        let not_empty: Vec<i32> = column.sections()
+            .filter(|section| !section.is_empty())
+            .map(|section| section.pos().y)
+            .collect();
+        assert_eq!(not_empty, vec![0, 2, 15]);
+    }
+
+    #[test]
+    fn heightmap_and_sky_light() {
+        let mut column = ChunkColumn::new(ChunkPos::new(0, 0));
+        let (stone, glass) = (Block::from_id(2), Block::from_id(3));
+        let blocks_light = |block: Block| block == stone;
+        column.place_block(BlockPos::new(1, 40, 1), stone).unwrap();
+        column.place_block(BlockPos::new(1, 20, 1), stone).unwrap();
+        column.place_block(BlockPos::new(2, 70, 2), glass).unwrap();
+        column.reset_light(blocks_light);
+
+        assert_eq!(column.height(1, 1), 41);
+        assert_eq!(column.height(2, 2), 0);
+        assert_eq!(column.height(0, 0), 0);
+        let light = |y: usize| column.sections[y / 16].light(BlockPos::new(1, y % 16, 1)).unwrap();
+        assert_eq!(light(41), Light::SKY);
+        assert_eq!(light(39), Light::new(0, 0));
+        assert_eq!(light(5), Light::new(0, 0));
+
+        column.place_block(BlockPos::new(1, 40, 1), Block::AIR).unwrap();
+        column.update_height(1, 1, 41, blocks_light);
+        assert_eq!(column.height(1, 1), 21);
+    }
+}
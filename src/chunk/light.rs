@@ -0,0 +1,76 @@
+/// Brightest light level, the sky and the strongest light sources
+pub const MAX_LIGHT: u8 = 15;
+
+/// Light reaching a block, from the sky and from light emitting blocks, both
+/// go from 0 to `MAX_LIGHT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Light {
+    pub sky: u8,
+    pub block: u8,
+}
+
+impl Light {
+    /// Light of the blocks open to the sky
+    pub const SKY: Self = Light {
+        sky: MAX_LIGHT,
+        block: 0,
+    };
+
+    pub const fn new(sky: u8, block: u8) -> Self {
+        Self {
+            sky,
+            block,
+        }
+    }
+}
+
+/// Fixed size array of 4 bit values, two per byte
+#[derive(Debug, Clone)]
+pub struct NibbleArray {
+    data: Vec<u8>,
+}
+
+impl NibbleArray {
+    pub fn new(len: usize, value: u8) -> Self {
+        Self {
+            data: vec![value * 0x11; len.div_ceil(2)],
+        }
+    }
+
+    pub fn get(&self, index: usize) -> u8 {
+        (self.data[index / 2] >> (index % 2 * 4)) & 0xf
+    }
+
+    pub fn set(&mut self, index: usize, value: u8) {
+        let shift = index % 2 * 4;
+        let byte = &mut self.data[index / 2];
+        *byte = (*byte & !(0xf << shift)) | ((value & 0xf) << shift);
+    }
+
+    pub fn fill(&mut self, value: u8) {
+        self.data.fill(value * 0x11);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn nibbles() {
+        let mut array = NibbleArray::new(5, 3);
+        assert_eq!((0..5).map(|i| array.get(i)).collect::<Vec<_>>(), vec![3; 5]);
+
+        array.set(1, 15);
+        array.set(2, 7);
+        array.set(4, 0);
+        assert_eq!(
+            (0..5).map(|i| array.get(i)).collect::<Vec<_>>(),
+            vec![3, 15, 7, 3, 0]
+        );
+
+        array.fill(9);
+        assert_eq!((0..5).map(|i| array.get(i)).collect::<Vec<_>>(), vec![9; 5]);
+    }
+}
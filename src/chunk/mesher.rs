@@ -0,0 +1,834 @@
+use crate::atlas::UvRect;
+use crate::block::{Block, BlockRegistry, Opacity};
+use crate::block_model::{BlockModel, ModelBox, SideArea, FULL_SIDE};
+use crate::mesh::{FaceShading, Mesh, MeshBuilder};
+
+use super::{BlockPos, ChunkNeighborhood, Face, Light};
+
+/// Strategy used to turn the visible faces of a chunk into quads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshingMode {
+    /// One quad per visible face
+    Naive,
+
+    /// Merge coplanar faces of the same block into the biggest rectangles
+    /// possible, a flat floor becomes a single quad
+    Greedy,
+}
+
+/// Everything that has to match for two faces to be merged into one quad
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FaceStyle {
+    block: Block,
+    color: [f32; 3],
+    alpha: f32,
+    texture: UvRect,
+    ao: [u8; 4],
+    light: [[f32; 2]; 4],
+}
+
+impl FaceStyle {
+    /// Faces with different occlusion or light on each corner can't be
+    /// stretched without smearing them over the whole quad
+    fn is_mergeable(&self) -> bool {
+        self.ao.iter().all(|ao| *ao == self.ao[0])
+            && self.light.iter().all(|light| *light == self.light[0])
+    }
+
+    fn shading(&self) -> FaceShading {
+        FaceShading::new(self.color)
+            .with_ao(self.ao)
+            .with_light(self.light)
+            .with_alpha(self.alpha)
+            .with_texture(self.texture)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct VisibleFace {
+    face: Face,
+    position: BlockPos,
+    style: FaceStyle,
+
+    /// Corners of a face that isn't a whole side of the block, in the order
+    /// of the face mesh and relative to the center of the block. These are
+    /// never merged
+    corners: Option<[[f32; 3]; 4]>,
+
+    /// Goes to the transparent mesh, drawn after everything else
+    transparent: bool,
+}
+
+impl VisibleFace {
+    fn mesh(&self) -> Mesh {
+        match self.corners {
+            Some(corners) => Mesh::quad(corners),
+            None => self.face.mesh(),
+        }
+    }
+}
+
+pub struct VoxelMesh {
+    mode: MeshingMode,
+
+    /// Size of the last serialized chunk as `[L, H, L]`
+    dims: [usize; 3],
+
+    /// Faces that can be seen, found by `serialize_chunk`
+    visible: Vec<VisibleFace>,
+
+    /// Direction of each quad of the last mesh in order, what the shader reads
+    /// from the faces storage
+    faces: Vec<Face>,
+
+    /// Same for the last transparent mesh
+    transparent_faces: Vec<Face>,
+}
+
+/// Whether the `face` of `block` that touches `neighbor` on `area` of that
+/// side can be seen, a missing neighbor (out of the loaded chunks) never
+/// hides anything and only the neighbors covering the whole face do
+fn is_face_visible(
+    registry: &BlockRegistry,
+    block: Block,
+    face: Face,
+    area: SideArea,
+    neighbor: Option<Block>
+) -> bool {
+    let Some(neighbor) = neighbor else {
+        return true;
+    };
+    if !registry.model(neighbor).covers_area(face.opposite(), area) {
+        return true;
+    }
+    match registry.opacity(neighbor) {
+        Opacity::Opaque => false,
+        // Don't render the inner faces of a group of the same transparent
+        // block, like a glass wall
+        Opacity::Transparent => neighbor != block,
+        Opacity::Invisible => true
+    }
+}
+
+/// Ambient occlusion level and smoothed light of each vertex of a face, from
+/// the blocks touching the vertex on the layer in front of the face
+fn face_corners<const L: usize, const H: usize>(
+    neighborhood: &ChunkNeighborhood<L, H>,
+    registry: &BlockRegistry,
+    block_pos: BlockPos,
+    face: Face
+) -> ([u8; 4], [[f32; 2]; 4]) {
+    let [nx, ny, nz] = face.normal();
+    let front = [
+        block_pos.x as i32 + nx,
+        block_pos.y as i32 + ny,
+        block_pos.z as i32 + nz
+    ];
+    let at = |offset: [i32; 3]| {
+        [front[0] + offset[0], front[1] + offset[1], front[2] + offset[2]]
+    };
+    let occludes = |offset: [i32; 3]| {
+        let [x, y, z] = at(offset);
+        neighborhood
+            .block_at(x, y, z)
+            .is_some_and(|block| registry.is_full_cube(block))
+    };
+    // Out of the loaded chunks everything is open to the sky
+    let light = |offset: [i32; 3]| {
+        let [x, y, z] = at(offset);
+        neighborhood.light_at(x, y, z).unwrap_or(Light::SKY)
+    };
+
+    let (_, u, v) = face_axes(face);
+    let mut ao = [3; 4];
+    let mut lights = [[0.0; 2]; 4];
+    for (i, position) in face.mesh().positions().enumerate() {
+        let mut side1 = [0; 3];
+        side1[u] = position[u].signum() as i32;
+        let mut side2 = [0; 3];
+        side2[v] = position[v].signum() as i32;
+        let mut corner = side1;
+        corner[v] = side2[v];
+
+        let (side1_occludes, side2_occludes, corner_occludes) =
+            (occludes(side1), occludes(side2), occludes(corner));
+        ao[i] = if side1_occludes && side2_occludes {
+            0
+        } else {
+            3 - side1_occludes as u8 - side2_occludes as u8 - corner_occludes as u8
+        };
+
+        // Average the light of the blocks around the vertex the light can
+        // get through, the corner can't be reached past two solid sides
+        let reachable = [
+            Some([0; 3]),
+            (!side1_occludes).then_some(side1),
+            (!side2_occludes).then_some(side2),
+            (!corner_occludes && ao[i] != 0).then_some(corner),
+        ];
+        let (sky, block, count) = reachable.iter()
+            .flatten()
+            .map(|offset| light(*offset))
+            .fold((0, 0, 0), |(sky, block, count), light| {
+                (sky + light.sky as u32, block + light.block as u32, count + 1)
+            });
+        lights[i] = [sky as f32 / count as f32, block as f32 / count as f32];
+    }
+
+    (ao, lights)
+}
+
+/// Corners of the `face` of a box of a model, in the order of the face mesh
+fn box_corners(model_box: &ModelBox, face: Face) -> [[f32; 3]; 4] {
+    let mut corners = [[0.0; 3]; 4];
+    for (corner, position) in corners.iter_mut().zip(face.mesh().positions()) {
+        for axis in 0..3 {
+            let side = if position[axis] < 0.0 {
+                model_box.min[axis]
+            } else {
+                model_box.max[axis]
+            };
+            corner[axis] = side - 0.5;
+        }
+    }
+
+    corners
+}
+
+/// The two quads of a `BlockModel::Cross`, along the diagonals of the block
+const CROSS_CORNERS: [[[f32; 3]; 4]; 2] = [
+    [[-0.5, -0.5, -0.5], [0.5, 0.5, 0.5], [-0.5, 0.5, -0.5], [0.5, -0.5, 0.5]],
+    [[-0.5, -0.5, 0.5], [0.5, 0.5, -0.5], [-0.5, 0.5, 0.5], [0.5, -0.5, -0.5]],
+];
+
+/// Axis the face looks along, followed by the two axes of its plane
+fn face_axes(face: Face) -> (usize, usize, usize) {
+    match face {
+        Face::Left | Face::Right => (0, 1, 2),
+        Face::Up | Face::Down => (1, 0, 2),
+        Face::Front | Face::Back => (2, 0, 1),
+    }
+}
+
+impl VoxelMesh {
+    pub fn with_mode(mode: MeshingMode) -> Self {
+        Self {
+            mode,
+            dims: [0; 3],
+            visible: Vec::new(),
+            faces: Vec::new(),
+            transparent_faces: Vec::new(),
+        }
+    }
+
+    pub fn serialize_chunk<
+        const L: usize,
+        const H: usize
+    >(
+        &mut self,
+        neighborhood: &ChunkNeighborhood<L, H>,
+        registry: &BlockRegistry
+    ) {
+        let chunk = neighborhood.center();
+        self.dims = [L, H, L];
+        self.visible.clear();
+
+        // Nothing to see on an all air chunk
+        if let Some(block) = chunk.single_block() {
+            if registry.opacity(block) == Opacity::Invisible {
+                return;
+            }
+        }
+
+        for block in chunk.iter() {
+            if registry.opacity(*block) == Opacity::Invisible {
+                continue;
+            }
+
+            let block_type = registry.get(*block);
+            let biome = neighborhood.biome(block.block_pos.x, block.block_pos.z);
+            let style = |face: Face, ao: [u8; 4], light: [[f32; 2]; 4]| FaceStyle {
+                block: *block,
+                color: {
+                    let appearance = registry.face(*block, face);
+                    let tint = appearance.tint.map_or([1.0; 3], |tint| biome.tint(tint));
+                    std::array::from_fn(|i| appearance.color[i] * tint[i])
+                },
+                alpha: block_type.alpha,
+                texture: registry.face_uv(*block, face),
+                ao,
+                light,
+            };
+            // Only the see through blocks that really let some of the
+            // background through have to be blended
+            let transparent = block_type.opacity == Opacity::Transparent
+                && block_type.alpha < 1.0;
+            let BlockPos { x, y, z } = block.block_pos;
+
+            match registry.model(*block) {
+                BlockModel::Cube => {
+                    for face in Face::ALL {
+                        let neighbor = neighborhood.neighbor(block.block_pos, face);
+                        if !is_face_visible(registry, *block, face, FULL_SIDE, neighbor) {
+                            continue;
+                        }
+                        let (ao, light) =
+                            face_corners(neighborhood, registry, block.block_pos, face);
+                        self.visible.push(VisibleFace {
+                            face,
+                            position: block.block_pos,
+                            style: style(face, ao, light),
+                            corners: None,
+                            transparent,
+                        });
+                    }
+                }
+                BlockModel::Boxes(boxes) => {
+                    for model_box in boxes {
+                        for face in Face::ALL {
+                            let neighbor = neighborhood.neighbor(block.block_pos, face);
+                            if model_box.touches_side(face)
+                                && !is_face_visible(
+                                    registry, *block, face,
+                                    model_box.area(face),
+                                    neighbor
+                                )
+                            {
+                                continue;
+                            }
+                            let (ao, light) =
+                                face_corners(neighborhood, registry, block.block_pos, face);
+                            self.visible.push(VisibleFace {
+                                face,
+                                position: block.block_pos,
+                                style: style(face, ao, light),
+                                corners: Some(box_corners(model_box, face)),
+                                transparent,
+                            });
+                        }
+                    }
+                }
+                BlockModel::Cross => {
+                    // Lit by the light of its own block, nothing occludes it
+                    let light = neighborhood
+                        .light_at(x as i32, y as i32, z as i32)
+                        .unwrap_or(Light::SKY);
+                    let light = [light.sky as f32, light.block as f32];
+                    let quads = [Face::Left, Face::Right].into_iter().zip(CROSS_CORNERS);
+                    for (face, corners) in quads {
+                        self.visible.push(VisibleFace {
+                            face,
+                            position: block.block_pos,
+                            style: style(face, [3; 4], [light; 4]),
+                            corners: Some(corners),
+                            transparent,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the last serialized chunk has no visible face, like one
+    /// buried under the ground
+    pub fn is_empty(&self) -> bool {
+        self.visible.is_empty()
+    }
+
+    pub fn faces(&self) -> &[u32] {
+        Self::face_ids(&self.faces)
+    }
+
+    pub fn transparent_faces(&self) -> &[u32] {
+        Self::face_ids(&self.transparent_faces)
+    }
+
+    fn face_ids(faces: &[Face]) -> &[u32] {
+        unsafe {
+            std::slice::from_raw_parts(
+                faces.as_ptr() as *const u32,
+                faces.len()
+            )
+        }
+    }
+
+    /// Convert the serialized opaque faces to a mesh with the selected mode
+    pub fn mesh(&mut self) -> Mesh {
+        let mut faces = std::mem::take(&mut self.faces);
+        let mesh = self.build(false, &mut faces);
+        self.faces = faces;
+        mesh
+    }
+
+    /// Convert the serialized transparent faces to a mesh with the selected
+    /// mode, it has to be sorted before drawing
+    pub fn transparent_mesh(&mut self) -> Mesh {
+        let mut faces = std::mem::take(&mut self.transparent_faces);
+        let mesh = self.build(true, &mut faces);
+        self.transparent_faces = faces;
+        mesh
+    }
+
+    fn build(&self, transparent: bool, faces: &mut Vec<Face>) -> Mesh {
+        faces.clear();
+        let mut builder = MeshBuilder::new();
+        let visible = self.visible.iter()
+            .filter(|visible| visible.transparent == transparent);
+        match self.mode {
+            MeshingMode::Naive => {
+                for visible in visible {
+                    builder.push_face(
+                        visible.mesh(),
+                        visible.position,
+                        [1; 3],
+                        visible.style.shading()
+                    );
+                    faces.push(visible.face);
+                }
+            }
+            MeshingMode::Greedy => {
+                for face in Face::ALL {
+                    self.greedy_face(face, transparent, &mut builder, faces);
+                }
+                // The faces of the models are left as they are
+                for visible in visible.filter(|visible| visible.corners.is_some()) {
+                    builder.push_face(
+                        visible.mesh(),
+                        visible.position,
+                        [1; 3],
+                        visible.style.shading()
+                    );
+                    faces.push(visible.face);
+                }
+            }
+        }
+
+        builder.build()
+    }
+
+    /// Merge all the visible faces looking towards `face`, slice by slice
+    fn greedy_face(
+        &self,
+        face: Face,
+        transparent: bool,
+        builder: &mut MeshBuilder,
+        faces: &mut Vec<Face>
+    ) {
+        let dims = self.dims;
+        let (n, u, v) = face_axes(face);
+        let index = |pos: [usize; 3]| (pos[1] * dims[2] + pos[2]) * dims[0] + pos[0];
+
+        // Lookup of the visible faces by position
+        let mut grid: Vec<Option<usize>> = vec![None; dims[0] * dims[1] * dims[2]];
+        for (i, visible) in self.visible.iter().enumerate() {
+            if visible.face == face
+                && visible.transparent == transparent
+                && visible.corners.is_none()
+            {
+                let BlockPos { x, y, z } = visible.position;
+                grid[index([x, y, z])] = Some(i);
+            }
+        }
+
+        let style_at = |grid: &[Option<usize>], pos: [usize; 3]| {
+            grid[index(pos)].map(|i| self.visible[i].style)
+        };
+        let point = |s: usize, a: usize, b: usize| {
+            let mut pos = [0; 3];
+            pos[n] = s;
+            pos[u] = a;
+            pos[v] = b;
+            pos
+        };
+
+        for s in 0..dims[n] {
+            for b in 0..dims[v] {
+                for a in 0..dims[u] {
+                    let Some(style) = style_at(&grid, point(s, a, b)) else {
+                        continue;
+                    };
+
+                    // Grow along the first axis, then add rows while the
+                    // whole row matches
+                    let mergeable = style.is_mergeable();
+                    let mut width = 1;
+                    while mergeable
+                        && a + width < dims[u]
+                        && style_at(&grid, point(s, a + width, b)) == Some(style)
+                    {
+                        width += 1;
+                    }
+                    let mut height = 1;
+                    while mergeable
+                        && b + height < dims[v]
+                        && (a..a + width).all(|a| {
+                            style_at(&grid, point(s, a, b + height)) == Some(style)
+                        })
+                    {
+                        height += 1;
+                    }
+
+                    for bb in b..b + height {
+                        for aa in a..a + width {
+                            grid[index(point(s, aa, bb))] = None;
+                        }
+                    }
+
+                    let [x, y, z] = point(s, a, b);
+                    let mut extent = [1; 3];
+                    extent[u] = width;
+                    extent[v] = height;
+                    builder.push_face(
+                        face.mesh(),
+                        BlockPos::new(x, y, z),
+                        extent,
+                        style.shading()
+                    );
+                    faces.push(face);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+    use crate::biome::{Biome, Tint};
+    use crate::chunk::{Chunk, SectionPos};
+
+    #[test]
+    fn quad_mesh() {
+        let registry = BlockRegistry::with_default_blocks();
+        let dirt = registry.by_name("dirt").unwrap();
+        let color = registry.get(dirt).face(Face::Up).color;
+        let mut chunk: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.place_block(BlockPos::new(0, 0, 0), dirt).unwrap();
+
+        let mut mesher = VoxelMesh::with_mode(MeshingMode::Naive);
+        mesher.serialize_chunk(&ChunkNeighborhood::new(&chunk), &registry);
+
+        assert_eq!(
+            mesher.mesh(),
+            {
+                let mut builder = MeshBuilder::new();
+                builder.push_face(Mesh::FRONT_FACE, BlockPos::new(0, 0, 0), [1; 3], FaceShading::new(color));
+                builder.push_face(Mesh::BACK_FACE, BlockPos::new(0, 0, 0), [1; 3], FaceShading::new(color));
+                builder.push_face(Mesh::UP_FACE, BlockPos::new(0, 0, 0), [1; 3], FaceShading::new(color));
+                builder.push_face(Mesh::DOWN_FACE, BlockPos::new(0, 0, 0), [1; 3], FaceShading::new(color));
+                builder.push_face(Mesh::LEFT_FACE, BlockPos::new(0, 0, 0), [1; 3], FaceShading::new(color));
+                builder.push_face(Mesh::RIGHT_FACE, BlockPos::new(0, 0, 0), [1; 3], FaceShading::new(color));
+                builder.build()
+            }
+        );
+
+        assert_eq!(
+            mesher.faces,
+            vec![
+                Face::Front,
+                Face::Back,
+                Face::Up,
+                Face::Down,
+                Face::Left,
+                Face::Right,
+            ]
+        );
+    }
+
+    #[test]
+    fn oriented_blocks() {
+        let registry = BlockRegistry::with_default_blocks();
+        let log = registry.parse("log[axis=x]").unwrap();
+        let rings = registry.get(log).face(Face::Up).color;
+        let bark = registry.get(log).face(Face::Front).color;
+        let mut chunk: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.place_block(BlockPos::new(0, 0, 0), log).unwrap();
+
+        let mut mesher = VoxelMesh::with_mode(MeshingMode::Naive);
+        mesher.serialize_chunk(&ChunkNeighborhood::new(&chunk), &registry);
+        let colors: Vec<(Face, [f32; 3])> = mesher.visible.iter()
+            .map(|visible| (visible.face, visible.style.color))
+            .collect();
+        assert_eq!(
+            colors,
+            vec![
+                (Face::Front, bark),
+                (Face::Back, bark),
+                (Face::Up, bark),
+                (Face::Down, bark),
+                (Face::Left, rings),
+                (Face::Right, rings),
+            ]
+        );
+    }
+
+    #[test]
+    fn biome_tints() {
+        let registry = BlockRegistry::with_default_blocks();
+        let grass = registry.by_name("grass").unwrap();
+        let soil = registry.get(grass).face(Face::Front).color;
+        let mut chunk: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.place_block(BlockPos::new(0, 0, 0), grass).unwrap();
+        chunk.place_block(BlockPos::new(1, 0, 1), grass).unwrap();
+        let biomes = [Biome::Desert, Biome::Plains, Biome::Plains, Biome::Forest];
+
+        // Only the top is tinted, each block by the biome of its column
+        let mut mesher = VoxelMesh::with_mode(MeshingMode::Naive);
+        mesher.serialize_chunk(&ChunkNeighborhood::new(&chunk).with_biomes(&biomes), &registry);
+        let colors: Vec<(BlockPos, Face, [f32; 3])> = mesher.visible.iter()
+            .filter(|visible| matches!(visible.face, Face::Up | Face::Front))
+            .map(|visible| (visible.position, visible.face, visible.style.color))
+            .collect();
+        assert_eq!(
+            colors,
+            vec![
+                (BlockPos::new(0, 0, 0), Face::Front, soil),
+                (BlockPos::new(0, 0, 0), Face::Up, Biome::Desert.tint(Tint::Grass)),
+                (BlockPos::new(1, 0, 1), Face::Front, soil),
+                (BlockPos::new(1, 0, 1), Face::Up, Biome::Forest.tint(Tint::Grass)),
+            ]
+        );
+    }
+
+    #[test]
+    fn border_faces_are_culled() {
+        let registry = BlockRegistry::with_default_blocks();
+        let dirt = registry.by_name("dirt").unwrap();
+        let glass = registry.by_name("glass").unwrap();
+        let mut left: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        let mut right: Chunk<2, 2> = Chunk::new(SectionPos::new(1, 0, 0));
+        left.place_block(BlockPos::new(1, 0, 0), dirt).unwrap();
+        left.place_block(BlockPos::new(1, 1, 0), dirt).unwrap();
+        right.place_block(BlockPos::new(0, 0, 0), dirt).unwrap();
+        right.place_block(BlockPos::new(0, 1, 0), glass).unwrap();
+
+        let mut mesher = VoxelMesh::with_mode(MeshingMode::Naive);
+        let neighborhood = ChunkNeighborhood::new(&left)
+            .with_neighbor(1, 0, 0, Some(&right));
+        mesher.serialize_chunk(&neighborhood, &registry);
+
+        // The dirt on the border is hidden by the dirt of the other chunk,
+        // but not by the glass
+        let right_faces: Vec<_> = mesher.visible.iter()
+            .filter(|visible| visible.face == Face::Right)
+            .map(|visible| visible.position)
+            .collect();
+        assert_eq!(right_faces, vec![BlockPos::new(1, 1, 0)]);
+
+        // Without the neighbor both faces are emitted
+        mesher.serialize_chunk(&ChunkNeighborhood::new(&left), &registry);
+        assert_eq!(
+            mesher.visible.iter()
+                .filter(|visible| visible.face == Face::Right)
+                .count(),
+            2
+        );
+    }
+
+    /// Mesh a chunk with both modes and return the quad counts
+    fn quad_counts(chunk: &Chunk<16, 16>, registry: &BlockRegistry) -> (usize, usize) {
+        let mut counts = [0; 2];
+        for (i, mode) in [MeshingMode::Naive, MeshingMode::Greedy].into_iter().enumerate() {
+            let mut mesher = VoxelMesh::with_mode(mode);
+            mesher.serialize_chunk(&ChunkNeighborhood::new(chunk), registry);
+            let mesh = mesher.mesh();
+            assert_eq!(mesh.indices_count() as usize, mesher.faces.len() * 6);
+            counts[i] = mesher.faces.len();
+        }
+
+        (counts[0], counts[1])
+    }
+
+    #[test]
+    fn greedy_floor() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+        let mut chunk = Chunk::new(SectionPos::new(0, 0, 0));
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.place_block(BlockPos::new(x, 0, z), stone).unwrap();
+            }
+        }
+
+        // Top and bottom per block plus the 4 sides of the border
+        assert_eq!(quad_counts(&chunk, &registry), (16 * 16 * 2 + 16 * 4, 6));
+    }
+
+    #[test]
+    fn greedy_doesnt_merge_different_blocks() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+        let dirt = registry.by_name("dirt").unwrap();
+        let mut chunk = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.place_block(BlockPos::new(0, 0, 0), stone).unwrap();
+        chunk.place_block(BlockPos::new(1, 0, 0), dirt).unwrap();
+
+        // Only the outer left and right faces are alone
+        assert_eq!(quad_counts(&chunk, &registry), (10, 10));
+
+        chunk.place_block(BlockPos::new(1, 0, 0), stone).unwrap();
+        assert_eq!(quad_counts(&chunk, &registry), (10, 6));
+    }
+
+    #[test]
+    fn greedy_shapes() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+
+        // A full 16x16x16 cube is a cube
+        let mut chunk = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.fill(stone);
+        assert_eq!(quad_counts(&chunk, &registry), (16 * 16 * 6, 6));
+
+        // An L on the floor: the top can be covered with two rectangles
+        let mut chunk = Chunk::new(SectionPos::new(0, 0, 0));
+        for x in 0..4 {
+            chunk.place_block(BlockPos::new(x, 0, 0), stone).unwrap();
+        }
+        for z in 1..4 {
+            chunk.place_block(BlockPos::new(0, 0, z), stone).unwrap();
+        }
+        let (_, greedy) = quad_counts(&chunk, &registry);
+        // 2 top, 2 bottom, and 6 walls around the L, the two walls of the
+        // inner corner have their first face occluded so it stays apart
+        assert_eq!(greedy, 2 + 2 + 6 + 2);
+    }
+
+    #[test]
+    fn greedy_quads_cover_the_faces() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+        let mut chunk: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.place_block(BlockPos::new(0, 0, 0), stone).unwrap();
+        chunk.place_block(BlockPos::new(1, 0, 0), stone).unwrap();
+
+        let mut mesher = VoxelMesh::with_mode(MeshingMode::Greedy);
+        mesher.serialize_chunk(&ChunkNeighborhood::new(&chunk), &registry);
+        let mesh = mesher.mesh();
+
+        let color = registry.get(stone).face(Face::Front).color;
+        let mut builder = MeshBuilder::new();
+        builder.push_face(Mesh::FRONT_FACE, BlockPos::new(0, 0, 0), [2, 1, 1], FaceShading::new(color));
+        builder.push_face(Mesh::BACK_FACE, BlockPos::new(0, 0, 0), [2, 1, 1], FaceShading::new(color));
+        builder.push_face(Mesh::UP_FACE, BlockPos::new(0, 0, 0), [2, 1, 1], FaceShading::new(color));
+        builder.push_face(Mesh::DOWN_FACE, BlockPos::new(0, 0, 0), [2, 1, 1], FaceShading::new(color));
+        builder.push_face(Mesh::LEFT_FACE, BlockPos::new(0, 0, 0), [1; 3], FaceShading::new(color));
+        builder.push_face(Mesh::RIGHT_FACE, BlockPos::new(1, 0, 0), [1; 3], FaceShading::new(color));
+        assert_eq!(mesh, builder.build());
+    }
+
+    #[test]
+    fn ambient_occlusion() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+        let glass = registry.by_name("glass").unwrap();
+        let mut chunk: Chunk<4, 4> = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.place_block(BlockPos::new(1, 0, 1), stone).unwrap();
+
+        let neighborhood = ChunkNeighborhood::new(&chunk);
+        let ao = |registry| face_corners(&neighborhood, registry, BlockPos::new(1, 0, 1), Face::Up).0;
+        assert_eq!(ao(&registry), [3, 3, 3, 3]);
+
+        // A wall on the right of the block darkens the right vertices, the
+        // up face vertices are [(-x, +z), (+x, -z), (-x, -z), (+x, +z)]
+        chunk.place_block(BlockPos::new(2, 1, 1), stone).unwrap();
+        let neighborhood = ChunkNeighborhood::new(&chunk);
+        let ao = |registry| face_corners(&neighborhood, registry, BlockPos::new(1, 0, 1), Face::Up).0;
+        assert_eq!(ao(&registry), [3, 2, 3, 2]);
+
+        // Closing the corner fully occludes the vertex between both walls
+        chunk.place_block(BlockPos::new(1, 1, 2), stone).unwrap();
+        chunk.place_block(BlockPos::new(0, 1, 0), stone).unwrap();
+        let neighborhood = ChunkNeighborhood::new(&chunk);
+        let ao = |registry| face_corners(&neighborhood, registry, BlockPos::new(1, 0, 1), Face::Up).0;
+        assert_eq!(ao(&registry), [2, 2, 2, 0]);
+
+        // Transparent blocks don't occlude
+        chunk.place_block(BlockPos::new(2, 1, 1), glass).unwrap();
+        chunk.place_block(BlockPos::new(1, 1, 2), glass).unwrap();
+        let neighborhood = ChunkNeighborhood::new(&chunk);
+        let ao = |registry| face_corners(&neighborhood, registry, BlockPos::new(1, 0, 1), Face::Up).0;
+        assert_eq!(ao(&registry), [3, 3, 2, 3]);
+    }
+
+    #[test]
+    fn greedy_keeps_occluded_faces_apart() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+        let mut chunk: Chunk<16, 16> = Chunk::new(SectionPos::new(0, 0, 0));
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.place_block(BlockPos::new(x, 0, z), stone).unwrap();
+            }
+        }
+        // A pillar in the middle of the floor darkens the 8 top faces around
+        // it, which can't be merged with the rest
+        chunk.place_block(BlockPos::new(8, 1, 8), stone).unwrap();
+
+        let mut mesher = VoxelMesh::with_mode(MeshingMode::Greedy);
+        mesher.serialize_chunk(&ChunkNeighborhood::new(&chunk), &registry);
+        mesher.mesh();
+        let up_quads = mesher.faces.iter()
+            .filter(|face| **face == Face::Up)
+            .count();
+        // The 8 occluded faces, the top of the pillar, and the rest of the
+        // floor split around the hole left in the middle
+        assert_eq!(up_quads, 8 + 1 + 4);
+    }
+
+    #[test]
+    fn transparent_blocks_go_to_their_own_mesh() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+        let glass = registry.by_name("glass").unwrap();
+        let mut chunk: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.place_block(BlockPos::new(0, 0, 0), stone).unwrap();
+        chunk.place_block(BlockPos::new(1, 0, 0), glass).unwrap();
+
+        let mut mesher = VoxelMesh::with_mode(MeshingMode::Naive);
+        mesher.serialize_chunk(&ChunkNeighborhood::new(&chunk), &registry);
+
+        // The stone face behind the glass is still drawn, the glass face
+        // against the stone isn't
+        assert_eq!(mesher.mesh().indices_count(), 6 * 6);
+        assert_eq!(mesher.faces().len(), 6);
+        let transparent = mesher.transparent_mesh();
+        assert_eq!(transparent.indices_count(), 5 * 6);
+        assert_eq!(mesher.transparent_faces().len(), 5);
+    }
+
+    #[test]
+    fn block_models() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+        let slab = registry.by_name("slab").unwrap();
+        let plant = registry.by_name("tall_grass").unwrap();
+        let mut chunk: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.place_block(BlockPos::new(0, 0, 0), stone).unwrap();
+        chunk.place_block(BlockPos::new(1, 0, 0), slab).unwrap();
+        chunk.place_block(BlockPos::new(0, 1, 0), plant).unwrap();
+
+        for mode in [MeshingMode::Naive, MeshingMode::Greedy] {
+            let mut mesher = VoxelMesh::with_mode(mode);
+            mesher.serialize_chunk(&ChunkNeighborhood::new(&chunk), &registry);
+
+            // The stone shows all its faces, as the slab and the plant don't
+            // cover them, but the side of the slab against it is hidden
+            let mesh = mesher.mesh();
+            assert_eq!(mesh.indices_count(), (6 + 5 + 2) * 6);
+            assert_eq!(mesher.transparent_mesh().indices_count(), 0);
+            let highest = mesh.positions()
+                .filter(|position| position[0] > 0.5)
+                .map(|position| position[1])
+                .fold(f32::MIN, f32::max);
+            assert_eq!(highest, 0.0);
+        }
+
+        // Two slabs next to each other hide the faces between them
+        chunk.place_block(BlockPos::new(1, 0, 1), slab).unwrap();
+        let mut mesher = VoxelMesh::with_mode(MeshingMode::Naive);
+        mesher.serialize_chunk(&ChunkNeighborhood::new(&chunk), &registry);
+        assert_eq!(mesher.mesh().indices_count(), (6 + 4 + 5 + 2) * 6);
+    }
+}
@@ -0,0 +1,548 @@
+use std::ops::Deref;
+use std::fmt;
+
+use cgmath::Vector3;
+
+use crate::block::Block;
+use crate::mesh::Mesh;
+
+mod column;
+mod light;
+mod mesher;
+mod neighborhood;
+mod palette;
+
+pub use column::{ChunkColumn, Section, COLUMN_HEIGHT, COLUMN_SECTIONS, SECTION_SIZE};
+pub use light::{Light, NibbleArray, MAX_LIGHT};
+pub use mesher::{MeshingMode, VoxelMesh};
+pub use neighborhood::ChunkNeighborhood;
+pub use palette::PalettedStorage;
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Face {
+    Front = 0,
+    Back  = 1,
+    Up    = 2,
+    Down  = 3,
+    Left  = 4,
+    Right = 5
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::Front,
+        Face::Back,
+        Face::Up,
+        Face::Down,
+        Face::Left,
+        Face::Right
+    ];
+
+    /// Unit vector pointing out of the face
+    pub fn normal(&self) -> [i32; 3] {
+        match *self {
+            Face::Front => [ 0,  0, -1],
+            Face::Back  => [ 0,  0,  1],
+            Face::Up    => [ 0,  1,  0],
+            Face::Down  => [ 0, -1,  0],
+            Face::Left  => [-1,  0,  0],
+            Face::Right => [ 1,  0,  0]
+        }
+    }
+
+    /// The face looking the other way
+    pub fn opposite(&self) -> Face {
+        match *self {
+            Face::Front => Face::Back,
+            Face::Back  => Face::Front,
+            Face::Up    => Face::Down,
+            Face::Down  => Face::Up,
+            Face::Left  => Face::Right,
+            Face::Right => Face::Left
+        }
+    }
+
+    fn mesh(&self) -> Mesh {
+        match *self {
+            Face::Front => Mesh::FRONT_FACE,
+            Face::Back  => Mesh::BACK_FACE,
+            Face::Up    => Mesh::UP_FACE,
+            Face::Down  => Mesh::DOWN_FACE,
+            Face::Left  => Mesh::LEFT_FACE,
+            Face::Right => Mesh::RIGHT_FACE
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub fn new(x: i32, z: i32) -> Self {
+        Self {
+            x,
+            z,
+        }
+    }
+
+    /// Position of the `y`th section of this column
+    pub fn section(&self, y: i32) -> SectionPos {
+        SectionPos::new(self.x, y, self.z)
+    }
+
+    /// Position in the world of a block of this column, `block_pos.y` goes
+    /// over the whole height of the column
+    pub fn block(&self, block_pos: BlockPos) -> WorldBlockPos {
+        let size = SECTION_SIZE as i64;
+        WorldBlockPos::new(
+            self.x as i64 * size + block_pos.x as i64,
+            block_pos.y as i64,
+            self.z as i64 * size + block_pos.z as i64,
+        )
+    }
+}
+
+/// Position of a section, in sections, a column is a stack of sections with
+/// the same `x` and `z`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SectionPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl SectionPos {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self {
+            x,
+            y,
+            z,
+        }
+    }
+
+    /// Position of the column this section is part of
+    pub fn column(&self) -> ChunkPos {
+        ChunkPos::new(self.x, self.z)
+    }
+
+    pub fn offset(&self, dx: i32, dy: i32, dz: i32) -> Self {
+        Self::new(self.x + dx, self.y + dy, self.z + dz)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockPos {
+    pub x: usize,
+    pub y: usize,
+    pub z: usize
+}
+
+impl BlockPos {
+    pub fn new(x: usize, y: usize, z: usize) -> Self {
+        Self {
+            x,
+            y,
+            z
+        }
+    }
+}
+
+/// Position of a block in the world, in blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WorldBlockPos {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl WorldBlockPos {
+    pub fn new(x: i64, y: i64, z: i64) -> Self {
+        Self {
+            x,
+            y,
+            z,
+        }
+    }
+
+    /// Column the block is in and its position inside of it, `None` out of
+    /// the vertical bounds of the world
+    pub fn local(&self) -> Option<(ChunkPos, BlockPos)> {
+        if !(0..COLUMN_HEIGHT as i64).contains(&self.y) {
+            return None;
+        }
+
+        let size = SECTION_SIZE as i64;
+        Some((
+            ChunkPos::new(self.x.div_euclid(size) as i32, self.z.div_euclid(size) as i32),
+            BlockPos::new(
+                self.x.rem_euclid(size) as usize,
+                self.y as usize,
+                self.z.rem_euclid(size) as usize
+            )
+        ))
+    }
+
+    /// The block next to this one through a face
+    pub fn offset(&self, face: Face) -> Self {
+        let [dx, dy, dz] = face.normal();
+        Self::new(self.x + dx as i64, self.y + dy as i64, self.z + dz as i64)
+    }
+}
+
+pub struct BlockRef<'a, const L: usize, const H: usize> {
+    chunk_ref: &'a Chunk<L, H>,
+    block: Block,
+    block_pos: BlockPos
+}
+
+impl<'a, const L: usize, const H: usize> fmt::Debug for BlockRef<'a, L, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockRef")
+            .field("block", &self.block)
+            .finish()
+    }
+}
+
+impl<'a, const L: usize, const H: usize> Deref for BlockRef<'a, L, H> {
+    type Target = Block;
+
+    fn deref(&self) -> &Self::Target {
+        &self.block
+    }
+}
+
+impl<'a, const L: usize, const H: usize> BlockRef<'a, L, H> {
+    pub fn neighbor(&self, face: Face) -> Option<BlockRef<'a, L, H>> {
+        let Self { block_pos: BlockPos { x, y, z }, .. } = *self;
+        match face {
+            Face::Front => {
+                self.chunk_ref.index_block(BlockPos::new(x, y, z.checked_sub(1)?))
+            }
+            Face::Back => {
+                self.chunk_ref.index_block(BlockPos::new(x, y, z + 1))
+            }
+            Face::Right => {
+                self.chunk_ref.index_block(BlockPos::new(x + 1, y, z))
+            }
+            Face::Left => {
+                self.chunk_ref.index_block(BlockPos::new(x.checked_sub(1)?, y, z))
+            }
+            Face::Up => {
+                self.chunk_ref.index_block(BlockPos::new(x, y + 1, z))
+            }
+            Face::Down => {
+                self.chunk_ref.index_block(BlockPos::new(x, y.checked_sub(1)?, z))
+            }
+        }
+    }
+}
+
+/// A box of `L`x`H`x`L` blocks, stored palette compressed, and the light
+/// reaching each of them
+#[derive(Debug, Clone)]
+pub struct Chunk<const L: usize, const H: usize> {
+    blocks: PalettedStorage,
+    sky_light: NibbleArray,
+    block_light: NibbleArray,
+    section_pos: SectionPos,
+
+    /// Bumped on every change of the blocks or the light, a mesh made from
+    /// the same generation is still up to date
+    generation: u64,
+}
+
+impl<const L: usize, const H: usize> Chunk<L, H> {
+    /// An all air chunk, fully lit by the sky until the light is computed
+    pub fn new(section_pos: SectionPos) -> Self {
+        Self::with_blocks(section_pos, PalettedStorage::new(L * L * H, Block::AIR))
+    }
+
+    /// A chunk made of already filled storage, like one read from disk
+    pub fn with_blocks(section_pos: SectionPos, blocks: PalettedStorage) -> Self {
+        assert_eq!(blocks.len(), L * L * H, "Wrong amount of blocks for the chunk");
+        Self {
+            blocks,
+            sky_light: NibbleArray::new(L * L * H, MAX_LIGHT),
+            block_light: NibbleArray::new(L * L * H, 0),
+            section_pos,
+            generation: 0,
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn changed(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn blocks(&self) -> &PalettedStorage {
+        &self.blocks
+    }
+
+    /// Position on the storage of a block, if its inside the chunk
+    fn storage_index(BlockPos { x, y, z }: BlockPos) -> Option<usize> {
+        if x < L && y < H && z < L {
+            Some(y * L * L + z * L + x)
+        } else {
+            None
+        }
+    }
+    
+    fn index_block<'a>(
+        &'a self,
+        block_pos: BlockPos
+    ) -> Option<BlockRef<'a, L, H>> {
+        let index = Self::storage_index(block_pos)?;
+
+        Some(BlockRef {
+            block: self.blocks.get(index),
+            chunk_ref: self,
+            block_pos
+        })
+    }
+    
+    pub fn get_block(&self, block_pos: BlockPos) -> Option<Block> {
+        Some(self.blocks.get(Self::storage_index(block_pos)?))
+    }
+
+    pub fn place_block(
+        &mut self,
+        block_pos: BlockPos,
+        block: Block
+    ) -> Option<()> {
+        let index = Self::storage_index(block_pos)?;
+        self.blocks.set(index, block);
+        self.changed();
+
+        Some(())
+    }
+
+    pub fn light(&self, block_pos: BlockPos) -> Option<Light> {
+        let index = Self::storage_index(block_pos)?;
+        Some(Light::new(self.sky_light.get(index), self.block_light.get(index)))
+    }
+
+    pub fn set_sky_light(&mut self, block_pos: BlockPos, level: u8) -> Option<()> {
+        let index = Self::storage_index(block_pos)?;
+        self.sky_light.set(index, level);
+        self.changed();
+        Some(())
+    }
+
+    pub fn set_block_light(&mut self, block_pos: BlockPos, level: u8) -> Option<()> {
+        let index = Self::storage_index(block_pos)?;
+        self.block_light.set(index, level);
+        self.changed();
+        Some(())
+    }
+
+    /// Set the light of every block of the chunk at once
+    pub fn fill_light(&mut self, light: Light) {
+        self.sky_light.fill(light.sky);
+        self.block_light.fill(light.block);
+        self.changed();
+    }
+
+    /// Set every block of the chunk to the same one
+    pub fn fill(&mut self, block: Block) {
+        self.blocks.fill(block);
+        self.changed();
+    }
+
+    /// The block the whole chunk is made of, if it's uniform
+    pub fn single_block(&self) -> Option<Block> {
+        self.blocks.single()
+    }
+
+    /// Whether the chunk is only air, these have nothing to mesh or render
+    pub fn is_empty(&self) -> bool {
+        self.single_block() == Some(Block::AIR)
+    }
+
+    /// Shrink the block storage after a batch of edits
+    pub fn compact(&mut self) {
+        self.blocks.compact();
+    }
+
+    pub fn iter<'a>(&'a self) -> ChunkIter<'a, L, H> {
+        ChunkIter {
+            chunk: self,
+            block_pos: BlockPos::new(0, 0, 0)
+        }
+    }
+
+    pub fn translation(&self) -> Vector3<f32> {
+        Vector3::new(
+            (self.section_pos.x * L as i32) as f32,
+            (self.section_pos.y * H as i32) as f32,
+            (self.section_pos.z * L as i32) as f32,
+        )
+    }
+
+    pub fn pos(&self) -> SectionPos {
+        self.section_pos
+    }
+}
+
+pub struct ChunkIter<'a, const L: usize, const H: usize> {
+    chunk: &'a Chunk<L, H>,
+    block_pos: BlockPos
+}
+
+impl<'a, const L: usize, const H: usize> ChunkIter<'a, L, H> {
+    fn advance_index(&mut self) -> bool {
+        let BlockPos { ref mut x, ref mut y, ref mut z } = self.block_pos;
+        if *x == L - 1 {
+            *x = 0;
+            if *z == L - 1 {
+                *z = 0;
+                if *y == H {
+                    false
+                } else {
+                    *y += 1;
+                    true
+                }
+            } else {
+                *z += 1;
+                true
+            }
+        } else {
+            *x += 1;
+            true
+        }
+    }
+}
+
+impl<'a, const L: usize, const H: usize> Iterator for ChunkIter<'a, L, H> {
+    type Item = BlockRef<'a, L, H>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.chunk.index_block(self.block_pos);
+
+        if !self.advance_index() {
+            return None;
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn world_block_positions() {
+        let cases = [
+            (WorldBlockPos::new(0, 0, 0), ChunkPos::new(0, 0), BlockPos::new(0, 0, 0)),
+            (WorldBlockPos::new(15, 255, 16), ChunkPos::new(0, 1), BlockPos::new(15, 255, 0)),
+            (WorldBlockPos::new(-1, 7, -16), ChunkPos::new(-1, -1), BlockPos::new(15, 7, 0)),
+            (WorldBlockPos::new(-17, 3, -33), ChunkPos::new(-2, -3), BlockPos::new(15, 3, 15)),
+        ];
+        for (pos, chunk_pos, block_pos) in cases {
+            assert_eq!(pos.local(), Some((chunk_pos, block_pos)));
+            assert_eq!(chunk_pos.block(block_pos), pos);
+        }
+        assert_eq!(WorldBlockPos::new(3, -1, 3).local(), None);
+        assert_eq!(WorldBlockPos::new(3, 256, 3).local(), None);
+
+        assert_eq!(
+            WorldBlockPos::new(0, 5, -16).offset(Face::Left).local(),
+            Some((ChunkPos::new(-1, -1), BlockPos::new(15, 5, 0)))
+        );
+    }
+
+    #[test]
+    fn changes_bump_the_generation() {
+        let mut chunk: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        let neighbor: Chunk<2, 2> = Chunk::new(SectionPos::new(1, 0, 0));
+        let generations = ChunkNeighborhood::new(&chunk)
+            .with_neighbor(1, 0, 0, Some(&neighbor))
+            .generations();
+
+        chunk.compact();
+        assert_eq!(chunk.generation(), 0);
+        chunk.place_block(BlockPos::new(1, 1, 1), Block::from_id(1)).unwrap();
+        chunk.set_block_light(BlockPos::new(0, 0, 0), 3).unwrap();
+        assert_eq!(chunk.generation(), 2);
+        // Nothing changes out of the chunk
+        assert_eq!(chunk.set_sky_light(BlockPos::new(2, 0, 0), 3), None);
+        assert_eq!(chunk.generation(), 2);
+
+        let new_generations = ChunkNeighborhood::new(&chunk)
+            .with_neighbor(1, 0, 0, Some(&neighbor))
+            .generations();
+        assert_ne!(generations, new_generations);
+        assert_eq!(new_generations[13], Some(2));
+        assert_eq!(new_generations[14], Some(0));
+        assert_eq!(new_generations[12], None);
+    }
+
+    #[test]
+    fn chunk_iteration_and_access() {
+        let mut chunk: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        chunk.place_block(BlockPos::new(0, 0, 0), Block::from_id(1)).unwrap();
+        chunk.place_block(BlockPos::new(1, 0, 0), Block::from_id(2)).unwrap();
+        chunk.place_block(BlockPos::new(0, 0, 1), Block::from_id(3)).unwrap();
+        chunk.place_block(BlockPos::new(1, 0, 1), Block::from_id(4)).unwrap();
+        chunk.place_block(BlockPos::new(0, 1, 0), Block::from_id(5)).unwrap();
+        chunk.place_block(BlockPos::new(1, 1, 0), Block::from_id(6)).unwrap();
+        chunk.place_block(BlockPos::new(0, 1, 1), Block::from_id(7)).unwrap();
+        chunk.place_block(BlockPos::new(1, 1, 1), Block::from_id(8)).unwrap();
+
+        // Right is +x and Back +z, see `Face::normal`, so the origin has
+        // neighbors on those sides and none on Front and Left
+        let b = chunk.index_block(BlockPos::new(0, 0, 0)).unwrap();
+        assert_eq!(b.neighbor(Face::Right).map(|b| b.block), Some(Block::from_id(2)));
+        assert_eq!(b.neighbor(Face::Back).map(|b| b.block), Some(Block::from_id(3)));
+        assert_eq!(b.neighbor(Face::Front).map(|b| b.block), None);
+        assert_eq!(b.neighbor(Face::Left).map(|b| b.block), None);
+        assert_eq!(
+            b.neighbor(Face::Back)
+                .and_then(|b| b.neighbor(Face::Up))
+                    .map(|b| b.block), 
+            Some(Block::from_id(7))
+        );
+        assert_eq!(
+            chunk.iter().map(|b| b.block.id()).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 6, 7, 8]
+        );
+    }
+
+    #[test]
+    fn neighborhood_indexing() {
+        let mut center: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 0));
+        let mut back: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 0, 1));
+        center.place_block(BlockPos::new(0, 1, 1), Block::from_id(1)).unwrap();
+        back.place_block(BlockPos::new(0, 1, 0), Block::from_id(2)).unwrap();
+
+        let neighborhood = ChunkNeighborhood::new(&center)
+            .with_neighbor(0, 0, 1, Some(&back));
+        assert_eq!(neighborhood.block_at(0, 1, 1), Some(Block::from_id(1)));
+        assert_eq!(neighborhood.block_at(0, 1, 2), Some(Block::from_id(2)));
+        assert_eq!(
+            neighborhood.neighbor(BlockPos::new(0, 1, 1), Face::Back),
+            Some(Block::from_id(2))
+        );
+        assert_eq!(neighborhood.block_at(0, 1, -1), None);
+        assert_eq!(neighborhood.block_at(0, 2, 0), None);
+
+        // Sections stacked on top of each other are neighbors too
+        let mut above: Chunk<2, 2> = Chunk::new(SectionPos::new(0, 1, 0));
+        above.place_block(BlockPos::new(0, 0, 1), Block::from_id(3)).unwrap();
+        let neighborhood = neighborhood.with_neighbor(0, 1, 0, Some(&above));
+        assert_eq!(neighborhood.block_at(0, 2, 1), Some(Block::from_id(3)));
+        assert_eq!(
+            neighborhood.neighbor(BlockPos::new(0, 1, 1), Face::Up),
+            Some(Block::from_id(3))
+        );
+        assert_eq!(neighborhood.block_at(0, -1, 1), None);
+    }
+}
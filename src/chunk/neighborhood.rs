@@ -0,0 +1,145 @@
+use crate::biome::Biome;
+use crate::block::Block;
+
+use super::{BlockPos, Chunk, Face, Light};
+
+/// A chunk together with the chunks around it, lets the mesher look past the
+/// borders of the chunk it's working on
+pub struct ChunkNeighborhood<'a, const L: usize, const H: usize> {
+    /// 3x3x3 grid of chunks, indexed by
+    /// `((dy + 1) * 3 + dz + 1) * 3 + dx + 1`, the center is always present
+    chunks: [Option<&'a Chunk<L, H>>; 27],
+
+    /// Biome of each `x`, `z` of the center, indexed by `z * L + x`
+    biomes: Option<&'a [Biome]>,
+}
+
+impl<'a, const L: usize, const H: usize> ChunkNeighborhood<'a, L, H> {
+    const CENTER: usize = 13;
+
+    /// A neighborhood with no neighbors, the borders will be treated as open
+    pub fn new(center: &'a Chunk<L, H>) -> Self {
+        let mut chunks = [None; 27];
+        chunks[Self::CENTER] = Some(center);
+
+        Self {
+            chunks,
+            biomes: None,
+        }
+    }
+
+    /// Add the chunk at the offset `(dx, dy, dz)`, in chunks, from the center
+    pub fn with_neighbor(
+        mut self,
+        dx: i32,
+        dy: i32,
+        dz: i32,
+        chunk: Option<&'a Chunk<L, H>>
+    ) -> Self {
+        assert!((dx, dy, dz) != (0, 0, 0), "The center can't be replaced");
+        self.chunks[Self::slot(dx, dy, dz)] = chunk;
+        self
+    }
+
+    /// Set the biomes of the column of the center, without them every block
+    /// is on the default biome
+    pub fn with_biomes(mut self, biomes: &'a [Biome]) -> Self {
+        assert_eq!(biomes.len(), L * L, "Wrong amount of biomes");
+        self.biomes = Some(biomes);
+        self
+    }
+
+    /// Biome of a column of the center
+    pub fn biome(&self, x: usize, z: usize) -> Biome {
+        self.biomes.map_or(Biome::default(), |biomes| biomes[z * L + x])
+    }
+
+    pub fn center(&self) -> &'a Chunk<L, H> {
+        self.chunks[Self::CENTER].unwrap()
+    }
+
+    /// Generation of each chunk of the neighborhood, they change whenever
+    /// anything the mesh of the center depends on does
+    pub fn generations(&self) -> [Option<u64>; 27] {
+        self.chunks.map(|chunk| chunk.map(|chunk| chunk.generation()))
+    }
+
+    /// Copy the chunks, to mesh them on another thread while these keep
+    /// changing
+    pub fn snapshot(&self) -> NeighborhoodSnapshot<L, H> {
+        NeighborhoodSnapshot {
+            chunks: self.chunks.map(|chunk| chunk.cloned()),
+            biomes: self.biomes.map(|biomes| biomes.to_vec()),
+        }
+    }
+
+    fn slot(dx: i32, dy: i32, dz: i32) -> usize {
+        assert!(dx.abs() <= 1 && dy.abs() <= 1 && dz.abs() <= 1);
+        (((dy + 1) * 3 + dz + 1) * 3 + dx + 1) as usize
+    }
+
+    /// The chunk a position relative to the center falls in and the position
+    /// inside it
+    fn locate(&self, x: i32, y: i32, z: i32) -> Option<(&'a Chunk<L, H>, BlockPos)> {
+        let (l, h) = (L as i32, H as i32);
+        if !(-h..2 * h).contains(&y) || !(-l..2 * l).contains(&x)
+            || !(-l..2 * l).contains(&z)
+        {
+            return None;
+        }
+
+        let chunk = self.chunks[Self::slot(
+            x.div_euclid(l),
+            y.div_euclid(h),
+            z.div_euclid(l)
+        )]?;
+        let block_pos = BlockPos::new(
+            x.rem_euclid(l) as usize,
+            y.rem_euclid(h) as usize,
+            z.rem_euclid(l) as usize
+        );
+
+        Some((chunk, block_pos))
+    }
+
+    /// Get the block at a position relative to the center chunk, which may be
+    /// inside one of its neighbors. `None` if it falls on a missing chunk
+    pub fn block_at(&self, x: i32, y: i32, z: i32) -> Option<Block> {
+        let (chunk, block_pos) = self.locate(x, y, z)?;
+        chunk.get_block(block_pos)
+    }
+
+    /// Get the light at a position relative to the center chunk, `None` if
+    /// it falls on a missing chunk
+    pub fn light_at(&self, x: i32, y: i32, z: i32) -> Option<Light> {
+        let (chunk, block_pos) = self.locate(x, y, z)?;
+        chunk.light(block_pos)
+    }
+
+    /// The block touching the `face` of the block at `block_pos` of the
+    /// center chunk
+    pub fn neighbor(&self, block_pos: BlockPos, face: Face) -> Option<Block> {
+        let [dx, dy, dz] = face.normal();
+        self.block_at(
+            block_pos.x as i32 + dx,
+            block_pos.y as i32 + dy,
+            block_pos.z as i32 + dz
+        )
+    }
+}
+
+/// A neighborhood that owns its chunks, see `ChunkNeighborhood::snapshot`
+pub struct NeighborhoodSnapshot<const L: usize, const H: usize> {
+    chunks: [Option<Chunk<L, H>>; 27],
+    biomes: Option<Vec<Biome>>,
+}
+
+impl<const L: usize, const H: usize> NeighborhoodSnapshot<L, H> {
+    /// The neighborhood the copy was made from
+    pub fn neighborhood(&self) -> ChunkNeighborhood<'_, L, H> {
+        ChunkNeighborhood {
+            chunks: self.chunks.each_ref().map(|chunk| chunk.as_ref()),
+            biomes: self.biomes.as_deref(),
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::io::Read;
+
+use anyhow::*;
+
+use crate::block::Block;
+use crate::region::{read_u16, read_u64, read_u8};
+
+/// Smallest and biggest amount of bits used to index the palette
+const MIN_BITS: u32 = 1;
+const MAX_BITS: u32 = 16;
+
+/// Fixed size array of unsigned integers of `bits` each, packed on `u64` words.
+/// An entry never spans two words so indexing stays a shift and a mask
+#[derive(Debug, Clone)]
+struct PackedArray {
+    bits: u32,
+    words: Vec<u64>,
+}
+
+impl PackedArray {
+    fn new(bits: u32, len: usize) -> Self {
+        let per_word = (64 / bits) as usize;
+        Self {
+            bits,
+            words: vec![0; len.div_ceil(per_word)],
+        }
+    }
+
+    fn location(&self, index: usize) -> (usize, u32) {
+        let per_word = (64 / self.bits) as usize;
+        (index / per_word, (index % per_word) as u32 * self.bits)
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    fn get(&self, index: usize) -> usize {
+        let (word, shift) = self.location(index);
+        ((self.words[word] >> shift) & self.mask()) as usize
+    }
+
+    fn set(&mut self, index: usize, value: usize) {
+        let (word, shift) = self.location(index);
+        let mask = self.mask();
+        self.words[word] &= !(mask << shift);
+        self.words[word] |= (value as u64 & mask) << shift;
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Storage {
+    /// Fast path, every block is the same so nothing has to be indexed, covers
+    /// the all air or all stone chunks
+    Single(Block),
+
+    /// The distinct blocks of the chunk and an index into them per block
+    Paletted {
+        palette: Vec<Block>,
+        indices: PackedArray,
+    },
+}
+
+/// Compressed block storage, keeps a palette of the different blocks present
+/// and a bit packed index per block that grows from 1 to 16 bits as the
+/// palette needs it
+#[derive(Debug, Clone)]
+pub struct PalettedStorage {
+    len: usize,
+    storage: Storage,
+}
+
+impl PalettedStorage {
+    /// Create a storage of `len` blocks all set to `block`
+    pub fn new(len: usize, block: Block) -> Self {
+        Self {
+            len,
+            storage: Storage::Single(block),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Bits used per block, 0 when all the blocks are the same
+    #[cfg(test)]
+    pub fn bits_per_block(&self) -> u32 {
+        match &self.storage {
+            Storage::Single(_) => 0,
+            Storage::Paletted { indices, .. } => indices.bits,
+        }
+    }
+
+    /// The single block every position holds, if that's the case
+    pub fn single(&self) -> Option<Block> {
+        match self.storage {
+            Storage::Single(block) => Some(block),
+            Storage::Paletted { .. } => None,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Block {
+        assert!(index < self.len, "Block index out of bounds");
+        match &self.storage {
+            Storage::Single(block) => *block,
+            Storage::Paletted { palette, indices } => {
+                palette[indices.get(index)]
+            }
+        }
+    }
+
+    /// Replace the block at `index`, returns the previous one
+    pub fn set(&mut self, index: usize, block: Block) -> Block {
+        assert!(index < self.len, "Block index out of bounds");
+
+        // Go from a single value to a palette of two
+        if let Storage::Single(single) = self.storage {
+            if single == block {
+                return block;
+            }
+            self.storage = Storage::Paletted {
+                palette: vec![single],
+                indices: PackedArray::new(MIN_BITS, self.len),
+            };
+        }
+
+        let palette_index = match self.palette_index(block) {
+            Some(palette_index) => palette_index,
+            None => self.insert_in_palette(block),
+        };
+        let Storage::Paletted { palette, indices } = &mut self.storage else {
+            unreachable!()
+        };
+        let previous = palette[indices.get(index)];
+        indices.set(index, palette_index);
+
+        previous
+    }
+
+    /// Set all the blocks at once, drops the palette
+    pub fn fill(&mut self, block: Block) {
+        self.storage = Storage::Single(block);
+    }
+
+    /// Remove the palette entries no block points to anymore and go back to
+    /// the single value representation when possible
+    pub fn compact(&mut self) {
+        let Storage::Paletted { palette, indices } = &self.storage else {
+            return;
+        };
+
+        let mut used = vec![false; palette.len()];
+        for i in 0..self.len {
+            used[indices.get(i)] = true;
+        }
+        if used.iter().all(|used| *used) {
+            return;
+        }
+
+        let new_palette: Vec<Block> = palette.iter()
+            .zip(used.iter())
+            .filter(|(_, used)| **used)
+            .map(|(block, _)| *block)
+            .collect();
+        if new_palette.len() == 1 {
+            self.storage = Storage::Single(new_palette[0]);
+            return;
+        }
+        self.repack(new_palette);
+    }
+
+    /// Append the storage to `out`, the blocks are written as the ids given
+    /// by `block_id`:
+    /// - `0u8, id: u16` when it's a single block
+    /// - `1u8, palette_len: u16, palette: [u16], bits: u8, words: [u64]`
+    ///   otherwise, the amount of words follows from `bits` and the length
+    pub fn write(&self, out: &mut Vec<u8>, mut block_id: impl FnMut(Block) -> u16) {
+        match &self.storage {
+            Storage::Single(block) => {
+                out.push(0);
+                out.extend(block_id(*block).to_le_bytes());
+            }
+            Storage::Paletted { palette, indices } => {
+                out.push(1);
+                out.extend((palette.len() as u16).to_le_bytes());
+                for block in palette {
+                    out.extend(block_id(*block).to_le_bytes());
+                }
+                out.push(indices.bits as u8);
+                for word in &indices.words {
+                    out.extend(word.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    /// Read a storage of `len` blocks written by `write`, `block` turns the
+    /// written ids back into blocks. Everything is validated so a damaged
+    /// input can't produce a storage that panics later
+    pub fn read(
+        len: usize,
+        input: &mut impl Read,
+        block: impl Fn(u16) -> Result<Block>
+    ) -> Result<Self> {
+        let storage = match read_u8(input)? {
+            0 => Storage::Single(block(read_u16(input)?)?),
+            1 => {
+                let palette_len = read_u16(input)? as usize;
+                let palette = (0..palette_len)
+                    .map(|_| block(read_u16(input)?))
+                    .collect::<Result<Vec<Block>>>()?;
+                let bits = read_u8(input)? as u32;
+                ensure!(
+                    (MIN_BITS..=MAX_BITS).contains(&bits),
+                    "Invalid bits per block {}", bits
+                );
+                ensure!(
+                    (1..=1 << bits).contains(&palette_len),
+                    "Invalid palette length {} for {} bits", palette_len, bits
+                );
+
+                let mut indices = PackedArray::new(bits, len);
+                for word in indices.words.iter_mut() {
+                    *word = read_u64(input)?;
+                }
+                ensure!(
+                    (0..len).all(|i| indices.get(i) < palette_len),
+                    "Block index out of the palette"
+                );
+
+                Storage::Paletted {
+                    palette,
+                    indices,
+                }
+            }
+            tag => bail!("Unknown block storage {}", tag),
+        };
+
+        Ok(Self {
+            len,
+            storage,
+        })
+    }
+
+    fn palette_index(&self, block: Block) -> Option<usize> {
+        match &self.storage {
+            Storage::Single(_) => None,
+            Storage::Paletted { palette, .. } => {
+                palette.iter().position(|b| *b == block)
+            }
+        }
+    }
+
+    /// Add a block to the palette, making room for it first by removing
+    /// unused entries or widening the indices
+    fn insert_in_palette(&mut self, block: Block) -> usize {
+        if self.palette_is_full() {
+            self.compact();
+            // Compacting may have dropped us back to a single block
+            if let Storage::Single(single) = self.storage {
+                self.storage = Storage::Paletted {
+                    palette: vec![single],
+                    indices: PackedArray::new(MIN_BITS, self.len),
+                };
+            }
+        }
+        if self.palette_is_full() {
+            let Storage::Paletted { palette, .. } = &self.storage else {
+                unreachable!()
+            };
+            self.repack(palette.clone());
+        }
+
+        let Storage::Paletted { palette, .. } = &mut self.storage else {
+            unreachable!()
+        };
+        palette.push(block);
+
+        palette.len() - 1
+    }
+
+    fn palette_is_full(&self) -> bool {
+        match &self.storage {
+            Storage::Single(_) => false,
+            Storage::Paletted { palette, indices } => {
+                palette.len() >= 1 << indices.bits
+            }
+        }
+    }
+
+    /// Rebuild the indices against `new_palette`, sized to fit one more entry
+    /// than it currently has
+    fn repack(&mut self, new_palette: Vec<Block>) {
+        let bits = (usize::BITS - new_palette.len().leading_zeros())
+            .clamp(MIN_BITS, MAX_BITS);
+        let mut new_indices = PackedArray::new(bits, self.len);
+        for i in 0..self.len {
+            let block = self.get(i);
+            let palette_index = new_palette.iter()
+                .position(|b| *b == block)
+                .expect("Block missing from the new palette");
+            new_indices.set(i, palette_index);
+        }
+
+        self.storage = Storage::Paletted {
+            palette: new_palette,
+            indices: new_indices,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    /// Tiny deterministic generator, we just want scattered values
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, max: usize) -> usize {
+            self.0 = self.0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % max as u64) as usize
+        }
+    }
+
+    #[test]
+    fn matches_dense_storage() {
+        const LEN: usize = 16 * 16 * 16;
+        let mut dense = vec![Block::AIR; LEN];
+        let mut paletted = PalettedStorage::new(LEN, Block::AIR);
+        let mut rng = Lcg(7);
+
+        // Grow the number of distinct blocks slowly so every width is hit
+        for kinds in [2, 3, 5, 17, 200, 1000] {
+            for _ in 0..LEN {
+                let index = rng.next(LEN);
+                let block = Block::from_id(rng.next(kinds) as u16);
+                assert_eq!(
+                    paletted.set(index, block),
+                    dense[index]
+                );
+                dense[index] = block;
+            }
+            for (index, block) in dense.iter().enumerate() {
+                assert_eq!(paletted.get(index), *block);
+            }
+        }
+        assert!(paletted.bits_per_block() > 8);
+    }
+
+    #[test]
+    fn bits_grow_with_the_palette() {
+        let mut storage = PalettedStorage::new(64, Block::AIR);
+        assert_eq!(storage.bits_per_block(), 0);
+        storage.set(0, Block::from_id(1));
+        assert_eq!(storage.bits_per_block(), 1);
+        storage.set(1, Block::from_id(2));
+        assert_eq!(storage.bits_per_block(), 2);
+        storage.set(2, Block::from_id(3));
+        assert_eq!(storage.bits_per_block(), 2);
+        storage.set(3, Block::from_id(4));
+        assert_eq!(storage.bits_per_block(), 3);
+    }
+
+    #[test]
+    fn single_value_fast_path() {
+        let stone = Block::from_id(1);
+        let mut storage = PalettedStorage::new(16, Block::AIR);
+        assert_eq!(storage.single(), Some(Block::AIR));
+        storage.set(3, Block::AIR);
+        assert_eq!(storage.single(), Some(Block::AIR));
+
+        for i in 0..16 {
+            storage.set(i, stone);
+        }
+        assert_eq!(storage.single(), None);
+        storage.compact();
+        assert_eq!(storage.single(), Some(stone));
+        assert_eq!(storage.get(7), stone);
+
+        storage.fill(Block::AIR);
+        assert_eq!(storage.get(7), Block::AIR);
+        assert_eq!(storage.bits_per_block(), 0);
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut storage = PalettedStorage::new(100, Block::AIR);
+        for i in (0..100).step_by(3) {
+            storage.set(i, Block::from_id(i as u16 % 5));
+        }
+
+        let id = |block: Block| block.id() + 10;
+        let block = |id: u16| Ok(Block::from_id(id - 10));
+        let mut bytes = Vec::new();
+        storage.write(&mut bytes, id);
+        let read = PalettedStorage::read(100, &mut bytes.as_slice(), block).unwrap();
+        assert_eq!(read.bits_per_block(), storage.bits_per_block());
+        for i in 0..100 {
+            assert_eq!(read.get(i), storage.get(i));
+        }
+
+        // Truncated or pointing out of the palette
+        assert!(PalettedStorage::read(100, &mut &bytes[..bytes.len() - 1], block).is_err());
+        let last = bytes.len() - 8;
+        bytes[last..].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(PalettedStorage::read(100, &mut bytes.as_slice(), block).is_err());
+
+        let mut bytes = Vec::new();
+        PalettedStorage::new(100, Block::from_id(2)).write(&mut bytes, id);
+        let read = PalettedStorage::read(100, &mut bytes.as_slice(), block).unwrap();
+        assert_eq!(read.single(), Some(Block::from_id(2)));
+    }
+
+    #[test]
+    fn unused_entries_are_reused() {
+        let mut storage = PalettedStorage::new(4, Block::AIR);
+        for id in 1..100 {
+            storage.set(0, Block::from_id(id));
+        }
+        // Only two different blocks are ever alive at the same time
+        assert!(storage.bits_per_block() <= 2);
+        assert_eq!(storage.get(0), Block::from_id(99));
+        assert_eq!(storage.get(1), Block::AIR);
+    }
+}
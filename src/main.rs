@@ -7,6 +7,10 @@ use winit::event::*;
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::{Window, WindowBuilder};
 
+mod atlas;
+mod biome;
+mod block;
+mod block_model;
 mod camera;
 mod renderer;
 mod mesh;
@@ -15,13 +19,36 @@ mod bind_group;
 mod texture;
 mod mouse_input;
 mod pipeline;
+mod raycast;
+mod region;
 mod chunk;
+mod workers;
 mod world;
 
+use crate::atlas::TextureAtlas;
+use crate::block::{Block, BlockRegistry};
 use crate::texture::Texture;
 use crate::camera::Camera;
+use crate::chunk::{MeshingMode, WorldBlockPos};
 use crate::renderer::MasterRenderer;
-use crate::world::World;
+use crate::region::RegionStorage;
+use crate::world::generator::{self, StructureTemplate};
+use crate::world::{ChunkStatus, World};
+
+/// Directory the region files of the world are saved to
+const SAVE_DIR: &str = "saves/world";
+
+/// Directory the block textures are loaded from
+const TEXTURES_DIR: &str = "assets/textures";
+
+/// Directory the structure templates placed on the terrain are loaded from
+const STRUCTURES_DIR: &str = "assets/structures";
+
+/// Seed of the terrain when none is given with `--seed`
+const DEFAULT_SEED: u64 = 1337;
+
+/// How far away the blocks can be broken and placed
+const REACH: f32 = 8.0;
 
 /// Contains all the wgpu primitives and state
 pub struct WgpuContext {
@@ -49,11 +76,14 @@ impl WgpuContext {
         device: wgpu::Device,
         queue: wgpu::Queue,
         config: &wgpu::SurfaceConfiguration,
+        meshing_mode: MeshingMode,
+        atlas: &TextureAtlas,
     ) -> Result<Self> {
         // Create the master renderer that will control all the renderers, its
         // order and its relations
-        let master_renderer = 
-            MasterRenderer::new(&device, config.format)?;
+        let master_renderer = MasterRenderer::new(
+            &device, &queue, config.format, meshing_mode, atlas
+        )?;
 
         // Depth bitmap, to avoid overlapping models
         let depth_texture = Texture::create_depth(&device, config);
@@ -87,10 +117,11 @@ impl WgpuContext {
     pub fn render<'a>(
         &'a mut self,
         view: &'a wgpu::TextureView,
-        world: &mut World
+        world: &mut World,
+        camera: &Camera
     ) -> Result<()> {
         // Prepare the GPU buffers before rendering
-        self.master_renderer.prepare(&self.device, world);
+        self.master_renderer.prepare(&self.device, &self.queue, world, camera);
 
         // Get the command encoder that will, let the master renderer and its
         // inner renderers push all its commands in order and submit them to
@@ -115,7 +146,7 @@ impl WgpuContext {
     /// Update all the uniforms owned by the master renderer / his child 
     /// renderers with refined input
     pub fn update(&mut self, camera: &Camera, world: &World) {
-        self.master_renderer.update_uniforms(&self.queue, camera, world.chunks());
+        self.master_renderer.update_uniforms(&self.queue, camera, world.sections());
     }
 }
 
@@ -138,10 +169,19 @@ struct Display {
     /// Camera and the controller of the camera used by the context
     camera: Camera,
     camera_controller: CameraController,
+
+    /// Block placed with the right click, chosen with the number keys
+    selected_block: Block,
 }
 
 impl Display {
-    async fn new(window: &Window) -> Result<Self> {
+    async fn new(
+        window: &Window,
+        meshing_mode: MeshingMode,
+        generator: &str,
+        seed: u64,
+        render_distance: i32
+    ) -> Result<Self> {
         // Extract the size of the window
         let PhysicalSize { width, height } = window.inner_size();
 
@@ -176,7 +216,7 @@ impl Display {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: *surface
                 .get_supported_formats(&adapter)
-                .get(0)
+                .first()
                 .context("No supported format available for surface")
                 .unwrap(),
             width,
@@ -185,22 +225,46 @@ impl Display {
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
         };
 
+        // The block textures, without them the blocks are drawn with just
+        // their colors
+        let atlas = TextureAtlas::load(TEXTURES_DIR).unwrap_or_else(|e| {
+            log::warn!("{:#}, drawing the blocks without textures", e);
+            TextureAtlas::empty()
+        });
+
         // Create the wgpu rendering context and configure the surface with that
         // config using it
-        let context = WgpuContext::new(device, queue, &config)?;
+        let context = WgpuContext::new(device, queue, &config, meshing_mode, &atlas)?;
         context.configure_surface(&surface, &config);
 
-        // Create the camera
+        let mut registry = BlockRegistry::with_default_blocks();
+        registry.set_atlas(&atlas);
+        let structures = StructureTemplate::load_dir(STRUCTURES_DIR, &registry)
+            .unwrap_or_else(|e| {
+                log::warn!("{:#}, generating the terrain without structures", e);
+                Vec::new()
+            });
+        let generator = generator::from_name(generator, seed, &registry, structures)?;
+        let world = World::with_storage(
+            registry, generator, Some(RegionStorage::new(SAVE_DIR))
+        )
+        .with_render_distance(render_distance)
+        .with_workers(workers::default_threads());
+        let selected_block = world.registry().by_name("stone").unwrap();
+
+        // Create the camera, a few blocks over the ground of the origin
+        let spawn_height = world.generator().spawn_height() as f32 + 8.0;
         let camera = 
-            Camera::new(width, height, (0.0, 0.0, 0.0), cgmath::Deg(90.0));
+            Camera::new(width, height, (0.0, spawn_height, 0.0), cgmath::Deg(90.0));
 
         Ok(Self {
             surface,
             config,
             context,
-            world: World::new(),
+            world,
             camera,
             camera_controller: CameraController::new(1.0, 0.01),
+            selected_block,
         })
     }
 
@@ -218,9 +282,91 @@ impl Display {
 
     /// Handle window input
     fn input(&mut self, event: &WindowEvent) -> bool {
+        if let WindowEvent::KeyboardInput {
+            input: KeyboardInput {
+                state: ElementState::Pressed,
+                virtual_keycode: Some(keycode),
+                ..
+            },
+            ..
+        } = event {
+            // The number keys select the registered blocks in order, air is
+            // the 0th one
+            let keys = [
+                VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3,
+                VirtualKeyCode::Key4, VirtualKeyCode::Key5, VirtualKeyCode::Key6,
+                VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+            ];
+            if let Some(index) = keys.iter().position(|key| key == keycode) {
+                if let Some((block, _)) = self.world.registry().iter().nth(index + 1) {
+                    self.selected_block = block;
+                }
+                return true;
+            }
+            if *keycode == VirtualKeyCode::F3 {
+                self.log_statuses();
+                return true;
+            }
+        }
+
         self.camera_controller.process_event(event)
     }
 
+    /// Break the targeted block with the left click and place the selected
+    /// one against it with the right click, the changed sections get remeshed
+    /// on the next frame
+    fn edit_world(&mut self) {
+        let (break_block, place_block) = self.camera_controller.take_clicks();
+        if !break_block && !place_block {
+            return;
+        }
+        let Some(hit) = self.world.raycast(
+            self.camera.position(),
+            self.camera.forward(),
+            REACH
+        ) else {
+            return;
+        };
+
+        if break_block {
+            self.world.set_block(hit.pos, Block::AIR);
+            return;
+        }
+        // A replaceable block, like the grass, is placed over instead of
+        // against
+        let registry = self.world.registry().clone();
+        let replaceable = |pos| self.world.get_block(pos)
+            .is_some_and(|block| registry.is_replaceable(block));
+        let pos = if replaceable(hit.pos) { hit.pos } else { hit.pos.offset(hit.face) };
+        if replaceable(pos) {
+            self.world.set_block(pos, self.selected_block);
+        }
+    }
+
+    /// Log the generation status of the column under the camera and how many
+    /// columns there are on each status
+    fn log_statuses(&self) {
+        let position = self.camera.position();
+        let pos = WorldBlockPos::new(position.x.floor() as i64, 0, position.z.floor() as i64);
+        let (chunk_pos, _) = pos.local().unwrap();
+        let mut counts = [0; ChunkStatus::ALL.len()];
+        for (_, status) in self.world.statuses() {
+            counts[status as usize] += 1;
+        }
+        let counts: Vec<_> = ChunkStatus::ALL.iter().zip(counts).collect();
+        log::info!(
+            "Column {:?} is {:?}, columns on each status: {:?}, {} on the workers",
+            chunk_pos, self.world.status(chunk_pos), counts, self.world.in_flight()
+        );
+        let counters = self.world.counters();
+        let chunks_renderer = self.context.master_renderer.chunks_renderer();
+        log::info!(
+            "{} columns loaded, {} pending and {} unloaded, {} sections drawn and {} waiting for their mesh",
+            counters.loaded, counters.pending, counters.unloaded,
+            chunks_renderer.chunk_count(), chunks_renderer.pending_meshes()
+        );
+    }
+
     /// Handle general input, needed for mouse 3d camera input, as we need the
     /// raw movements
     fn process_device_event(&mut self, event: &DeviceEvent) {
@@ -229,6 +375,7 @@ impl Display {
 
     /// Update loop, transformation from refined input, to refined state
     fn update(&mut self, dt: f32) {
+        self.edit_world();
         self.context.update(&self.camera, &self.world);
         self.camera_controller.update_camera(&mut self.camera, dt);
     }
@@ -242,7 +389,7 @@ impl Display {
             .create_view(&wgpu::TextureViewDescriptor::default());
 
         // Render to it
-        self.context.render(&view, &mut self.world)?;
+        self.context.render(&view, &mut self.world, &self.camera)?;
 
         // Issue propagation of that rendering from the GPU to the OS surface
         output.present();
@@ -250,6 +397,11 @@ impl Display {
         Ok(())
     }
 
+    /// Write the changes made to the world to disk
+    fn save(&mut self) -> Result<()> {
+        self.world.save()
+    }
+
     pub fn width(&self) -> u32 {
         self.config.width
     }
@@ -263,6 +415,36 @@ fn main() -> Result<()> {
     // Initialize the logging backend
     env_logger::init();
 
+    // Greedy meshing unless asked otherwise, the naive one is useful to debug
+    let meshing_mode = if std::env::args().any(|arg| arg == "--naive-meshing") {
+        MeshingMode::Naive
+    } else {
+        MeshingMode::Greedy
+    };
+
+    // The same seed always generates the same terrain
+    let args: Vec<String> = std::env::args().collect();
+    let seed = match args.iter().position(|arg| arg == "--seed") {
+        Some(index) => args.get(index + 1)
+            .context("Missing the value of --seed")?
+            .parse()
+            .context("The seed must be a positive integer")?,
+        None => DEFAULT_SEED,
+    };
+    let generator = match args.iter().position(|arg| arg == "--generator") {
+        Some(index) => args.get(index + 1).context("Missing the value of --generator")?,
+        None => "terrain",
+    };
+    let render_distance = match args.iter().position(|arg| arg == "--render-distance") {
+        Some(index) => args.get(index + 1)
+            .context("Missing the value of --render-distance")?
+            .parse()
+            .ok()
+            .filter(|distance| *distance >= 0)
+            .context("The render distance must be a positive integer")?,
+        None => World::RENDER_DISTANCE,
+    };
+
     // Create the event loop and the window
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
@@ -270,7 +452,7 @@ fn main() -> Result<()> {
         .context("Failed to create window")?;
 
     // Initialize wgpu rendering context
-    let mut state = pollster::block_on(Display::new(&window))?;
+    let mut state = pollster::block_on(Display::new(&window, meshing_mode, generator, seed, render_distance))?;
 
     // Main loop
     let mut dt = 0.0;
@@ -335,6 +517,11 @@ fn main() -> Result<()> {
             } => {
                 state.process_device_event(event);
             }
+            Event::LoopDestroyed => {
+                if let Err(e) = state.save() {
+                    log::error!("Failed to save the world: {:#}", e);
+                }
+            }
             Event::MainEventsCleared => {
                 dt = it.elapsed().as_secs_f32();
                 it = Instant::now();
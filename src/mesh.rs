@@ -1,6 +1,7 @@
 use std::mem;
 use std::borrow::Cow;
 
+use crate::atlas::UvRect;
 use crate::chunk::BlockPos;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -24,16 +25,6 @@ macro_rules! face {
 }
 
 impl Mesh {
-    pub const QUAD: Self = Mesh {
-        vertices: Cow::Borrowed(&[
-            Vertex::new([-0.5, 0.5, 0.0], [1.0, 0.0, 0.0]),
-            Vertex::new([-0.5, -0.5, 0.0], [0.0, 1.0, 0.0]),
-            Vertex::new([0.5, -0.5, 0.0], [0.0, 0.0, 1.0]),
-            Vertex::new([0.5, 0.5, 0.0], [1.0, 1.0, 1.0]),
-        ]),
-        indices: Cow::Borrowed(&[0, 1, 2, 0, 2, 3]),
-    };
-
     face!(UP_FACE, 
         [-0.5,  0.5,  0.5],
         [ 0.5,  0.5, -0.5],
@@ -70,31 +61,6 @@ impl Mesh {
         [-0.5, -0.5,  0.5],
         [ 0.5,  0.5,  0.5]);
 
-    #[allow(dead_code)]
-    pub const TRIANGLE: Self = Mesh {
-        vertices: Cow::Borrowed(&[
-            Vertex::new([0.5, -0.5, 0.0], [1.0, 0.0, 0.0]),
-            Vertex::new([0.0, 0.5, 0.0], [0.0, 1.0, 0.0]),
-            Vertex::new([-0.5, -0.5, 0.0], [0.0, 0.0, 1.0]),
-        ]),
-        indices: Cow::Borrowed(&[0, 1, 2]),
-    };
-
-    #[allow(dead_code)]
-    pub const PENTAGON: Self = Mesh {
-        vertices: Cow::Borrowed(&[
-            Vertex::new([-0.0868241, 0.49240386, 0.0], [0.0, 0.0, 0.0]), 
-            Vertex::new([-0.49513406, 0.06958647, 0.0], [0.0, 0.0, 0.0]),
-            Vertex::new([-0.21918549, -0.44939706, 0.0], [0.0, 0.0, 0.0]), 
-            Vertex::new([0.35966998, -0.3473291, 0.0], [0.0, 0.0, 0.0]),
-            Vertex::new([0.44147372, 0.2347359, 0.0], [0.0, 0.0, 0.0]),
-        ]),
-        indices:  Cow::Borrowed(
-                    &[0, 1, 4,
-                      1, 2, 4,
-                      2, 3, 4])
-    };
-
     pub const WEIRD: Self = Mesh {
         vertices: Cow::Borrowed(&[
             Vertex::new([-0.5, -0.5, 0.0], [1.0, 0.0, 0.0]),
@@ -117,11 +83,51 @@ impl Mesh {
         }
     }
 
+    /// A quad with the same split as the block faces, the first two corners
+    /// are the ends of the shared diagonal
+    pub fn quad(corners: [[f32; 3]; 4]) -> Self {
+        Self::new(
+            corners.map(|corner| Vertex::new(corner, [1.0; 3])).to_vec(),
+            &[0, 1, 2, 0, 3, 1][..]
+        )
+    }
+
+    /// Position of each vertex, in order
+    pub fn positions(&self) -> impl Iterator<Item = [f32; 3]> + '_ {
+        self.vertices.iter().map(|vertex| vertex.position)
+    }
+
+    /// Reorder the quads from the furthest to the closest to `eye`, so they
+    /// can be blended. Only for meshes of quads, like the ones made with
+    /// `MeshBuilder::push_face`
+    pub fn sort_quads(&mut self, eye: [f32; 3]) {
+        let vertices = &self.vertices;
+        let distance = |quad: &[u16]| {
+            let first = *quad.iter().min().unwrap() as usize;
+            vertices[first..first + 4].iter()
+                .map(|vertex| {
+                    (0..3)
+                        .map(|axis| (vertex.position[axis] - eye[axis]).powi(2))
+                        .sum::<f32>()
+                })
+                .sum::<f32>()
+        };
+
+        let mut quads: Vec<(f32, &[u16])> = self.indices.chunks(6)
+            .map(|quad| (distance(quad), quad))
+            .collect();
+        quads.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let indices: Vec<u16> = quads.into_iter()
+            .flat_map(|(_, quad)| quad.iter().copied())
+            .collect();
+        self.indices = indices.into();
+    }
+
     pub fn indices_count(&self) -> u32 {
         self.indices.len() as u32
     }
 
-    pub fn vertex_data<'a>(&'a self) -> &'a [u8] {
+    pub fn vertex_data(&self) -> &[u8] {
         unsafe {
             std::slice::from_raw_parts(
                 self.vertices.as_ref().as_ptr() as *const u8,
@@ -130,7 +136,7 @@ impl Mesh {
         }
     }
 
-    pub fn index_data<'a>(&'a self) -> &'a [u8] {
+    pub fn index_data(&self) -> &[u8] {
         unsafe {
             std::slice::from_raw_parts(
                 self.indices.as_ref().as_ptr() as *const u8,
@@ -155,7 +161,7 @@ impl MeshBuilder {
         }
     }
 
-    pub fn push(&mut self, mut mesh: Mesh, position: BlockPos) {
+    pub fn push(&mut self, mesh: Mesh, position: BlockPos) {
         let mut max_idx = 0;
         for index in mesh.indices.iter() {
             max_idx = u16::max(max_idx, *index);
@@ -167,11 +173,118 @@ impl MeshBuilder {
         }
     }
 
+    /// Push a block face stretched to cover `extent` blocks on each axis
+    /// starting at `position`, the vertices on the positive side of an axis
+    /// are moved `extent - 1` blocks further. Only works with quads
+    pub fn push_face(
+        &mut self,
+        mesh: Mesh,
+        position: BlockPos,
+        extent: [usize; 3],
+        shading: FaceShading
+    ) {
+        assert_eq!(mesh.vertices.len(), 4, "Faces must be quads");
+        let first_vertex = self.vertices.len();
+        let first_index = self.indices.len();
+        self.push(mesh, position);
+
+        for (i, vertex) in self.vertices[first_vertex..].iter_mut().enumerate() {
+            *vertex = vertex.stretch(position, extent);
+            vertex.color = shading.color;
+            vertex.ao = shading.ao[i] as f32;
+            vertex.light = shading.light[i];
+            vertex.alpha = shading.alpha;
+            vertex.tile = shading.texture.to_array();
+        }
+
+        // The horizontal faces map the texture on `x` and `z`, the rest on
+        // the horizontal axis they go along and from the top of the block
+        // down, so the textures are upright and a slab shows the lower half
+        let vertices = &mut self.vertices[first_vertex..];
+        let flat_axis = (0..3).find(|axis| {
+            vertices.iter().all(|vertex| vertex.position[*axis] == vertices[0].position[*axis])
+        });
+        let u_axis = if flat_axis == Some(0) { 2 } else { 0 };
+        let origin = [position.x as f32, position.y as f32, position.z as f32];
+        for vertex in vertices.iter_mut() {
+            let local = [0, 1, 2].map(|axis| vertex.position[axis] - origin[axis] + 0.5);
+            vertex.uv = match flat_axis {
+                Some(1) => [local[0], local[2]],
+                _ => [local[u_axis], 1.0 - local[1]],
+            };
+        }
+
+        // The quads are split along the v0-v1 diagonal, when the other
+        // diagonal is darker split along it instead so the ambient occlusion
+        // is interpolated the same way on both triangles
+        let [a0, a1, a2, a3] = shading.ao.map(|ao| ao as u32);
+        if a0 + a1 > a2 + a3 {
+            let base = first_vertex as u16;
+            self.indices.truncate(first_index);
+            self.indices.extend(
+                [2, 0, 3, 2, 3, 1].iter().map(|index| base + index)
+            );
+        }
+    }
+
     pub fn build(self) -> Mesh {
         Mesh::new(self.vertices, self.indices)
     }
 }
 
+/// Per vertex data of a block face that doesn't come from its geometry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaceShading {
+    pub color: [f32; 3],
+
+    /// Ambient occlusion level of each vertex in the order of the face mesh,
+    /// from 0 (fully occluded) to 3 (not occluded)
+    pub ao: [u8; 4],
+
+    /// Sky and block light of each vertex in the order of the face mesh, from
+    /// 0 to 15
+    pub light: [[f32; 2]; 4],
+
+    /// Opacity of the face, only blended on the transparent pass
+    pub alpha: f32,
+
+    /// Texture of the face on the atlas, repeated once per block
+    pub texture: UvRect,
+}
+
+impl FaceShading {
+    /// An opaque face fully lit by the sky
+    pub fn new(color: [f32; 3]) -> Self {
+        Self {
+            color,
+            ao: [3; 4],
+            light: [[15.0, 0.0]; 4],
+            alpha: 1.0,
+            texture: UvRect::default(),
+        }
+    }
+
+    pub fn with_ao(mut self, ao: [u8; 4]) -> Self {
+        self.ao = ao;
+        self
+    }
+
+    pub fn with_light(mut self, light: [[f32; 2]; 4]) -> Self {
+        self.light = light;
+        self
+    }
+
+    pub fn with_alpha(mut self, alpha: f32) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: UvRect) -> Self {
+        self.texture = texture;
+        self
+    }
+}
+
 pub const VERTEX_DESC: wgpu::VertexBufferLayout<'static> = 
     wgpu::VertexBufferLayout {
         array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
@@ -186,6 +299,31 @@ pub const VERTEX_DESC: wgpu::VertexBufferLayout<'static> =
                 offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                 shader_location: 1,
                 format: wgpu::VertexFormat::Float32x3,
+            },
+            wgpu::VertexAttribute {
+                offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                shader_location: 2,
+                format: wgpu::VertexFormat::Float32,
+            },
+            wgpu::VertexAttribute {
+                offset: mem::size_of::<[f32; 7]>() as wgpu::BufferAddress,
+                shader_location: 3,
+                format: wgpu::VertexFormat::Float32x2,
+            },
+            wgpu::VertexAttribute {
+                offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                shader_location: 4,
+                format: wgpu::VertexFormat::Float32,
+            },
+            wgpu::VertexAttribute {
+                offset: mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                shader_location: 5,
+                format: wgpu::VertexFormat::Float32x2,
+            },
+            wgpu::VertexAttribute {
+                offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                shader_location: 6,
+                format: wgpu::VertexFormat::Float32x4,
             }
         ]
     };
@@ -195,20 +333,49 @@ pub const VERTEX_DESC: wgpu::VertexBufferLayout<'static> =
 pub struct Vertex {
     position: [f32; 3],
     color: [f32; 3],
+
+    /// Ambient occlusion level, see `FaceShading`
+    ao: f32,
+
+    /// Sky and block light level, see `FaceShading`
+    light: [f32; 2],
+
+    alpha: f32,
+
+    /// Texture coordinates in blocks, the shader wraps them to the tile so
+    /// the texture repeats on each block of a stretched face
+    uv: [f32; 2],
+
+    /// Area of the atlas with the texture, as `[u, v, width, height]`
+    tile: [f32; 4],
 }
 
 impl Vertex {
     const fn new(position: [f32; 3], color: [f32; 3]) -> Self {
         Self {
             position,
-            color
+            color,
+            ao: 3.0,
+            light: [15.0, 0.0],
+            alpha: 1.0,
+            uv: [0.0; 2],
+            tile: [0.0; 4],
         }
     }
 
-    const fn from_blockpos(BlockPos { x, y, z }: BlockPos, color: [f32; 3]) -> Self {
-        Self { 
-            position: [x as f32, y as f32, z as f32],
-            color,
+    /// Move the vertices on the positive side of the block at `origin`
+    fn stretch(&self, origin: BlockPos, extent: [usize; 3]) -> Self {
+        let origin = [origin.x as f32, origin.y as f32, origin.z as f32];
+        let mut position = self.position;
+        for axis in 0..3 {
+            if position[axis] > origin[axis] {
+                position[axis] += (extent[axis] - 1) as f32;
+            }
+        }
+
+        Self {
+            position,
+            ..*self
         }
     }
 
@@ -220,7 +387,7 @@ impl Vertex {
 
         Self {
             position: [x, y, z],
-            color: self.color
+            ..*self
         }
     }
 }
@@ -237,6 +404,8 @@ mod tests {
         m.push(Mesh::RIGHT_FACE, BlockPos::new(0, 0, 0));
         let m = m.build();
 
+        // Each face is its constant as is, the right one lies on x = 0.5
+
         pretty_assertions::assert_eq!(
             m,
             Mesh::new(
@@ -252,8 +421,8 @@ mod tests {
                     Vertex::new([ 0.5, -0.5,  0.5], [1.0, 1.0, 1.0]),
 
                     Vertex::new([ 0.5, -0.5,  0.5], [1.0, 0.0, 0.0]),
-                    Vertex::new([-0.5,  0.5,  0.5], [0.0, 1.0, 0.0]),
-                    Vertex::new([-0.5, -0.5,  0.5], [0.0, 0.0, 1.0]),
+                    Vertex::new([ 0.5,  0.5, -0.5], [0.0, 1.0, 0.0]),
+                    Vertex::new([ 0.5, -0.5, -0.5], [0.0, 0.0, 1.0]),
                     Vertex::new([ 0.5,  0.5,  0.5], [1.0, 1.0, 1.0]),
                 ],
                 vec![
@@ -266,4 +435,101 @@ mod tests {
         assert_eq!(m.indices_count(), 18);
     }
 
+    #[test]
+    fn face_shading() {
+        let mut m = MeshBuilder::new();
+        m.push_face(
+            Mesh::UP_FACE,
+            BlockPos::new(1, 0, 0),
+            [2, 1, 1],
+            FaceShading::new([0.5; 3]).with_ao([2, 3, 3, 3])
+        );
+        m.push_face(
+            Mesh::UP_FACE,
+            BlockPos::new(0, 0, 0),
+            [1, 1, 1],
+            FaceShading::new([0.5; 3]).with_ao([0, 3, 3, 3])
+        );
+        m.push_face(
+            Mesh::UP_FACE,
+            BlockPos::new(0, 1, 0),
+            [1, 1, 1],
+            FaceShading::new([0.5; 3]).with_ao([3, 3, 0, 3])
+        );
+        let m = m.build();
+
+        assert_eq!(
+            m.positions().take(4).collect::<Vec<_>>(),
+            vec![
+                [0.5, 0.5, 0.5],
+                [2.5, 0.5, -0.5],
+                [0.5, 0.5, -0.5],
+                [2.5, 0.5, 0.5],
+            ]
+        );
+        assert_eq!(m.vertices[0].ao, 2.0);
+        assert_eq!(m.vertices[3].ao, 3.0);
+        assert_eq!(m.vertices[4].ao, 0.0);
+
+        // Only the face darker on the v2-v3 diagonal is flipped
+        assert_eq!(
+            m.indices.as_ref(),
+            &[
+                0, 1, 2, 0, 3, 1,
+                4, 5, 6, 4, 7, 5,
+                10, 8, 11, 10, 11, 9
+            ]
+        );
+    }
+
+    #[test]
+    fn quads_sorted_back_to_front() {
+        let mut m = MeshBuilder::new();
+        for x in [0, 4, 2] {
+            m.push_face(
+                Mesh::UP_FACE,
+                BlockPos::new(x, 0, 0),
+                [1; 3],
+                FaceShading::new([0.5; 3]).with_alpha(0.4)
+            );
+        }
+        let mut m = m.build();
+        assert_eq!(m.vertices[0].alpha, 0.4);
+
+        m.sort_quads([0.0, 1.0, 0.0]);
+        let first_vertices = m.indices.chunks(6)
+            .map(|quad| *quad.iter().min().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(first_vertices, vec![4, 8, 0]);
+        assert_eq!(m.indices_count(), 18);
+    }
+
+    #[test]
+    fn texture_coordinates() {
+        let texture = UvRect { min: [0.5, 0.0], size: [0.25, 0.25] };
+        let mut m = MeshBuilder::new();
+        m.push_face(
+            Mesh::UP_FACE,
+            BlockPos::new(3, 1, 0),
+            [2, 1, 1],
+            FaceShading::new([1.0; 3]).with_texture(texture)
+        );
+        m.push_face(
+            Mesh::LEFT_FACE,
+            BlockPos::new(0, 0, 0),
+            [1, 2, 1],
+            FaceShading::new([1.0; 3]).with_texture(texture)
+        );
+        let m = m.build();
+
+        // In blocks, stretched faces repeat the texture
+        assert_eq!(
+            m.vertices.iter().map(|vertex| vertex.uv).collect::<Vec<_>>(),
+            vec![
+                [0.0, 1.0], [2.0, 0.0], [0.0, 0.0], [2.0, 1.0],
+                [1.0, 1.0], [0.0, -1.0], [0.0, 1.0], [1.0, -1.0],
+            ]
+        );
+        assert!(m.vertices.iter().all(|vertex| vertex.tile == [0.5, 0.0, 0.25, 0.25]));
+    }
 }
@@ -1,4 +1,3 @@
-use wgpu::util::DeviceExt;
 use cgmath::{Vector3, Matrix4};
 
 use crate::mesh::Mesh;
@@ -20,6 +19,27 @@ impl Model {
         }
     }
 
+    /// Replace the mesh, the old buffers are written again if the new one
+    /// fits in them
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mesh: Mesh
+    ) {
+        if !self.render_info.write(queue, &mesh) {
+            self.render_info = RenderInfo::new(device, &mesh);
+        }
+        self.mesh = mesh;
+    }
+
+    /// Reorder the quads of the mesh back to front as seen from `eye`, only
+    /// the indices are written again
+    pub fn sort_quads(&mut self, queue: &wgpu::Queue, eye: [f32; 3]) {
+        self.mesh.sort_quads(eye);
+        self.render_info.write_indices(queue, &self.mesh);
+    }
+
     pub fn render<'a>(
         &'a self,
         render_pass: &mut wgpu::RenderPass<'a>
@@ -31,6 +51,11 @@ impl Model {
 pub struct RenderInfo {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
+
+    /// Size in bytes of each buffer, they are made bigger than the mesh they
+    /// are created for so the next ones can grow a bit
+    vertex_capacity: usize,
+    index_capacity: usize,
 }
 
 impl RenderInfo {
@@ -38,22 +63,76 @@ impl RenderInfo {
         device: &wgpu::Device,
         mesh: &Mesh
     ) -> Self {
+        let (vertex_buffer, vertex_capacity) = Self::create_buffer(
+            device,
+            "Vertex Buffer",
+            mesh.vertex_data(),
+            wgpu::BufferUsages::VERTEX
+        );
+        let (index_buffer, index_capacity) = Self::create_buffer(
+            device,
+            "Index Buffer",
+            mesh.index_data(),
+            wgpu::BufferUsages::INDEX
+        );
+
         Self {
-            vertex_buffer: device.create_buffer_init(
-                &wgpu::util::BufferInitDescriptor {
-                    label: Some("Vertex Buffer"),
-                    contents: mesh.vertex_data(),
-                    usage: wgpu::BufferUsages::VERTEX,
-                }
-            ),
-            index_buffer: device.create_buffer_init(
-                &wgpu::util::BufferInitDescriptor {
-                    label: Some("Index Buffer"),
-                    contents: mesh.index_data(),
-                    usage: wgpu::BufferUsages::INDEX,
-                }
-            ),
+            vertex_buffer,
+            index_buffer,
+            vertex_capacity,
+            index_capacity,
+        }
+    }
+
+    /// A buffer with room for the next power of two bytes of `contents`,
+    /// starting with them
+    fn create_buffer(
+        device: &wgpu::Device,
+        label: &str,
+        contents: &[u8],
+        usage: wgpu::BufferUsages
+    ) -> (wgpu::Buffer, usize) {
+        let capacity = contents.len()
+            .next_power_of_two()
+            .max(wgpu::COPY_BUFFER_ALIGNMENT as usize);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: capacity as wgpu::BufferAddress,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: true,
+        });
+        buffer.slice(..).get_mapped_range_mut()[..contents.len()]
+            .copy_from_slice(contents);
+        buffer.unmap();
+
+        (buffer, capacity)
+    }
+
+    /// Write a mesh to the buffers, false if it doesn't fit. The writes have
+    /// to be aligned, the others have to be recreated too
+    pub fn write(&self, queue: &wgpu::Queue, mesh: &Mesh) -> bool {
+        let vertex_data = mesh.vertex_data();
+        let index_data = mesh.index_data();
+        let aligned = |data: &[u8]| {
+            data.len().is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize)
+        };
+        if vertex_data.len() > self.vertex_capacity
+            || index_data.len() > self.index_capacity
+            || !aligned(vertex_data) || !aligned(index_data)
+        {
+            return false;
         }
+
+        queue.write_buffer(&self.vertex_buffer, 0, vertex_data);
+        queue.write_buffer(&self.index_buffer, 0, index_data);
+
+        true
+    }
+
+    /// Write the indices of a mesh with the same amount of them as the one
+    /// on the buffers
+    pub fn write_indices(&self, queue: &wgpu::Queue, mesh: &Mesh) {
+        queue.write_buffer(&self.index_buffer, 0, mesh.index_data());
     }
 
     pub fn render<'a>(
@@ -88,15 +167,3 @@ impl ModelUniform {
         self.uniform.update(queue, transform);
     }
 }
-
-pub struct ModelsUniform {
-    uniform: Uniform
-}
-
-impl From<Uniform> for ModelsUniform {
-    fn from(uniform: Uniform) -> Self {
-        Self {
-            uniform
-        }
-    }
-}
\ No newline at end of file
@@ -23,19 +23,28 @@ impl MouseInput {
             } => {
                 self.scroll = *y;
             }
-            DeviceEvent::Button { 
+            _ => return false
+        }
+
+        true
+    }
+
+    /// The buttons come from the window events, the ids of the raw device
+    /// buttons depend on the platform
+    pub fn process_window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput {
                 button,
-                state: ElementState::Pressed
-            } => {
-                if *button == 0 {
-                    self.left_button = true;
-                } else if *button == 1 {
-                    self.right_button = true;
-                }
-            }
+                state: ElementState::Pressed,
+                ..
+            } => match button {
+                MouseButton::Left => self.left_button = true,
+                MouseButton::Right => self.right_button = true,
+                _ => return false
+            },
             _ => return false
         }
 
-        return true;
+        true
     }
 }
\ No newline at end of file
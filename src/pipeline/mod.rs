@@ -9,6 +9,17 @@ mod voxel_pipeline;
 pub use model_pipeline::ModelPipeline;
 pub use voxel_pipeline::VoxelPipeline;
 
+/// How the fragments of a pipeline are mixed with what's already drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blending {
+    /// Replace what's behind and write the depth
+    Opaque,
+
+    /// Blend over what's behind by the alpha without writing the depth, so it
+    /// has to be drawn after the opaque geometry and back to front
+    Transparent,
+}
+
 pub struct Pipeline {
     pipeline: wgpu::RenderPipeline,
     bind_group: BindGroup,
@@ -20,6 +31,16 @@ impl Pipeline {
         format: wgpu::TextureFormat,
         bind_group: BindGroup,
         shader: wgpu::ShaderModule,
+    ) -> Result<Self> {
+        Self::with_blending(device, format, bind_group, shader, Blending::Opaque)
+    }
+
+    pub fn with_blending(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        bind_group: BindGroup,
+        shader: wgpu::ShaderModule,
+        blending: Blending,
     ) -> Result<Self> {
         // Create the pipeline and add its bind groups, the shader must have
         // fixed entry point names
@@ -44,7 +65,10 @@ impl Pipeline {
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
                     format,
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    blend: Some(match blending {
+                        Blending::Opaque => wgpu::BlendState::REPLACE,
+                        Blending::Transparent => wgpu::BlendState::ALPHA_BLENDING,
+                    }),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -59,7 +83,7 @@ impl Pipeline {
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
+                depth_write_enabled: blending == Blending::Opaque,
                 depth_compare: wgpu::CompareFunction::Less,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default()
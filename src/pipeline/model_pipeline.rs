@@ -23,7 +23,7 @@ impl ModelPipeline {
         );
 
         // Create the uniform group and the uniforms
-        let mut builder = BindGroupBuilder::new(&device);
+        let mut builder = BindGroupBuilder::new(device);
         let camera_uniform = CameraUniform::from(
             builder.create_uniform::<Matrix4<f32>>(wgpu::ShaderStages::VERTEX)
         );
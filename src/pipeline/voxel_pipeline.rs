@@ -1,10 +1,11 @@
 use anyhow::*;
 use cgmath::Matrix4;
 
-use super::Pipeline;
+use super::{Blending, Pipeline};
 use crate::bind_group::{BindGroupBuilder, Storage, GPUWrite};
 use crate::camera::{Camera, CameraUniform};
 use crate::model::ModelUniform;
+use crate::texture::Texture;
 
 pub struct FacesStorage {
     storage: Storage
@@ -14,6 +15,11 @@ impl FacesStorage {
     fn upload_slice(&self, queue: &wgpu::Queue, arr: &[u32]) {
         self.storage.update(queue, arr);
     }
+
+    /// Faces that fit on the buffer
+    fn capacity(&self) -> usize {
+        self.storage.size() as usize / std::mem::size_of::<u32>()
+    }
 }
 
 impl From<Storage> for FacesStorage {
@@ -32,9 +38,32 @@ pub struct VoxelPipeline {
 }
 
 impl VoxelPipeline {
+    /// A pipeline for meshes of up to `faces` faces, see `fits`
     pub fn new(
         device: &wgpu::Device,
         format: wgpu::TextureFormat,
+        atlas: &Texture,
+        faces: usize,
+    ) -> Result<Self> {
+        Self::with_blending(device, format, atlas, faces, Blending::Opaque)
+    }
+
+    /// The variant for the transparent meshes, drawn after the opaque ones
+    pub fn transparent(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        atlas: &Texture,
+        faces: usize,
+    ) -> Result<Self> {
+        Self::with_blending(device, format, atlas, faces, Blending::Transparent)
+    }
+
+    fn with_blending(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        atlas: &Texture,
+        faces: usize,
+        blending: Blending,
     ) -> Result<Self> {
         // Create the shader module
         let shader = device.create_shader_module(
@@ -42,26 +71,41 @@ impl VoxelPipeline {
         );
 
         // Create the uniform group and the uniforms
-        let mut builder = BindGroupBuilder::new(&device);
+        let mut builder = BindGroupBuilder::new(device);
         let camera_uniform = CameraUniform::from(
             builder.create_uniform::<Matrix4<f32>>(wgpu::ShaderStages::VERTEX)
         );
         let model_uniform = ModelUniform::from(
             builder.create_uniform::<Matrix4<f32>>(wgpu::ShaderStages::VERTEX)
         );
+        // Room for a few more faces, so a mesh that grows a bit doesn't need
+        // a new buffer
+        let faces = faces.max(1).next_power_of_two();
         let faces_storage = FacesStorage::from(
-            builder.create_storage::<[u32; 16 * 16 * 16]>(wgpu::ShaderStages::FRAGMENT)
+            builder.create_storage(
+                wgpu::ShaderStages::FRAGMENT,
+                (faces * std::mem::size_of::<u32>()) as u64
+            )
         );
+        builder.register_texture(&atlas.view, &atlas.sampler);
         let uniform_group = builder.build();
 
         Ok(Self {
-            pipeline: Pipeline::new(device, format, uniform_group, shader)?,
+            pipeline: Pipeline::with_blending(
+                device, format, uniform_group, shader, blending
+            )?,
             camera_uniform,
             model_uniform,
             faces_storage
         })
     }
 
+    /// Whether the faces of a mesh fit on the storage buffer, otherwise a
+    /// bigger pipeline has to be made
+    pub fn fits(&self, faces: usize) -> bool {
+        faces <= self.faces_storage.capacity()
+    }
+
     pub fn set_current<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
         self.pipeline.set_current(render_pass);
     }
@@ -0,0 +1,161 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::chunk::{Face, WorldBlockPos};
+
+/// The first solid block a ray goes through
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub pos: WorldBlockPos,
+
+    /// Face of the block the ray got in through
+    pub face: Face,
+
+    /// Distance from the origin of the ray to where it hit the block
+    pub distance: f32,
+}
+
+/// Walk the blocks a ray goes through, in order, until `is_solid` says one of
+/// them is or the ray is longer than `max_distance`. This is the voxel
+/// traversal of Amanatides and Woo, each step goes to the next block boundary
+/// the ray crosses on any of the axes.
+///
+/// The blocks are centered on their position, like the meshes, so the block
+/// at 0, 0, 0 goes from -0.5 to 0.5
+pub fn raycast(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+    mut is_solid: impl FnMut(WorldBlockPos) -> bool
+) -> Option<RayHit> {
+    if direction.magnitude2() == 0.0 {
+        return None;
+    }
+    let direction = direction.normalize();
+
+    // Work with the blocks going from their position to the next one
+    let origin = [origin.x + 0.5, origin.y + 0.5, origin.z + 0.5];
+    let direction = [direction.x, direction.y, direction.z];
+    let [x, y, z] = origin.map(|coord| coord.floor() as i64);
+    let mut pos = WorldBlockPos::new(x, y, z);
+
+    // Per axis, the direction of the steps, the distance along the ray to
+    // cross a whole block and the distance to the next boundary
+    let step = direction.map(|dir| if dir > 0.0 { 1 } else { -1 });
+    let delta = direction.map(|dir| (1.0 / dir).abs());
+    let mut next = [0.0; 3];
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            next[axis] = f32::INFINITY;
+            continue;
+        }
+        let offset = origin[axis] - origin[axis].floor();
+        let to_boundary = if step[axis] > 0 { 1.0 - offset } else { offset };
+        next[axis] = to_boundary * delta[axis];
+    }
+
+    // Faces the ray gets into a block through when stepping on each axis
+    let faces = [
+        if step[0] > 0 { Face::Left } else { Face::Right },
+        if step[1] > 0 { Face::Down } else { Face::Up },
+        if step[2] > 0 { Face::Front } else { Face::Back },
+    ];
+
+    // Starting inside a block hits it right away, through the face that
+    // looks back the most to the origin
+    if is_solid(pos) {
+        let axis = (0..3)
+            .max_by(|a, b| direction[*a].abs().total_cmp(&direction[*b].abs()))
+            .unwrap();
+        return Some(RayHit {
+            pos,
+            face: faces[axis],
+            distance: 0.0,
+        });
+    }
+
+    loop {
+        let axis = (0..3)
+            .min_by(|a, b| next[*a].total_cmp(&next[*b]))
+            .unwrap();
+        let distance = next[axis];
+        if distance > max_distance {
+            return None;
+        }
+
+        match axis {
+            0 => pos.x += step[0],
+            1 => pos.y += step[1],
+            _ => pos.z += step[2],
+        }
+        next[axis] += delta[axis];
+        if is_solid(pos) {
+            return Some(RayHit {
+                pos,
+                face: faces[axis],
+                distance,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    fn hit(
+        origin: [f32; 3],
+        direction: [f32; 3],
+        solid: &[[i64; 3]]
+    ) -> Option<RayHit> {
+        raycast(origin.into(), direction.into(), 100.0, |pos| {
+            solid.contains(&[pos.x, pos.y, pos.z])
+        })
+    }
+
+    #[test]
+    fn axis_aligned_rays() {
+        let solid = [[5, 0, 0], [-5, 0, 0], [0, 3, 0], [0, -3, 0], [0, 0, 7], [0, 0, -7]];
+        let cases = [
+            ([1.0, 0.0, 0.0], [5, 0, 0], Face::Left, 4.5),
+            ([-1.0, 0.0, 0.0], [-5, 0, 0], Face::Right, 4.5),
+            ([0.0, 1.0, 0.0], [0, 3, 0], Face::Down, 2.5),
+            ([0.0, -1.0, 0.0], [0, -3, 0], Face::Up, 2.5),
+            ([0.0, 0.0, 1.0], [0, 0, 7], Face::Front, 6.5),
+            ([0.0, 0.0, -1.0], [0, 0, -7], Face::Back, 6.5),
+        ];
+        for (direction, [x, y, z], face, distance) in cases {
+            assert_eq!(
+                hit([0.0, 0.0, 0.0], direction, &solid),
+                Some(RayHit { pos: WorldBlockPos::new(x, y, z), face, distance })
+            );
+        }
+    }
+
+    #[test]
+    fn diagonal_and_negative_coordinates() {
+        // Crossing a section border and going below 0
+        let result = hit([14.0, 0.0, -1.2], [1.0, 0.0, -1.0], &[[17, 0, -4]]).unwrap();
+        assert_eq!(result.pos, WorldBlockPos::new(17, 0, -4));
+        assert_eq!(result.face, Face::Left);
+        assert!((result.distance - 2.5 * 2f32.sqrt()).abs() < 1e-4);
+
+        // Misses the block by a bit
+        assert_eq!(hit([0.0, 0.0, 0.0], [1.0, 0.2, 0.0], &[[5, 2, 0]]), None);
+        assert_eq!(hit([0.0, 0.0, 0.0], [1.0, 0.4, 0.0], &[[5, 2, 0]]).unwrap().face, Face::Left);
+        assert_eq!(hit([-0.3, 0.0, 0.0], [1.0, 0.6, 0.0], &[[4, 3, 0]]).unwrap().face, Face::Down);
+    }
+
+    #[test]
+    fn limits() {
+        assert_eq!(
+            raycast([0.0; 3].into(), [1.0, 0.0, 0.0].into(), 4.0, |pos| pos.x == 5),
+            None
+        );
+        assert_eq!(hit([0.0; 3], [0.0; 3], &[[0, 0, 0]]), None);
+        assert_eq!(
+            hit([0.2, 0.0, 0.0], [0.0, -1.0, 0.3], &[[0, 0, 0]]),
+            Some(RayHit { pos: WorldBlockPos::new(0, 0, 0), face: Face::Up, distance: 0.0 })
+        );
+    }
+}
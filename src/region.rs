@@ -0,0 +1,754 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+use anyhow::*;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use crate::biome::Biome;
+use crate::block::{Block, BlockRegistry};
+use crate::chunk::{
+    BlockPos, ChunkColumn, ChunkPos, PalettedStorage, Section, COLUMN_HEIGHT,
+    COLUMN_SECTIONS, SECTION_SIZE
+};
+use crate::world::generator::{BlockEdit, BlockEdits};
+
+/// Columns per side of a region, a region file holds `REGION_SIZE²` columns
+pub const REGION_SIZE: i32 = 32;
+
+/// Bumped every time the layout of the region files changes
+pub const REGION_VERSION: u32 = 3;
+
+const MAGIC: &[u8; 4] = b"VXRG";
+
+/// Columns of a region
+const COLUMNS: usize = (REGION_SIZE * REGION_SIZE) as usize;
+
+/// Bytes taken by each payload on the offset table, `offset`, `length` and
+/// `checksum` as `u32`s
+const ENTRY_SIZE: usize = 12;
+
+/// The magic, the version and an entry for each column and for the edits
+/// waiting for each column
+const HEADER_SIZE: usize = MAGIC.len() + 4 + 2 * COLUMNS * ENTRY_SIZE;
+
+pub fn read_u8(input: &mut impl Read) -> Result<u8> {
+    let mut bytes = [0; 1];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub fn read_u16(input: &mut impl Read) -> Result<u16> {
+    let mut bytes = [0; 2];
+    input.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+pub fn read_u32(input: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn read_u64(input: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Position of a region, in regions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RegionPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl RegionPos {
+    /// The region a column belongs to
+    pub fn of(chunk_pos: ChunkPos) -> Self {
+        Self {
+            x: chunk_pos.x.div_euclid(REGION_SIZE),
+            z: chunk_pos.z.div_euclid(REGION_SIZE),
+        }
+    }
+
+    /// Index of a column of this region on the offset table
+    fn index(chunk_pos: ChunkPos) -> usize {
+        (chunk_pos.z.rem_euclid(REGION_SIZE) * REGION_SIZE
+            + chunk_pos.x.rem_euclid(REGION_SIZE)) as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Missing,
+
+    /// Compressed payload, its checksum matched
+    Stored(Vec<u8>),
+
+    /// What was wrong with the payload when the file was read, with the bytes
+    /// and checksum it had so they are written back as they were
+    Corrupted {
+        error: String,
+        payload: Vec<u8>,
+        checksum: u32,
+    },
+}
+
+/// The columns of a region file and the blocks of the features waiting for
+/// them, kept compressed until asked for.
+///
+/// The file is a header followed by the payloads:
+/// - `"VXRG"` and the format version as `u32`
+/// - The offset table, an `offset`, `length` and crc32 `checksum` (all `u32`)
+///   per column ordered by `z` and then `x`, followed by the same for the
+///   edits waiting for each column. An offset of 0 means there is nothing
+///   stored
+/// - The zlib compressed payloads, see `encode_column` and `encode_edits`
+///   for their contents
+///
+/// Everything is little endian
+#[derive(Debug, Clone)]
+pub struct Region {
+    /// The columns and then the edits of each column
+    entries: Vec<Entry>,
+}
+
+impl Region {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![Entry::Missing; 2 * COLUMNS],
+        }
+    }
+
+    /// Parse a region file, a damaged header fails the whole region but a
+    /// damaged column only fails when that column is read
+    pub fn read(bytes: &[u8]) -> Result<Self> {
+        ensure!(bytes.len() >= HEADER_SIZE, "Region header is truncated");
+        ensure!(&bytes[..MAGIC.len()] == MAGIC, "Not a region file");
+        let mut header = &bytes[MAGIC.len()..HEADER_SIZE];
+        let version = read_u32(&mut header)?;
+        ensure!(
+            version == REGION_VERSION,
+            "Unsupported region version {}, expected {}", version, REGION_VERSION
+        );
+
+        let mut region = Self::new();
+        for entry in region.entries.iter_mut() {
+            let offset = read_u32(&mut header)? as usize;
+            let length = read_u32(&mut header)? as usize;
+            let checksum = read_u32(&mut header)?;
+            if offset == 0 {
+                continue;
+            }
+
+            let corrupted = |error: &str, payload: &[u8]| Entry::Corrupted {
+                error: error.into(),
+                payload: payload.to_vec(),
+                checksum,
+            };
+            *entry = match bytes.get(offset..offset + length) {
+                None => corrupted(
+                    "Payload past the end of the file",
+                    bytes.get(offset..).unwrap_or_default()
+                ),
+                Some(payload) if crc32fast::hash(payload) != checksum => {
+                    corrupted("Payload checksum mismatch", payload)
+                }
+                Some(payload) => Entry::Stored(payload.to_vec()),
+            };
+        }
+
+        Ok(region)
+    }
+
+    /// Serialize the region to the file layout
+    pub fn write(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend(MAGIC);
+        header.extend(REGION_VERSION.to_le_bytes());
+
+        let mut payloads: Vec<u8> = Vec::new();
+        for entry in &self.entries {
+            // The corrupted payloads keep their bytes and checksum, so they
+            // still fail to load but can be recovered by hand
+            let (payload, checksum) = match entry {
+                Entry::Missing => {
+                    header.extend([0; ENTRY_SIZE]);
+                    continue;
+                }
+                Entry::Stored(payload) => (payload, crc32fast::hash(payload)),
+                Entry::Corrupted { payload, checksum, .. } => (payload, *checksum),
+            };
+            header.extend(((HEADER_SIZE + payloads.len()) as u32).to_le_bytes());
+            header.extend((payload.len() as u32).to_le_bytes());
+            header.extend(checksum.to_le_bytes());
+            payloads.extend(payload);
+        }
+
+        header.extend(payloads);
+        header
+    }
+
+    /// Decode a column, `None` if the region doesn't have it
+    pub fn column(
+        &self,
+        chunk_pos: ChunkPos,
+        registry: &BlockRegistry
+    ) -> Result<Option<ChunkColumn>> {
+        match &self.entries[RegionPos::index(chunk_pos)] {
+            Entry::Missing => Ok(None),
+            Entry::Corrupted { error, .. } => bail!("{}", error),
+            Entry::Stored(payload) => {
+                decode_column(chunk_pos, payload, registry).map(Some)
+            }
+        }
+    }
+
+    /// Store a column, replacing the previous one
+    pub fn set_column(&mut self, column: &ChunkColumn, registry: &BlockRegistry) {
+        self.entries[RegionPos::index(column.pos())] =
+            Entry::Stored(encode_column(column, registry));
+    }
+
+    /// Decode the edits waiting for a column, in the order they were made
+    pub fn edits(&self, chunk_pos: ChunkPos, registry: &BlockRegistry) -> Result<Vec<BlockEdit>> {
+        match &self.entries[COLUMNS + RegionPos::index(chunk_pos)] {
+            Entry::Missing => Ok(Vec::new()),
+            Entry::Corrupted { error, .. } => bail!("{}", error),
+            Entry::Stored(payload) => decode_edits(payload, registry),
+        }
+    }
+
+    /// Store the edits waiting for a column, replacing the previous ones.
+    /// Without edits there is nothing stored
+    pub fn set_edits(&mut self, chunk_pos: ChunkPos, edits: &[BlockEdit], registry: &BlockRegistry) {
+        self.entries[COLUMNS + RegionPos::index(chunk_pos)] = if edits.is_empty() {
+            Entry::Missing
+        } else {
+            Entry::Stored(encode_edits(edits, registry))
+        };
+    }
+}
+
+/// Write the names of the block states a payload refers to by index, as a
+/// `u16` amount followed by each name (like `log[axis=x]`) as a `u16` length
+/// and the utf8 bytes
+fn write_names(
+    output: &mut impl Write,
+    blocks: &[Block],
+    registry: &BlockRegistry
+) -> std::io::Result<()> {
+    output.write_all(&(blocks.len() as u16).to_le_bytes())?;
+    for block in blocks {
+        let name = registry.state_name(*block);
+        output.write_all(&(name.len() as u16).to_le_bytes())?;
+        output.write_all(name.as_bytes())?;
+    }
+
+    std::io::Result::Ok(())
+}
+
+/// Read the names written by `write_names` as blocks of `registry`
+fn read_names(input: &mut impl Read, registry: &BlockRegistry) -> Result<Vec<Block>> {
+    let block_count = read_u16(input)?;
+    let mut blocks = Vec::with_capacity(block_count as usize);
+    for _ in 0..block_count {
+        let mut name = vec![0; read_u16(input)? as usize];
+        input.read_exact(&mut name)?;
+        let name = String::from_utf8(name).context("Block name isn't utf8")?;
+        blocks.push(registry.parse(&name)?);
+    }
+
+    Ok(blocks)
+}
+
+/// Index of a block on a table of names, added to it the first time
+fn name_index(blocks: &mut Vec<Block>, block: Block) -> u16 {
+    match blocks.iter().position(|b| *b == block) {
+        Some(index) => index as u16,
+        None => {
+            blocks.push(block);
+            blocks.len() as u16 - 1
+        }
+    }
+}
+
+/// Compress a column, the uncompressed payload is:
+/// - The different block states, see `write_names`. The sections refer to
+///   the states by their index here, so the file doesn't depend on the order
+///   the blocks are registered
+/// - The id of the biome of every `x`, `z` as a `u8`, ordered by `z` and then
+///   `x`
+/// - Every section from the bottom, see `PalettedStorage::write`
+fn encode_column(column: &ChunkColumn, registry: &BlockRegistry) -> Vec<u8> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut sections = Vec::new();
+    for section in column.sections() {
+        section.blocks().write(&mut sections, |block| name_index(&mut blocks, block));
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let mut write = || -> std::io::Result<()> {
+        write_names(&mut encoder, &blocks, registry)?;
+        let biomes: Vec<u8> = column.biomes().iter().map(|biome| biome.id()).collect();
+        encoder.write_all(&biomes)?;
+        encoder.write_all(&sections)
+    };
+    write().expect("Writing to memory can't fail");
+
+    encoder.finish().expect("Writing to memory can't fail")
+}
+
+fn decode_column(
+    chunk_pos: ChunkPos,
+    payload: &[u8],
+    registry: &BlockRegistry
+) -> Result<ChunkColumn> {
+    let mut input = ZlibDecoder::new(payload);
+
+    let blocks = read_names(&mut input, registry)?;
+    let block = |index: u16| {
+        blocks.get(index as usize)
+            .copied()
+            .with_context(|| format!("Block {} isn't on the column table", index))
+    };
+
+    let mut biomes = Vec::with_capacity(SECTION_SIZE * SECTION_SIZE);
+    for _ in 0..SECTION_SIZE * SECTION_SIZE {
+        let id = read_u8(&mut input)?;
+        biomes.push(Biome::from_id(id).with_context(|| format!("Unknown biome {}", id))?);
+    }
+
+    let mut sections = Vec::with_capacity(COLUMN_SECTIONS);
+    for y in 0..COLUMN_SECTIONS {
+        let storage = PalettedStorage::read(
+            SECTION_SIZE * SECTION_SIZE * SECTION_SIZE,
+            &mut input,
+            block
+        )?;
+        sections.push(Section::with_blocks(chunk_pos.section(y as i32), storage));
+    }
+    ensure!(
+        input.read(&mut [0])? == 0,
+        "Unexpected data after the last section"
+    );
+
+    let mut column = ChunkColumn::from_sections(chunk_pos, sections);
+    for (i, biome) in biomes.into_iter().enumerate() {
+        column.set_biome(i % SECTION_SIZE, i / SECTION_SIZE, biome);
+    }
+
+    Ok(column)
+}
+
+/// Compress the edits waiting for a column, the uncompressed payload is:
+/// - The block states they write, see `write_names`
+/// - The amount of edits as `u32`, followed by the `x` as `u8`, `y` as `u16`
+///   and `z` as `u8` of each one, the index of its block as `u16` and
+///   whether it overwrites as `u8`
+fn encode_edits(edits: &[BlockEdit], registry: &BlockRegistry) -> Vec<u8> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut list = Vec::with_capacity(edits.len() * 7);
+    for edit in edits {
+        let BlockPos { x, y, z } = edit.block_pos;
+        list.push(x as u8);
+        list.extend((y as u16).to_le_bytes());
+        list.push(z as u8);
+        list.extend(name_index(&mut blocks, edit.block).to_le_bytes());
+        list.push(edit.overwrite as u8);
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let mut write = || -> std::io::Result<()> {
+        write_names(&mut encoder, &blocks, registry)?;
+        encoder.write_all(&(edits.len() as u32).to_le_bytes())?;
+        encoder.write_all(&list)
+    };
+    write().expect("Writing to memory can't fail");
+
+    encoder.finish().expect("Writing to memory can't fail")
+}
+
+fn decode_edits(payload: &[u8], registry: &BlockRegistry) -> Result<Vec<BlockEdit>> {
+    let mut input = ZlibDecoder::new(payload);
+
+    let blocks = read_names(&mut input, registry)?;
+    let count = read_u32(&mut input)?;
+    let mut edits = Vec::new();
+    for _ in 0..count {
+        let block_pos = BlockPos::new(
+            read_u8(&mut input)? as usize,
+            read_u16(&mut input)? as usize,
+            read_u8(&mut input)? as usize
+        );
+        ensure!(
+            block_pos.x < SECTION_SIZE && block_pos.y < COLUMN_HEIGHT && block_pos.z < SECTION_SIZE,
+            "Edit at {:?} out of the column", block_pos
+        );
+        let index = read_u16(&mut input)?;
+        let block = blocks.get(index as usize)
+            .copied()
+            .with_context(|| format!("Block {} isn't on the edits table", index))?;
+        edits.push(BlockEdit {
+            block_pos,
+            block,
+            overwrite: read_u8(&mut input)? != 0,
+        });
+    }
+    ensure!(
+        input.read(&mut [0])? == 0,
+        "Unexpected data after the last edit"
+    );
+
+    Ok(edits)
+}
+
+/// A directory of region files, named `r.<x>.<z>.region`. The regions that
+/// have been used are kept in memory
+pub struct RegionStorage {
+    dir: PathBuf,
+    regions: HashMap<RegionPos, Region>,
+}
+
+impl RegionStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            regions: HashMap::new(),
+        }
+    }
+
+    fn path(&self, region_pos: RegionPos) -> PathBuf {
+        self.dir.join(format!("r.{}.{}.region", region_pos.x, region_pos.z))
+    }
+
+    /// Get a region from memory or disk, a missing file is an empty region
+    fn region(&mut self, region_pos: RegionPos) -> Result<&mut Region> {
+        if !self.regions.contains_key(&region_pos) {
+            let path = self.path(region_pos);
+            let region = match fs::read(&path) {
+                Result::Ok(bytes) => Region::read(&bytes)
+                    .with_context(|| format!("Failed to read {}", path.display()))?,
+                Err(e) if e.kind() == ErrorKind::NotFound => Region::new(),
+                Err(e) => return Err(e)
+                    .with_context(|| format!("Failed to open {}", path.display())),
+            };
+            self.regions.insert(region_pos, region);
+        }
+
+        Ok(self.regions.get_mut(&region_pos).unwrap())
+    }
+
+    /// Move a region file that can't be read out of the way, as
+    /// `r.<x>.<z>.region.corrupt`, and start an empty region in its place.
+    /// Fails with `error` if the file can't be moved
+    fn back_up(&mut self, region_pos: RegionPos, error: Error) -> Result<()> {
+        let path = self.path(region_pos);
+        let backup = path.with_extension("region.corrupt");
+        if fs::rename(&path, &backup).is_err() {
+            return Err(error);
+        }
+        log::warn!("{:#}, moved it to {}", error, backup.display());
+        self.regions.insert(region_pos, Region::new());
+
+        Ok(())
+    }
+
+    /// Read a column, `None` if it was never saved
+    pub fn load_column(
+        &mut self,
+        chunk_pos: ChunkPos,
+        registry: &BlockRegistry
+    ) -> Result<Option<ChunkColumn>> {
+        self.region(RegionPos::of(chunk_pos))?
+            .column(chunk_pos, registry)
+            .with_context(|| format!("Failed to load column {:?}", chunk_pos))
+    }
+
+    /// Write the columns to their region files, every touched file is
+    /// rewritten whole
+    pub fn save_columns<'a>(
+        &mut self,
+        columns: impl IntoIterator<Item = &'a ChunkColumn>,
+        registry: &BlockRegistry
+    ) -> Result<()> {
+        let mut touched = Vec::new();
+        for column in columns {
+            let region_pos = RegionPos::of(column.pos());
+            if let Err(e) = self.region(region_pos) {
+                self.back_up(region_pos, e)?;
+            }
+            self.regions.get_mut(&region_pos).unwrap().set_column(column, registry);
+            if !touched.contains(&region_pos) {
+                touched.push(region_pos);
+            }
+        }
+
+        self.write_regions(touched)
+    }
+
+    /// Take the edits waiting for a column, they are gone from the storage
+    /// once the region is written again
+    pub fn take_edits(
+        &mut self,
+        chunk_pos: ChunkPos,
+        registry: &BlockRegistry
+    ) -> Result<Vec<BlockEdit>> {
+        let region = self.region(RegionPos::of(chunk_pos))?;
+        let edits = region.edits(chunk_pos, registry)
+            .with_context(|| format!("Failed to load the edits of column {:?}", chunk_pos))?;
+        region.set_edits(chunk_pos, &[], registry);
+
+        Ok(edits)
+    }
+
+    /// Write the edits waiting for each column to their region files,
+    /// replacing the ones stored for those columns
+    pub fn save_edits(&mut self, edits: &BlockEdits, registry: &BlockRegistry) -> Result<()> {
+        let mut touched = Vec::new();
+        for (chunk_pos, edits) in edits.iter() {
+            let region_pos = RegionPos::of(chunk_pos);
+            if let Err(e) = self.region(region_pos) {
+                self.back_up(region_pos, e)?;
+            }
+            self.regions.get_mut(&region_pos).unwrap().set_edits(chunk_pos, edits, registry);
+            if !touched.contains(&region_pos) {
+                touched.push(region_pos);
+            }
+        }
+
+        self.write_regions(touched)
+    }
+
+    /// Rewrite whole the files of the regions
+    fn write_regions(&self, touched: Vec<RegionPos>) -> Result<()> {
+        if touched.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        for region_pos in touched {
+            // Write next to it and rename so a crash never leaves half a file
+            let path = self.path(region_pos);
+            let tmp = path.with_extension("region.tmp");
+            fs::write(&tmp, self.regions[&region_pos].write())
+                .with_context(|| format!("Failed to write {}", tmp.display()))?;
+            fs::rename(&tmp, &path)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+    use crate::chunk::BlockPos;
+
+    /// An empty directory of the system temp dir only used by one test
+    pub fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("wgpu-renderer-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn column(registry: &BlockRegistry, chunk_pos: ChunkPos) -> ChunkColumn {
+        let stone = registry.by_name("stone").unwrap();
+        let grass = registry.by_name("grass").unwrap();
+        let mut column = ChunkColumn::new(chunk_pos);
+        for x in 0..16 {
+            for z in 0..16 {
+                let height = (x * 7 + z * 3 + chunk_pos.x.unsigned_abs() as usize) % 40;
+                for y in 0..height {
+                    column.place_block(BlockPos::new(x, y, z), stone);
+                }
+                column.place_block(BlockPos::new(x, height, z), grass);
+            }
+        }
+        column.place_block(BlockPos::new(3, 255, 3), grass);
+        column.place_block(BlockPos::new(3, 254, 3), registry.parse("log[axis=z]").unwrap());
+        column.set_biome(4, 9, Biome::Desert);
+        column.set_biome(15, 15, Biome::Ocean);
+        column
+    }
+
+    fn blocks(column: &ChunkColumn) -> Vec<Block> {
+        column.sections()
+            .flat_map(|section| section.iter().map(|block| *block))
+            .collect()
+    }
+
+    #[test]
+    fn columns_round_trip() {
+        let registry = BlockRegistry::with_default_blocks();
+        let dir = test_dir("round-trip");
+        // The first two share a region, the rest are on their own one with
+        // negative coordinates
+        let positions = [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(31, 31),
+            ChunkPos::new(-1, 0),
+            ChunkPos::new(40, -33),
+        ];
+        let columns: Vec<ChunkColumn> = positions.iter()
+            .map(|pos| column(&registry, *pos))
+            .collect();
+
+        RegionStorage::new(&dir).save_columns(&columns, &registry).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+        let mut storage = RegionStorage::new(&dir);
+        for column in &columns {
+            let loaded = storage.load_column(column.pos(), &registry)
+                .unwrap()
+                .unwrap();
+            assert_eq!(loaded.pos(), column.pos());
+            assert_eq!(blocks(&loaded), blocks(column));
+            assert_eq!(loaded.biomes(), column.biomes());
+        }
+        assert!(storage.load_column(ChunkPos::new(1, 0), &registry).unwrap().is_none());
+
+        // Saving again only replaces the saved column
+        let mut changed = column(&registry, ChunkPos::new(0, 0));
+        changed.place_block(BlockPos::new(0, 100, 0), Block::AIR);
+        changed.place_block(BlockPos::new(0, 0, 0), Block::AIR);
+        storage.save_columns([&changed], &registry).unwrap();
+        let mut storage = RegionStorage::new(&dir);
+        let loaded = storage.load_column(ChunkPos::new(0, 0), &registry).unwrap().unwrap();
+        assert_eq!(blocks(&loaded), blocks(&changed));
+        let loaded = storage.load_column(ChunkPos::new(31, 31), &registry).unwrap().unwrap();
+        assert_eq!(blocks(&loaded), blocks(&columns[1]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn edits_round_trip() {
+        let registry = BlockRegistry::with_default_blocks();
+        let edits = vec![
+            BlockEdit {
+                block_pos: BlockPos::new(15, 255, 0),
+                block: registry.parse("log[axis=z]").unwrap(),
+                overwrite: true,
+            },
+            BlockEdit {
+                block_pos: BlockPos::new(0, 70, 9),
+                block: registry.by_name("leaves").unwrap(),
+                overwrite: false,
+            },
+        ];
+        let mut region = Region::new();
+        region.set_column(&column(&registry, ChunkPos::new(3, 1)), &registry);
+        region.set_edits(ChunkPos::new(3, 1), &edits, &registry);
+        region.set_edits(ChunkPos::new(4, 1), &edits[1..], &registry);
+
+        // The edits of a column are apart from the column itself
+        let mut region = Region::read(&region.write()).unwrap();
+        assert_eq!(region.edits(ChunkPos::new(3, 1), &registry).unwrap(), edits);
+        assert_eq!(region.edits(ChunkPos::new(4, 1), &registry).unwrap(), &edits[1..]);
+        assert!(region.column(ChunkPos::new(4, 1), &registry).unwrap().is_none());
+        assert!(region.edits(ChunkPos::new(5, 1), &registry).unwrap().is_empty());
+        region.set_edits(ChunkPos::new(3, 1), &[], &registry);
+        let region = Region::read(&region.write()).unwrap();
+        assert!(region.edits(ChunkPos::new(3, 1), &registry).unwrap().is_empty());
+        assert!(region.column(ChunkPos::new(3, 1), &registry).unwrap().is_some());
+    }
+
+    #[test]
+    fn unreadable_regions_are_backed_up() {
+        let registry = BlockRegistry::with_default_blocks();
+        let dir = test_dir("backed-up");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("r.0.0.region");
+        fs::write(&path, b"not a region").unwrap();
+
+        let mut storage = RegionStorage::new(&dir);
+        assert!(storage.load_column(ChunkPos::new(0, 0), &registry).is_err());
+        storage.save_columns([&column(&registry, ChunkPos::new(1, 0))], &registry).unwrap();
+
+        // The old file is kept aside untouched and the new one has the column
+        assert_eq!(fs::read(dir.join("r.0.0.region.corrupt")).unwrap(), b"not a region");
+        let mut storage = RegionStorage::new(&dir);
+        assert!(storage.load_column(ChunkPos::new(1, 0), &registry).unwrap().is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn partial_files() {
+        let registry = BlockRegistry::with_default_blocks();
+        let mut region = Region::new();
+        region.set_column(&column(&registry, ChunkPos::new(0, 0)), &registry);
+        region.set_column(&column(&registry, ChunkPos::new(1, 0)), &registry);
+        let bytes = region.write();
+
+        // Without the end of the last column only that one is lost
+        let truncated = Region::read(&bytes[..bytes.len() - 10]).unwrap();
+        assert!(truncated.column(ChunkPos::new(0, 0), &registry).unwrap().is_some());
+        assert!(truncated.column(ChunkPos::new(1, 0), &registry).is_err());
+        // Writing it again keeps what was left of the lost one
+        let rewritten = truncated.write();
+        assert_eq!(rewritten.len(), bytes.len() - 10);
+        let truncated = Region::read(&rewritten).unwrap();
+        assert!(truncated.column(ChunkPos::new(0, 0), &registry).unwrap().is_some());
+        assert!(truncated.column(ChunkPos::new(1, 0), &registry).is_err());
+
+        // Without the full header nothing can be trusted
+        assert!(Region::read(&bytes[..HEADER_SIZE - 1]).is_err());
+        assert!(Region::read(&[]).is_err());
+    }
+
+    #[test]
+    fn corrupted_files() {
+        let registry = BlockRegistry::with_default_blocks();
+        let mut region = Region::new();
+        region.set_column(&column(&registry, ChunkPos::new(0, 0)), &registry);
+        region.set_column(&column(&registry, ChunkPos::new(1, 0)), &registry);
+        let bytes = region.write();
+
+        let mut damaged_bytes = bytes.clone();
+        damaged_bytes[HEADER_SIZE + 20] ^= 0xff;
+        let mut damaged = Region::read(&damaged_bytes).unwrap();
+        assert!(damaged.column(ChunkPos::new(0, 0), &registry).is_err());
+        assert!(damaged.column(ChunkPos::new(1, 0), &registry).unwrap().is_some());
+        // The damaged column is written back as it was, still failing to load
+        assert_eq!(damaged.write(), damaged_bytes);
+        damaged.set_column(&column(&registry, ChunkPos::new(2, 0)), &registry);
+        let damaged = Region::read(&damaged.write()).unwrap();
+        assert!(damaged.column(ChunkPos::new(0, 0), &registry).is_err());
+        assert!(damaged.column(ChunkPos::new(2, 0), &registry).unwrap().is_some());
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(Region::read(&wrong_magic).is_err());
+
+        let mut newer = bytes.clone();
+        newer[4..8].copy_from_slice(&(REGION_VERSION + 1).to_le_bytes());
+        assert!(Region::read(&newer).is_err());
+
+        // A payload that passes the checksum but isn't a column
+        let mut region = Region::new();
+        region.entries[0] = Entry::Stored(vec![1, 2, 3]);
+        let garbage = Region::read(&region.write()).unwrap();
+        assert!(garbage.column(ChunkPos::new(0, 0), &registry).is_err());
+
+        // A block this registry doesn't know about
+        let mut other = BlockRegistry::with_default_blocks();
+        other.register(crate::block::BlockType::new("marble"));
+        let marble = other.by_name("marble").unwrap();
+        let mut column = ChunkColumn::new(ChunkPos::new(0, 0));
+        column.place_block(BlockPos::new(0, 0, 0), marble);
+        let mut region = Region::new();
+        region.set_column(&column, &other);
+        let unknown = Region::read(&region.write()).unwrap();
+        assert!(unknown.column(ChunkPos::new(0, 0), &registry).is_err());
+        assert!(unknown.column(ChunkPos::new(0, 0), &other).unwrap().is_some());
+    }
+}
@@ -1,57 +1,207 @@
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::rc::Rc;
+use std::sync::Arc;
 
 use anyhow::*;
 
 use crate::pipeline::ModelPipeline;
 use crate::model::Model;
 use crate::mesh::{Mesh, MeshBuilder};
-use crate::chunk::{BlockPos, ChunkPos,Chunk, Block};
+use crate::block::BlockRegistry;
+use crate::chunk::{
+    BlockPos, Chunk, ChunkNeighborhood, ChunkPos, MeshingMode, SectionPos, COLUMN_SECTIONS,
+    SECTION_SIZE
+};
 use crate::camera::Camera;
 use crate::world::World;
+use crate::atlas::TextureAtlas;
+use crate::texture::Texture;
+use crate::workers::{self, WorkerPool};
 
 mod model_renderer;
 mod voxel_renderer;
 
-pub use model_renderer::ModelRenderer;
-pub use voxel_renderer::ChunkRenderer;
+pub use voxel_renderer::{ChunkMesh, ChunkRenderer};
 
+/// A mesh a section is waiting for from the workers
+struct PendingMesh {
+    /// Id of its job, the meshes of the jobs sent before it are stale
+    job: u64,
+
+    /// Generations of the neighborhood it's made from
+    generations: [Option<u64>; 27],
+}
+
+/// Renders the sections of the world, only the ones with something to draw
+/// have a `ChunkRenderer`. The sections are meshed on the workers, the
+/// closest to the camera first, and only their buffers are made here
 pub struct ChunksRenderer {
-    renderers: HashMap<ChunkPos, ChunkRenderer>,
+    renderers: HashMap<SectionPos, ChunkRenderer>,
+
+    /// How the chunks loaded from now on are meshed
+    meshing_mode: MeshingMode,
+
+    /// Block textures the chunks are drawn with
+    atlas: Rc<Texture>,
+
+    workers: WorkerPool<SectionPos, ChunkMesh>,
+
+    /// Last mesh sent to the workers for each section
+    pending: HashMap<SectionPos, PendingMesh>,
+
+    /// Meshes of each section that failed in a row
+    failures: HashMap<SectionPos, u32>,
 }
 
 impl ChunksRenderer {
-    pub fn new() -> Self {
+    /// Meshes of a section that can fail in a row before it's given up on,
+    /// the meshing panics again for the same blocks
+    const MESH_ATTEMPTS: u32 = 3;
+
+    pub fn new(meshing_mode: MeshingMode, atlas: Rc<Texture>, threads: usize) -> Self {
         Self {
             renderers: HashMap::new(),
+            meshing_mode,
+            atlas,
+            workers: WorkerPool::new("meshing", threads),
+            pending: HashMap::new(),
+            failures: HashMap::new(),
         }
     }
 
-    pub fn load_chunk(
-        &mut self,
-        device: &wgpu::Device,
-        format: wgpu::TextureFormat,
-        chunk_pos: ChunkPos,
-    ) -> Result<()> {
-        self.renderers.insert(chunk_pos, ChunkRenderer::new(device, format)?);
+    /// Stop drawing a section, its buffers are freed
+    pub fn unload_chunk(&mut self, section_pos: SectionPos) {
+        self.cancel_mesh(section_pos);
+        self.failures.remove(&section_pos);
+        self.renderers.remove(&section_pos);
+    }
 
-        Ok(())
+    /// Stop drawing every section of a column
+    pub fn unload_column(&mut self, chunk_pos: ChunkPos) {
+        for y in 0..COLUMN_SECTIONS as i32 {
+            self.unload_chunk(chunk_pos.section(y));
+        }
     }
 
-    pub fn unload_chunk(&mut self, chunk_pos: ChunkPos) {
-        self.renderers.remove(&chunk_pos);
+    /// Sections with something to draw
+    pub fn chunk_count(&self) -> usize {
+        self.renderers.len()
     }
-    
-    pub fn update_chunk<
+
+    /// Move the point the closest sections are meshed first from
+    pub fn set_center(&mut self, section_pos: SectionPos) {
+        self.workers.set_center([section_pos.x, section_pos.y, section_pos.z]);
+    }
+
+    /// Sections waiting for their mesh
+    pub fn pending_meshes(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Send a section to be remeshed if anything around it changed since
+    /// its last mesh, the one it was waiting for is dropped. An empty one
+    /// loses its renderer right away
+    pub fn schedule_chunk<
         const L: usize,
         const H: usize
     >(
         &mut self,
-        device: &wgpu::Device,
-        chunk: &Chunk<L, H>
+        neighborhood: &ChunkNeighborhood<L, H>,
+        registry: &Arc<BlockRegistry>
     ) {
-        let renderer = self.renderers.get_mut(&chunk.pos())
-            .expect("ChunkRenderer not found");
-        renderer.update_model(device, chunk);
+        let section_pos = neighborhood.center().pos();
+        if neighborhood.center().is_empty() {
+            self.unload_chunk(section_pos);
+            return;
+        }
+
+        let generations = neighborhood.generations();
+        let up_to_date = match self.pending.get(&section_pos) {
+            Some(pending) => pending.generations == generations,
+            None => self.renderers.get(&section_pos)
+                .is_some_and(|renderer| renderer.generations() == Some(generations)),
+        };
+        if up_to_date {
+            return;
+        }
+
+        self.cancel_mesh(section_pos);
+        let snapshot = neighborhood.snapshot();
+        let registry = registry.clone();
+        let meshing_mode = self.meshing_mode;
+        let job = self.workers.spawn(
+            section_pos,
+            [section_pos.x, section_pos.y, section_pos.z],
+            move || ChunkMesh::new(&snapshot.neighborhood(), &registry, meshing_mode)
+        );
+        self.pending.insert(section_pos, PendingMesh {
+            job,
+            generations,
+        });
+    }
+
+    /// Drop the mesh a section is waiting for, if it's already being made
+    /// it's dropped when it comes back
+    fn cancel_mesh(&mut self, section_pos: SectionPos) {
+        if self.pending.remove(&section_pos).is_some() {
+            self.workers.cancel(&section_pos);
+        }
+    }
+
+    /// Make the buffers of the meshes the workers are done with, creating the
+    /// renderer of a section the first time it has something to draw and
+    /// dropping it once it's empty. The stale meshes are dropped. Returns the
+    /// sections whose meshing failed to be scheduled again, they keep their
+    /// old mesh meanwhile. After `MESH_ATTEMPTS` failures in a row a section
+    /// isn't returned anymore
+    pub fn receive_meshes(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat
+    ) -> Result<Vec<SectionPos>> {
+        let mut failed = Vec::new();
+        for (section_pos, job, mesh) in self.workers.finished() {
+            if self.pending.get(&section_pos).is_none_or(|pending| pending.job != job) {
+                continue;
+            }
+            self.pending.remove(&section_pos);
+            let mesh = match mesh {
+                Result::Ok(mesh) => mesh,
+                Err(e) => {
+                    let failures = self.failures.entry(section_pos).or_insert(0);
+                    *failures += 1;
+                    if *failures == 1 {
+                        log::warn!("{:#}, section {:?} is meshed again", e, section_pos);
+                    }
+                    if *failures < Self::MESH_ATTEMPTS {
+                        failed.push(section_pos);
+                    } else if *failures == Self::MESH_ATTEMPTS {
+                        log::error!(
+                            "Meshing section {:?} failed {} times, giving up",
+                            section_pos, failures
+                        );
+                    }
+                    continue;
+                }
+            };
+            self.failures.remove(&section_pos);
+            if mesh.is_empty() {
+                self.renderers.remove(&section_pos);
+                continue;
+            }
+
+            let renderer = match self.renderers.entry(section_pos) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    ChunkRenderer::new(self.meshing_mode, self.atlas.clone())
+                ),
+            };
+            renderer.set_mesh(device, queue, format, mesh)?;
+        }
+
+        Ok(failed)
     }
 
     pub fn prepare_chunk<
@@ -63,9 +213,9 @@ impl ChunksRenderer {
         camera: &Camera,
         chunk: &Chunk<L, H>
     ) {
-       let renderer = self.renderers.get_mut(&chunk.pos())
-            .expect("ChunkRenderer not found");
-        renderer.update_uniforms(queue, camera, chunk);
+        if let Some(renderer) = self.renderers.get_mut(&chunk.pos()) {
+            renderer.update_uniforms(queue, camera, chunk);
+        }
     }
 
     pub fn render<'a>(
@@ -76,6 +226,24 @@ impl ChunksRenderer {
             renderer.render(render_pass);
         }
     }
+
+    /// Render the transparent faces of every chunk, the furthest chunks
+    /// first so the closer ones blend over them
+    pub fn render_transparent<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>
+    ) {
+        let mut renderers = self.renderers
+            .values()
+            .filter_map(|renderer| {
+                Some((renderer.transparent_distance()?, renderer))
+            })
+            .collect::<Vec<_>>();
+        renderers.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        for (_, renderer) in renderers {
+            renderer.render_transparent(render_pass);
+        }
+    }
 }
 
 pub struct MasterRenderer {
@@ -84,14 +252,7 @@ pub struct MasterRenderer {
     /// Color used to clear the screen
     clear_color: wgpu::Color,
 
-    /// The chunk data
-    /// TODO: In the future this should be outside the renderer
-    chunk: Chunk<16, 16>,
-    chunk2: Chunk<16, 16>,
-
-    /// Renderer that can render a chunk
-    // chunk_renderer: ChunkRenderer,
-    // chunk_renderer2: ChunkRenderer,
+    /// Renders the sections of the world
     chunks_renderer: ChunksRenderer,
 
     // Test figure just to mark the center of the world
@@ -100,11 +261,30 @@ pub struct MasterRenderer {
 }
 
 impl MasterRenderer {
+    /// Most sections sent to be remeshed on a single frame, each is copied
+    /// with its neighbors
+    const REMESH_BUDGET: usize = 64;
+
+    /// Most generation stages run on a single frame, the ones on the workers
+    /// aren't limited
+    const GENERATION_BUDGET: usize = 8;
+
     /// Create a `MasterRenderer` for a certain SurfaceTexture
     pub fn new(
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         format: wgpu::TextureFormat,
+        meshing_mode: MeshingMode,
+        atlas: &TextureAtlas,
     ) -> Result<Self> {
+        // Upload the block textures, shared by all the chunk renderers, with
+        // only the mip levels that keep them apart
+        let atlas = Texture::from_image_with_mips(
+            device, queue,
+            &image::DynamicImage::ImageRgba8(atlas.image().clone()),
+            atlas.mip_levels()
+        )?;
+
         Ok(Self {
             format,
             clear_color: wgpu::Color {
@@ -113,31 +293,9 @@ impl MasterRenderer {
                 b: 0.4,
                 a: 1.0
             },
-            chunk: {
-                let mut chunk = Chunk::new(ChunkPos::new(0, -1));
-                for x in 0..16 {
-                    for y in x..16 {
-                        for z in 0..16 {
-                            chunk.place_block(BlockPos::new(x, y, z), Block::Dirt);
-                        }
-                    }
-                }
-                chunk
-            },
-            chunk2: {
-                let mut chunk = Chunk::new(ChunkPos::new(1, 0));
-                for x in 0..16 {
-                    for y in x..16 {
-                        for z in 0..16 {
-                            chunk.place_block(BlockPos::new(x, y, z), Block::Dirt);
-                        }
-                    }
-                }
-                chunk
-            },
-            // chunk_renderer: ChunkRenderer::new(device, format)?,
-            // chunk_renderer2: ChunkRenderer::new(device, format)?,
-            chunks_renderer: ChunksRenderer::new(),
+            chunks_renderer: ChunksRenderer::new(
+                meshing_mode, Rc::new(atlas), workers::default_threads()
+            ),
             m1_pipeline: ModelPipeline::new(
                 device,
                 format,
@@ -170,23 +328,44 @@ impl MasterRenderer {
     pub fn prepare(
         &mut self,
         device: &wgpu::Device,
-        world: &mut World
+        queue: &wgpu::Queue,
+        world: &mut World,
+        camera: &Camera
     ) {
-        for chunk in world.scheduled_chunks() {
-            self.chunks_renderer.load_chunk(device, self.format, chunk.pos()).unwrap();
-            self.chunks_renderer.update_chunk(device, chunk);
+        let registry = world.registry().clone();
+
+        // The columns around the camera are requested and the far ones
+        // unloaded, the workers take the closest first
+        let position = camera.position();
+        let section = |coord: f32| (coord / SECTION_SIZE as f32).floor() as i32;
+        let center = SectionPos::new(section(position.x), section(position.y), section(position.z));
+        world.set_center(center.column());
+        self.chunks_renderer.set_center(center);
+
+        // Move the columns a few stages further, the meshed ones queue their
+        // sections. Only a few sections per frame go to the workers, the rest
+        // wait for the next ones
+        world.generate_columns(Self::GENERATION_BUDGET);
+        for chunk_pos in world.unloaded_columns() {
+            self.chunks_renderer.unload_column(chunk_pos);
         }
-        for chunk in world.to_update_chunks() {
-            self.chunks_renderer.update_chunk(device, chunk);
+        for section_pos in world.dirty_sections(Self::REMESH_BUDGET) {
+            if let Some(neighborhood) = world.neighborhood(section_pos) {
+                self.chunks_renderer.schedule_chunk(&neighborhood, &registry);
+            }
+        }
+        let failed = self.chunks_renderer
+            .receive_meshes(device, queue, self.format)
+            .unwrap();
+        for section_pos in failed {
+            world.mark_dirty(section_pos);
         }
-        // self.chunk_renderer.update_model(device, &self.chunk);
-        // self.chunk_renderer2.update_model(device, &self.chunk2);
     }
 
     /// Main rendering, creates the render pass and manages the order of 
     /// pipelines/models and subrenderers
-    pub fn render<'a>(
-        &'a mut self,
+    pub fn render(
+        &mut self,
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
         depth_view: &wgpu::TextureView
@@ -216,11 +395,17 @@ impl MasterRenderer {
             ),
         });
 
-        // Draw
+        // Draw, the transparent faces go last as they don't write depth
         self.chunks_renderer.render(&mut render_pass);
         // self.chunk_renderer2.render(&mut render_pass);
         self.m1_pipeline.set_current(&mut render_pass);
         self.m1.render(&mut render_pass);
+        self.chunks_renderer.render_transparent(&mut render_pass);
+    }
+
+    /// Renders the sections of the world
+    pub fn chunks_renderer(&self) -> &ChunksRenderer {
+        &self.chunks_renderer
     }
 
     pub fn clear_color(&self) -> wgpu::Color {
@@ -43,7 +43,7 @@ impl ModelRenderer {
     ) {
         let model = self.model.as_ref().unwrap();
         for instance in &self.instances {
-            self.model_uniform.update(queue, instance.clone());
+            self.model_uniform.update(queue, *instance);
             model.render(render_pass);
         }
     }
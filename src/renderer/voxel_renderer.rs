@@ -1,40 +1,155 @@
+use std::rc::Rc;
+
 use anyhow::*;
-use cgmath::Vector3;
+use cgmath::{MetricSpace, Point3, Vector3};
 
+use crate::block::BlockRegistry;
 use crate::camera::Camera;
-use crate::chunk::{VoxelMesh, Chunk};
+use crate::chunk::{VoxelMesh, Chunk, ChunkNeighborhood, MeshingMode};
+use crate::mesh::Mesh;
 use crate::pipeline::VoxelPipeline;
 use crate::model::Model;
+use crate::texture::Texture;
+
+/// The see through faces of a chunk, with their own pipeline as they are
+/// blended and sorted
+struct TransparentPart {
+    model: Model,
+    pipeline: VoxelPipeline,
+
+    /// Block the camera was on when the faces were last sorted
+    sorted_from: Option<[i32; 3]>,
+
+    /// Squared distance from the camera to the center of the chunk, to draw
+    /// the chunks back to front
+    eye_distance: f32,
+}
+
+/// The meshes of a chunk made on a worker, only the buffers are left to make
+pub struct ChunkMesh {
+    /// Has the faces of the meshes, what the shader reads them with
+    voxel_mesh: VoxelMesh,
+    opaque: Option<Mesh>,
+    transparent: Option<Mesh>,
+
+    /// Generations of the neighborhood the meshes were made from
+    generations: [Option<u64>; 27],
+}
+
+impl ChunkMesh {
+    /// Mesh the center of a neighborhood
+    pub fn new<const L: usize, const H: usize>(
+        neighborhood: &ChunkNeighborhood<L, H>,
+        registry: &BlockRegistry,
+        meshing_mode: MeshingMode
+    ) -> Self {
+        let mut voxel_mesh = VoxelMesh::with_mode(meshing_mode);
+        voxel_mesh.serialize_chunk(neighborhood, registry);
+        let (opaque, transparent) = if voxel_mesh.is_empty() {
+            (None, None)
+        } else {
+            let some = |mesh: Mesh| Some(mesh).filter(|mesh| mesh.indices_count() > 0);
+            (some(voxel_mesh.mesh()), some(voxel_mesh.transparent_mesh()))
+        };
+
+        Self {
+            voxel_mesh,
+            opaque,
+            transparent,
+            generations: neighborhood.generations(),
+        }
+    }
+
+    /// Whether there is nothing to draw
+    pub fn is_empty(&self) -> bool {
+        self.opaque.is_none() && self.transparent.is_none()
+    }
+}
 
 pub struct ChunkRenderer {
     model: Option<Model>,
-    chunk_pipeline: VoxelPipeline,
+
+    /// Made with the first opaque mesh, and again when one doesn't fit
+    chunk_pipeline: Option<VoxelPipeline>,
+    transparent: Option<TransparentPart>,
     voxel_mesh: VoxelMesh,
+
+    /// Block textures, shared by all the chunks
+    atlas: Rc<Texture>,
+
+    /// Generations of the neighborhood the current mesh was made from
+    generations: Option<[Option<u64>; 27]>,
 }
 
 impl ChunkRenderer {
-    /// Create the model renderer, that renders a certain model
-    pub fn new(
-        device: &wgpu::Device,
-        format: wgpu::TextureFormat
-    ) -> Result<Self> {
-        Ok(Self {
+    /// Create the model renderer, that renders a certain model. Nothing is
+    /// drawn until `set_mesh`
+    pub fn new(meshing_mode: MeshingMode, atlas: Rc<Texture>) -> Self {
+        Self {
             model: None,
-            chunk_pipeline: VoxelPipeline::new(device, format)?,
-            voxel_mesh: VoxelMesh::new()
-        })
+            chunk_pipeline: None,
+            transparent: None,
+            voxel_mesh: VoxelMesh::with_mode(meshing_mode),
+            atlas,
+            generations: None,
+        }
     }
 
-    /// Change the model for a new one
-    pub fn update_model<const L: usize, const H: usize>(
+    /// Generations of the neighborhood the current mesh was made from
+    pub fn generations(&self) -> Option<[Option<u64>; 27]> {
+        self.generations
+    }
+
+    /// Draw a new mesh of the chunk, reusing the buffers of the model when
+    /// possible. The pipelines are made again when the faces don't fit on
+    /// their storage
+    pub fn set_mesh(
         &mut self,
         device: &wgpu::Device,
-        chunk: &Chunk<L, H>
-    ) {
-        // Generate the full voxel mesh and store the new model
-        self.voxel_mesh.serialize_chunk(&chunk);
-        let mesh = self.voxel_mesh.mesh();
-        self.model = Some(Model::new(device, mesh));
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        mesh: ChunkMesh
+    ) -> Result<()> {
+        let ChunkMesh { voxel_mesh, opaque, transparent, generations } = mesh;
+        self.voxel_mesh = voxel_mesh;
+        self.generations = Some(generations);
+        match (self.model.as_mut(), opaque) {
+            (_, None) => self.model = None,
+            (Some(model), Some(mesh)) => model.update(device, queue, mesh),
+            (None, Some(mesh)) => self.model = Some(Model::new(device, mesh)),
+        }
+        let faces = self.voxel_mesh.faces().len();
+        if self.model.is_some()
+            && !self.chunk_pipeline.as_ref().is_some_and(|pipeline| pipeline.fits(faces))
+        {
+            self.chunk_pipeline = Some(VoxelPipeline::new(device, format, &self.atlas, faces)?);
+        }
+
+        let Some(mesh) = transparent else {
+            self.transparent = None;
+            return Ok(());
+        };
+        let faces = self.voxel_mesh.transparent_faces().len();
+        match self.transparent.as_mut() {
+            Some(transparent) => {
+                transparent.model.update(device, queue, mesh);
+                transparent.sorted_from = None;
+                if !transparent.pipeline.fits(faces) {
+                    transparent.pipeline =
+                        VoxelPipeline::transparent(device, format, &self.atlas, faces)?;
+                }
+            }
+            None => {
+                self.transparent = Some(TransparentPart {
+                    model: Model::new(device, mesh),
+                    pipeline: VoxelPipeline::transparent(device, format, &self.atlas, faces)?,
+                    sorted_from: None,
+                    eye_distance: 0.0,
+                });
+            }
+        }
+
+        Ok(())
     }
 
     pub fn update_uniforms<
@@ -46,21 +161,61 @@ impl ChunkRenderer {
         camera: &Camera,
         chunk: &Chunk<L, H>
     ) {
-        self.chunk_pipeline.update(
+        if let Some(pipeline) = self.chunk_pipeline.as_mut() {
+            pipeline.update(
+                queue, camera,
+                chunk.translation(),
+                self.voxel_mesh.faces()
+            );
+        }
+
+        let Some(transparent) = self.transparent.as_mut() else {
+            return;
+        };
+        transparent.pipeline.update(
             queue, camera,
             chunk.translation(),
-            self.voxel_mesh.faces()
+            self.voxel_mesh.transparent_faces()
         );
+
+        // Sort again only when the camera moves to another block, the order
+        // barely changes inside of it
+        let eye = camera.position() - chunk.translation();
+        let center = Point3::new(L as f32, H as f32, L as f32) / 2.0
+            - Vector3::new(0.5, 0.5, 0.5);
+        transparent.eye_distance = eye.distance2(center);
+        let block = [eye.x, eye.y, eye.z].map(|coord| coord.round() as i32);
+        if transparent.sorted_from != Some(block) {
+            transparent.model.sort_quads(queue, [eye.x, eye.y, eye.z]);
+            transparent.sorted_from = Some(block);
+        }
     }
 
-    /// Render all the instanced models on a single pass
+    /// How far the camera is from the transparent faces of the chunk, `None`
+    /// if there are none
+    pub fn transparent_distance(&self) -> Option<f32> {
+        self.transparent.as_ref().map(|transparent| transparent.eye_distance)
+    }
+
+    /// Render the opaque faces of the chunk
     pub fn render<'a>(
         &'a self,
         render_pass: &mut wgpu::RenderPass<'a>
     ) {
-        if let Some(model) = self.model.as_ref() {
-            self.chunk_pipeline.set_current(render_pass);
+        if let (Some(model), Some(pipeline)) = (self.model.as_ref(), self.chunk_pipeline.as_ref()) {
+            pipeline.set_current(render_pass);
             model.render(render_pass);
         }
     }
+
+    /// Render the transparent faces of the chunk, after all the opaque ones
+    pub fn render_transparent<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>
+    ) {
+        if let Some(transparent) = self.transparent.as_ref() {
+            transparent.pipeline.set_current(render_pass);
+            transparent.model.render(render_pass);
+        }
+    }
 }
\ No newline at end of file
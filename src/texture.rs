@@ -1,9 +1,9 @@
 #![allow(dead_code)]
 
-use std::rc::Rc;
 use std::num::NonZeroU32;
 
 use anyhow::*;
+use image::{Rgba, RgbaImage};
 
 /*
 #[derive(Clone)]
@@ -85,13 +85,33 @@ impl Texture {
         }
     }
 
+    /// Create a texture with its whole mip chain, each level half the size
+    /// of the previous one down to a single texel
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage
+    ) -> Result<Self> {
+        let (width, height) = (img.width(), img.height());
+        let mip_level_count = 32 - width.max(height).leading_zeros();
+        Self::from_image_with_mips(device, queue, img, mip_level_count)
+    }
+
+    /// Create a texture with only the first `mip_level_count` levels of its
+    /// mip chain, for images made of tiles that shouldn't be mixed together
+    pub fn from_image_with_mips(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        mip_level_count: u32
     ) -> Result<Self> {
         let img = img.to_rgba8();
         let (width, height) = img.dimensions();
+        let max_levels = 32 - width.max(height).leading_zeros();
+        ensure!(
+            (1..=max_levels).contains(&mip_level_count),
+            "A {}x{} texture can't have {} mip levels", width, height, mip_level_count
+        );
         let size = wgpu::Extent3d {
             width,
             height,
@@ -102,7 +122,7 @@ impl Texture {
             &wgpu::TextureDescriptor {
                 label: Some("texture"),
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format: wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -111,30 +131,44 @@ impl Texture {
             }
         );
         
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                aspect: wgpu::TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            &img,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: NonZeroU32::new(4 * width),
-                rows_per_image: NonZeroU32::new(height),
-            },
-            size,
-        );
+        // Each level is filtered down from the previous one on the CPU
+        let mut level = img;
+        for mip_level in 0..mip_level_count {
+            if mip_level > 0 {
+                level = half_size(&level);
+            }
+            let (width, height) = level.dimensions();
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &level,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(4 * width),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1
+                },
+            );
+        }
 
+        // Blocky up close, smooth between the mip levels far away so the
+        // textures don't shimmer
         let view = texture.create_view(&Default::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         });
 
@@ -144,4 +178,46 @@ impl Texture {
             sampler
         })
     }
-}
\ No newline at end of file
+}
+
+/// The next mip level of an image, each texel is the average of the 2x2
+/// texels it covers. On odd sizes the last row or column is repeated
+fn half_size(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let mut sum = [0u32; 4];
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let texel = image.get_pixel(
+                (x * 2 + dx).min(width - 1),
+                (y * 2 + dy).min(height - 1)
+            );
+            for (sum, channel) in sum.iter_mut().zip(texel.0) {
+                *sum += channel as u32;
+            }
+        }
+        Rgba(sum.map(|sum| ((sum + 2) / 4) as u8))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn mip_levels() {
+        let mut image = RgbaImage::new(4, 2);
+        for (x, y, texel) in image.enumerate_pixels_mut() {
+            *texel = Rgba([if x < 2 { 200 } else { 100 }, (y * 100) as u8, 0, 255]);
+        }
+
+        let level = half_size(&image);
+        assert_eq!(level.dimensions(), (2, 1));
+        assert_eq!(level.get_pixel(0, 0), &Rgba([200, 50, 0, 255]));
+        assert_eq!(level.get_pixel(1, 0), &Rgba([100, 50, 0, 255]));
+
+        let level = half_size(&level);
+        assert_eq!(level.dimensions(), (1, 1));
+        assert_eq!(level.get_pixel(0, 0), &Rgba([150, 50, 0, 255]));
+    }
+}
@@ -9,16 +9,32 @@ var<uniform> model_transform: mat4x4<f32>;
 @group(0) @binding(2)
 var<storage, read> faces: array<u32>;
 
+@group(0) @binding(3)
+var atlas: texture_2d<f32>;
+
+@group(0) @binding(4)
+var atlas_sampler: sampler;
+
 struct VertexInput {
     @builtin(vertex_index) vertex_index: u32,
     @location(0) position: vec3<f32>,
     @location(1) color: vec3<f32>,
+    @location(2) ao: f32,
+    @location(3) light: vec2<f32>,
+    @location(4) alpha: f32,
+    @location(5) uv: vec2<f32>,
+    @location(6) tile: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
     @location(1) primitive_id: u32,
+    @location(2) ao: f32,
+    @location(3) light: vec2<f32>,
+    @location(4) alpha: f32,
+    @location(5) uv: vec2<f32>,
+    @location(6) tile: vec4<f32>,
 }
 
 @vertex
@@ -27,6 +43,11 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.color = model.color;
+    out.ao = model.ao;
+    out.light = model.light;
+    out.alpha = model.alpha;
+    out.uv = model.uv;
+    out.tile = model.tile;
     out.clip_position = camera * model_transform * vec4<f32>(model.position, 1.0);
     out.primitive_id = u32(floor(f32(model.vertex_index) / 4.0));
     return out;
@@ -39,20 +60,49 @@ fn fs_main(
     in: VertexOutput
 ) -> @location(0) vec4<f32> {
     let err = vec4(0.5, 0.5, 0.5, 1.0);
-    let red = vec4(1.0, 0.0, 0.0, 1.0);
+
+    // The texture repeats on every block of the face, the color of the block
+    // tints it. The fully transparent texels are cut out, like around the
+    // leaves of the plants. The mip level comes from the coordinates before
+    // wrapping them, or it would jump to the smallest one on the seams
+    let unwrapped = in.uv * in.tile.zw;
+    let texel = textureSampleGrad(
+        atlas, atlas_sampler,
+        in.tile.xy + fract(in.uv) * in.tile.zw,
+        dpdx(unwrapped), dpdy(unwrapped)
+    );
+    if texel.a < 0.5 {
+        discard;
+    }
+
+    // Ambient occlusion goes from 0 (corner fully occluded) to 3, don't let
+    // the corners go fully black
+    let ao = 0.4 + 0.6 * (in.ao / 3.0);
+
+    // Sky light and block light go from 0 to 15, each level a bit darker than
+    // the one above, the block light is a bit warmer
+    let sky = pow(0.8, 15.0 - in.light.x);
+    let block = pow(0.8, 15.0 - in.light.y);
+    let light = max(vec3(sky), block * vec3(1.0, 0.9, 0.75));
+
+    // Only the color is shaded by the direction of the face, the alpha is
+    // kept for the transparent blocks
+    var shade: f32;
     switch faces[in.primitive_id] {
         case 0u, 1u: {
-            return red * 0.3;
+            shade = 0.3;
         }
         case 2u, 3u: {
-            return red * 0.5;
+            shade = 0.5;
         }
         case 4u, 5u: {
-            return red * 0.75;
+            shade = 0.75;
         }
         default: {
             return err;
         }
     }
+
+    return vec4(texel.rgb * in.color * ao * light * shade, in.alpha);
 }
 
@@ -0,0 +1,304 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+#[cfg(test)]
+use std::time::Duration;
+
+use anyhow::*;
+
+/// Threads each pool starts when not told otherwise, half the cores so the
+/// generation and the meshing can share them with the render thread
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |threads| threads.get() / 2).max(1)
+}
+
+/// Squared distance between two points, in whatever units they are
+fn distance2(a: [i32; 3], b: [i32; 3]) -> i64 {
+    a.iter().zip(b).map(|(a, b)| (*a as i64 - b as i64).pow(2)).sum()
+}
+
+/// Work waiting for a thread
+struct Job<K, T> {
+    key: K,
+
+    /// Where the work is, the closest jobs to the center go first
+    point: [i32; 3],
+    distance: i64,
+
+    /// Number of the job, the ones as far from the center go in the order
+    /// they were sent
+    id: u64,
+
+    run: Box<dyn FnOnce() -> T + Send>,
+}
+
+impl<K, T> PartialEq for Job<K, T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.distance, self.id) == (other.distance, other.id)
+    }
+}
+
+impl<K, T> Eq for Job<K, T> {}
+
+impl<K, T> PartialOrd for Job<K, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K, T> Ord for Job<K, T> {
+    /// The heap pops the greatest, the closest and oldest job
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.cmp(&self.distance).then(other.id.cmp(&self.id))
+    }
+}
+
+struct Jobs<K, T> {
+    queue: BinaryHeap<Job<K, T>>,
+    center: [i32; 3],
+    next_id: u64,
+
+    /// Set when the pool is dropped, the threads stop once they see it
+    closed: bool,
+}
+
+/// The jobs and the threads waiting for them
+struct Shared<K, T> {
+    jobs: Mutex<Jobs<K, T>>,
+    available: Condvar,
+}
+
+/// What a job sent back, an error if it panicked
+type JobResult<K, T> = (K, u64, Result<T>);
+
+/// A few threads running jobs in order of distance to a center, like the
+/// camera. The results come back in the order they're done together with the
+/// key and id of their job, so the ones that went stale while running can be
+/// told apart and dropped. A job that panics comes back as an error and
+/// leaves its thread running
+pub struct WorkerPool<K, T> {
+    shared: Arc<Shared<K, T>>,
+    results: Receiver<JobResult<K, T>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl<K, T> WorkerPool<K, T>
+where
+    K: PartialEq + Send + 'static,
+    T: Send + 'static,
+{
+    /// Start `threads` threads named after the pool
+    pub fn new(name: &str, threads: usize) -> Self {
+        let shared = Arc::new(Shared {
+            jobs: Mutex::new(Jobs {
+                queue: BinaryHeap::new(),
+                center: [0; 3],
+                next_id: 0,
+                closed: false,
+            }),
+            available: Condvar::new(),
+        });
+        let (sender, results) = mpsc::channel();
+        let threads = (0..threads.max(1))
+            .map(|index| {
+                let shared = shared.clone();
+                let sender = sender.clone();
+                std::thread::Builder::new()
+                    .name(format!("{} worker {}", name, index))
+                    .spawn(move || work(&shared, &sender))
+                    .expect("Failed to start a worker thread")
+            })
+            .collect();
+
+        Self {
+            shared,
+            results,
+            threads,
+        }
+    }
+
+    /// Queue the work of `key` at `point`, returns the id its result will
+    /// come back with
+    pub fn spawn(
+        &self,
+        key: K,
+        point: [i32; 3],
+        run: impl FnOnce() -> T + Send + 'static
+    ) -> u64 {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        let distance = distance2(point, jobs.center);
+        jobs.queue.push(Job {
+            key,
+            point,
+            distance,
+            id,
+            run: Box::new(run),
+        });
+        self.shared.available.notify_one();
+
+        id
+    }
+
+    /// Move the point the jobs are sorted around, the queued ones are sorted
+    /// again
+    pub fn set_center(&self, center: [i32; 3]) {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        if jobs.center == center {
+            return;
+        }
+        jobs.center = center;
+        let mut queue = std::mem::take(&mut jobs.queue).into_vec();
+        for job in queue.iter_mut() {
+            job.distance = distance2(job.point, center);
+        }
+        jobs.queue = BinaryHeap::from(queue);
+    }
+
+    /// Drop the queued jobs of `key`, the running ones still send their
+    /// results. Returns how many were dropped
+    pub fn cancel(&self, key: &K) -> usize {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        let before = jobs.queue.len();
+        jobs.queue.retain(|job| job.key != *key);
+        before - jobs.queue.len()
+    }
+
+    /// Jobs waiting for a thread
+    #[cfg(test)]
+    pub fn queued(&self) -> usize {
+        self.shared.jobs.lock().unwrap().queue.len()
+    }
+
+    /// The results done since the last call, without waiting
+    pub fn finished(&self) -> Vec<JobResult<K, T>> {
+        self.results.try_iter().collect()
+    }
+
+    /// Wait up to `timeout` for the next result
+    #[cfg(test)]
+    pub fn wait(&self, timeout: Duration) -> Option<JobResult<K, T>> {
+        self.results.recv_timeout(timeout).ok()
+    }
+}
+
+impl<K, T> Drop for WorkerPool<K, T> {
+    /// The queued jobs are dropped, the threads finish the ones they are
+    /// running
+    fn drop(&mut self) {
+        {
+            let mut jobs = self.shared.jobs.lock().unwrap();
+            jobs.closed = true;
+            jobs.queue.clear();
+        }
+        self.shared.available.notify_all();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// What each thread runs, the closest job at a time until the pool is closed
+fn work<K, T>(shared: &Shared<K, T>, results: &Sender<JobResult<K, T>>) {
+    loop {
+        let job = {
+            let mut jobs = shared.jobs.lock().unwrap();
+            loop {
+                if jobs.closed {
+                    return;
+                }
+                if let Some(job) = jobs.queue.pop() {
+                    break job;
+                }
+                jobs = shared.available.wait(jobs).unwrap();
+            }
+        };
+        // The job owns all it touches, nothing is left half done behind
+        let result = panic::catch_unwind(AssertUnwindSafe(job.run)).map_err(|payload| {
+            let message = payload.downcast_ref::<&str>().copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown reason");
+            anyhow!("Job panicked: {}", message)
+        });
+        if results.send((job.key, job.id, result)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn closest_jobs_go_first() {
+        let pool = WorkerPool::new("test", 1);
+
+        // Keep the only thread busy until every job is queued
+        let (release, blocked) = mpsc::channel::<()>();
+        let first = pool.spawn('a', [0, 0, 0], move || blocked.recv().unwrap());
+        while pool.queued() > 0 {
+            std::thread::yield_now();
+        }
+
+        pool.spawn('b', [5, 0, 0], || ());
+        pool.spawn('c', [0, 1, 0], || ());
+        pool.spawn('d', [-5, 0, 0], || ());
+        let dropped = pool.spawn('e', [1, 1, 1], || ());
+        pool.spawn('f', [0, 0, 2], || ());
+        assert_eq!(pool.queued(), 5);
+        assert_eq!(pool.cancel(&'e'), 1);
+
+        // The camera moved next to the last ones
+        pool.set_center([-4, 0, 0]);
+        release.send(()).unwrap();
+
+        let mut done = Vec::new();
+        while done.len() < 5 {
+            let (key, id, _) = pool.wait(TIMEOUT).unwrap();
+            assert_ne!(id, dropped);
+            done.push((key, id == first));
+        }
+        assert_eq!(done, [('a', true), ('d', false), ('c', false), ('f', false), ('b', false)]);
+        assert!(pool.finished().is_empty());
+    }
+
+    #[test]
+    fn results_come_back_from_every_thread() {
+        let pool = WorkerPool::new("test", 4);
+        for n in 0..64u64 {
+            pool.spawn(n, [n as i32, 0, 0], move || n * n);
+        }
+        let mut results: Vec<_> = (0..64)
+            .map(|_| {
+                let (key, id, square) = pool.wait(TIMEOUT).unwrap();
+                (key, id, square.unwrap())
+            })
+            .collect();
+        results.sort();
+        for (n, (key, id, square)) in results.into_iter().enumerate() {
+            assert_eq!((key, id, square), (n as u64, n as u64, (n * n) as u64));
+        }
+    }
+
+    #[test]
+    fn panics_come_back_as_errors() {
+        let pool = WorkerPool::new("test", 1);
+        let failed = pool.spawn(0, [0, 0, 0], || -> u32 { panic!("broken job") });
+        let next = pool.spawn(1, [0, 0, 0], || 7);
+
+        let (key, id, result) = pool.wait(TIMEOUT).unwrap();
+        assert_eq!((key, id), (0, failed));
+        assert!(result.unwrap_err().to_string().contains("broken job"));
+        // The thread is still there for the next jobs
+        let (key, id, result) = pool.wait(TIMEOUT).unwrap();
+        assert_eq!((key, id, result.unwrap()), (1, next, 7));
+    }
+}
@@ -0,0 +1,374 @@
+use std::f64::consts::{PI, TAU};
+
+use crate::block::Block;
+use crate::chunk::{BlockPos, ChunkColumn, ChunkPos, COLUMN_HEIGHT, SECTION_SIZE};
+use crate::world::noise::{hash, Fractal, Random};
+
+/// Shape of the caves carved out of the terrain
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaveConfig {
+    /// Lowest `y` carved, the blocks under it are never opened
+    pub min_height: usize,
+
+    /// Blocks under the surface the big caverns stay, so they don't open the
+    /// ground from above. The tunnels and the ravines do reach the surface
+    pub cheese_depth: usize,
+
+    /// Big caverns, carved where the noise goes over the threshold
+    pub cheese_noise: Fractal,
+    pub cheese_threshold: f64,
+
+    /// Long tunnels, carved where two noises are both close to zero
+    pub spaghetti_noise: Fractal,
+
+    /// How close to zero, wider tunnels the higher, 0 turns them off
+    pub spaghetti_width: f64,
+
+    /// Chance of each column starting a ravine, 0 turns them off
+    pub ravine_chance: f64,
+}
+
+impl Default for CaveConfig {
+    fn default() -> Self {
+        Self {
+            min_height: 1,
+            cheese_depth: 12,
+            cheese_noise: Fractal {
+                octaves: 2,
+                frequency: 1.0 / 64.0,
+                persistence: 0.5,
+            },
+            cheese_threshold: 0.4,
+            spaghetti_noise: Fractal {
+                octaves: 2,
+                frequency: 1.0 / 48.0,
+                persistence: 0.5,
+            },
+            spaghetti_width: 0.05,
+            ravine_chance: 0.02,
+        }
+    }
+}
+
+/// A ball of a ravine, stretched up
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RavineStep {
+    center: [f64; 3],
+    radius: f64,
+    height: f64,
+}
+
+/// The noises of the caves on the corners of cells of a column, the blocks
+/// in between are interpolated, way cheaper than sampling every block
+struct CaveNoise {
+    /// The cheese and the two spaghetti noises of each corner, ordered by
+    /// `y`, then `z` and then `x`
+    values: Vec<[f64; 3]>,
+}
+
+impl CaveNoise {
+    /// Size of the cells, in blocks
+    const STEP_XZ: usize = 4;
+    const STEP_Y: usize = 8;
+
+    const SIDE: usize = SECTION_SIZE / Self::STEP_XZ + 1;
+    const POINTS: usize = Self::SIDE * Self::SIDE * (COLUMN_HEIGHT / Self::STEP_Y + 1);
+
+    fn corner(&self, x: usize, y: usize, z: usize) -> [f64; 3] {
+        self.values[(y * Self::SIDE + z) * Self::SIDE + x]
+    }
+
+    /// Trilinear interpolation of the corners of the cell of a block
+    fn at(&self, block_pos: BlockPos) -> [f64; 3] {
+        let (x, fx) = (block_pos.x / Self::STEP_XZ, (block_pos.x % Self::STEP_XZ) as f64 / Self::STEP_XZ as f64);
+        let (y, fy) = (block_pos.y / Self::STEP_Y, (block_pos.y % Self::STEP_Y) as f64 / Self::STEP_Y as f64);
+        let (z, fz) = (block_pos.z / Self::STEP_XZ, (block_pos.z % Self::STEP_XZ) as f64 / Self::STEP_XZ as f64);
+        std::array::from_fn(|i| {
+            let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+            let value = |dx, dy, dz| self.corner(x + dx, y + dy, z + dz)[i];
+            lerp(
+                fy,
+                lerp(fz, lerp(fx, value(0, 0, 0), value(1, 0, 0)), lerp(fx, value(0, 0, 1), value(1, 0, 1))),
+                lerp(fz, lerp(fx, value(0, 1, 0), value(1, 1, 0)), lerp(fx, value(0, 1, 1), value(1, 1, 1))),
+            )
+        })
+    }
+}
+
+/// Digs the caves out of the columns after the terrain is made. Everything
+/// it carves only depends on the seed and the world position, so a cave
+/// crossing a border lines up no matter the order the columns are made in
+pub struct CaveCarver {
+    seed: u64,
+    config: CaveConfig,
+
+    /// Never carved, nor the blocks right under it, so the seas don't end up
+    /// floating over the caves
+    water: Block,
+}
+
+impl CaveCarver {
+    /// Most steps of a ravine, also how far from its column it can go
+    const RAVINE_LENGTH: usize = 112;
+
+    pub fn new(seed: u64, config: CaveConfig, water: Block) -> Self {
+        Self {
+            seed,
+            config,
+            water,
+        }
+    }
+
+    /// The cave noises of a column, sampled on a grid aligned to the world
+    /// so the neighbors interpolate the same values on their borders
+    fn sample_noise(&self, chunk_pos: ChunkPos) -> CaveNoise {
+        let config = &self.config;
+        let mut values = Vec::with_capacity(CaveNoise::POINTS);
+        for grid_y in 0..=COLUMN_HEIGHT / CaveNoise::STEP_Y {
+            for grid_z in 0..=SECTION_SIZE / CaveNoise::STEP_XZ {
+                for grid_x in 0..=SECTION_SIZE / CaveNoise::STEP_XZ {
+                    let x = (chunk_pos.x as i64 * SECTION_SIZE as i64
+                        + (grid_x * CaveNoise::STEP_XZ) as i64) as f64;
+                    let y = (grid_y * CaveNoise::STEP_Y) as f64;
+                    let z = (chunk_pos.z as i64 * SECTION_SIZE as i64
+                        + (grid_z * CaveNoise::STEP_XZ) as i64) as f64;
+
+                    // Flattened, the caverns are wider than they are tall
+                    let cheese = config.cheese_noise.sample3(self.seed ^ 0x68e31da4, x, y * 1.5, z);
+                    let tunnels = if config.spaghetti_width > 0.0 {
+                        [
+                            config.spaghetti_noise.sample3(self.seed ^ 0xb5297a4d, x, y, z),
+                            config.spaghetti_noise.sample3(self.seed ^ 0x1b56c4e9, x, y, z),
+                        ]
+                    } else {
+                        [1.0; 2]
+                    };
+                    values.push([cheese, tunnels[0], tunnels[1]]);
+                }
+            }
+        }
+
+        CaveNoise {
+            values,
+        }
+    }
+
+    /// Whether the noise caves open a block of the column, `depth` is how
+    /// far under the surface it is
+    fn is_noise_cave(&self, noise: &CaveNoise, block_pos: BlockPos, depth: usize) -> bool {
+        let [cheese, first, second] = noise.at(block_pos);
+        let width = self.config.spaghetti_width;
+        (depth >= self.config.cheese_depth && cheese > self.config.cheese_threshold)
+            || first * first + second * second < width * width
+    }
+
+    /// The ravine that starts on a column, if any, as the balls it's carved
+    /// with. Worms that go a step at a time, turning a bit on each one
+    fn ravine(&self, chunk_pos: ChunkPos) -> Option<Vec<RavineStep>> {
+        let mut random = Random::new(hash(
+            self.seed ^ 0x94d049bb,
+            [chunk_pos.x as i64, 0, chunk_pos.z as i64]
+        ));
+        if random.next_f64() >= self.config.ravine_chance {
+            return None;
+        }
+
+        let size = SECTION_SIZE as f64;
+        let mut position = [
+            chunk_pos.x as f64 * size + random.range(0.0, size),
+            random.range(20.0, 56.0),
+            chunk_pos.z as f64 * size + random.range(0.0, size),
+        ];
+        let mut yaw = random.range(0.0, TAU);
+        let mut pitch = random.range(-0.1, 0.1);
+        let width = random.range(1.5, 3.5);
+        let length = random.range(Self::RAVINE_LENGTH as f64 * 0.6, Self::RAVINE_LENGTH as f64) as usize;
+
+        let mut steps = Vec::with_capacity(length);
+        for step in 0..length {
+            // Thin on the ends and the widest on the middle
+            let radius = 1.0 + width * (PI * step as f64 / length as f64).sin();
+            steps.push(RavineStep {
+                center: position,
+                radius,
+                height: radius * 3.0,
+            });
+
+            position[0] += yaw.cos() * pitch.cos();
+            position[1] += pitch.sin();
+            position[2] += yaw.sin() * pitch.cos();
+            yaw += random.range(-0.1, 0.1);
+            pitch = (pitch + random.range(-0.05, 0.05)).clamp(-0.2, 0.2);
+        }
+
+        Some(steps)
+    }
+
+    /// Carve the caves of a column, made of only terrain
+    pub fn carve(&self, column: &mut ChunkColumn) {
+        let chunk_pos = column.pos();
+        let chunk_x = chunk_pos.x as i64 * SECTION_SIZE as i64;
+        let chunk_z = chunk_pos.z as i64 * SECTION_SIZE as i64;
+
+        // The ravines of the columns close enough to reach this one, only the
+        // balls that touch it
+        let reach = (Self::RAVINE_LENGTH / SECTION_SIZE + 1) as i32;
+        let touches = |step: &RavineStep| {
+            let [x, _, z] = step.center;
+            let size = SECTION_SIZE as f64;
+            x + step.radius >= chunk_x as f64 - 0.5 && x - step.radius <= (chunk_x as f64 + size - 0.5)
+                && z + step.radius >= chunk_z as f64 - 0.5 && z - step.radius <= (chunk_z as f64 + size - 0.5)
+        };
+        let mut ravines = Vec::new();
+        if self.config.ravine_chance > 0.0 {
+            for dx in -reach..=reach {
+                for dz in -reach..=reach {
+                    let origin = ChunkPos::new(chunk_pos.x + dx, chunk_pos.z + dz);
+                    if let Some(steps) = self.ravine(origin) {
+                        ravines.extend(steps.into_iter().filter(touches));
+                    }
+                }
+            }
+        }
+
+        let noise = self.sample_noise(chunk_pos);
+        for x in 0..SECTION_SIZE {
+            for z in 0..SECTION_SIZE {
+                let (world_x, world_z) = (chunk_x + x as i64, chunk_z + z as i64);
+                let column_steps: Vec<_> = ravines.iter()
+                    .filter(|step| {
+                        let (dx, dz) = (world_x as f64 - step.center[0], world_z as f64 - step.center[2]);
+                        dx * dx + dz * dz < step.radius * step.radius
+                    })
+                    .collect();
+
+                // From the top down, so the block above is still the one the
+                // terrain left there
+                let mut depth = None;
+                let mut above = Block::AIR;
+                for y in (self.config.min_height..COLUMN_HEIGHT).rev() {
+                    let block_pos = BlockPos::new(x, y, z);
+                    let block = column.get_block(block_pos).unwrap();
+                    let previous = std::mem::replace(&mut above, block);
+                    if block == Block::AIR || block == self.water {
+                        continue;
+                    }
+                    let ground_depth = depth.map_or(0, |depth| depth + 1);
+                    depth = Some(ground_depth);
+                    if previous == self.water {
+                        continue;
+                    }
+
+                    let in_ravine = column_steps.iter().any(|step| {
+                        let (dx, dz) = (world_x as f64 - step.center[0], world_z as f64 - step.center[2]);
+                        let dy = (y as f64 - step.center[1]) / step.height;
+                        (dx * dx + dz * dz) / (step.radius * step.radius) + dy * dy < 1.0
+                    });
+                    if in_ravine || self.is_noise_cave(&noise, block_pos, ground_depth) {
+                        column.place_block(block_pos, Block::AIR);
+                    }
+                }
+            }
+        }
+        column.compact();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    /// A column of stone up to `y = 100`
+    fn stone_column(chunk_pos: ChunkPos) -> ChunkColumn {
+        let mut column = ChunkColumn::new(chunk_pos);
+        for y in 0..100 {
+            for x in 0..SECTION_SIZE {
+                for z in 0..SECTION_SIZE {
+                    column.place_block(BlockPos::new(x, y, z), Block::from_id(1));
+                }
+            }
+        }
+        column
+    }
+
+    fn blocks(column: &ChunkColumn) -> Vec<Block> {
+        column.sections()
+            .flat_map(|section| section.iter().map(|block| *block))
+            .collect()
+    }
+
+    fn is_air(column: &ChunkColumn, x: usize, y: usize, z: usize) -> bool {
+        column.get_block(BlockPos::new(x, y, z)) == Some(Block::AIR)
+    }
+
+    /// Carve the 2x2 columns from the origin in an order, the ravines of
+    /// each one start on the columns around
+    fn carve_in_order(carver: &CaveCarver, order: [(i32, i32); 4]) -> Vec<Vec<Block>> {
+        let mut columns: Vec<_> = order.iter()
+            .map(|(x, z)| {
+                let mut column = stone_column(ChunkPos::new(*x, *z));
+                carver.carve(&mut column);
+                column
+            })
+            .collect();
+        columns.sort_by_key(|column| (column.pos().x, column.pos().z));
+        columns.iter().map(blocks).collect()
+    }
+
+    #[test]
+    fn carving_doesnt_depend_on_the_order() {
+        let config = CaveConfig {
+            ravine_chance: 0.2,
+            ..CaveConfig::default()
+        };
+        let water = Block::from_id(2);
+        let carver = CaveCarver::new(5, config, water);
+        let columns = carve_in_order(&carver, [(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!(columns, carve_in_order(&carver, [(1, 1), (0, 1), (1, 0), (0, 0)]));
+        assert!(columns[0].contains(&Block::AIR));
+
+        // The floor is left alone
+        let mut column = stone_column(ChunkPos::new(0, 0));
+        carver.carve(&mut column);
+        assert!((0..SECTION_SIZE).all(|x| (0..SECTION_SIZE).all(|z| !is_air(&column, x, 0, z))));
+
+        // And so is what is under the water
+        let mut column = stone_column(ChunkPos::new(0, 0));
+        for x in 0..SECTION_SIZE {
+            for z in 0..SECTION_SIZE {
+                column.place_block(BlockPos::new(x, 99, z), water);
+            }
+        }
+        carver.carve(&mut column);
+        assert!((0..SECTION_SIZE).all(|x| (0..SECTION_SIZE).all(|z| !is_air(&column, x, 98, z))));
+    }
+
+    #[test]
+    fn ravines_cross_borders() {
+        // Only ravines, one from every column
+        let config = CaveConfig {
+            cheese_threshold: 2.0,
+            spaghetti_width: 0.0,
+            ravine_chance: 1.0,
+            ..CaveConfig::default()
+        };
+        let carver = CaveCarver::new(9, config, Block::from_id(2));
+        let mut left = stone_column(ChunkPos::new(-1, 3));
+        let mut right = stone_column(ChunkPos::new(0, 3));
+        carver.carve(&mut right);
+        carver.carve(&mut left);
+
+        // Some ravine goes from one to the other
+        let crossings = (0..100)
+            .flat_map(|y| (0..SECTION_SIZE).map(move |z| (y, z)))
+            .filter(|(y, z)| is_air(&left, 15, *y, *z) && is_air(&right, 0, *y, *z))
+            .count();
+        assert!(crossings > 0);
+
+        // The steps of a ravine are the same asked from any column
+        let ravine = carver.ravine(ChunkPos::new(2, 2)).unwrap();
+        assert_eq!(carver.ravine(ChunkPos::new(2, 2)), Some(ravine.clone()));
+        assert!(ravine.len() >= CaveCarver::RAVINE_LENGTH * 3 / 5);
+    }
+}
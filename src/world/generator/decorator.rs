@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+
+use anyhow::*;
+
+use crate::biome::{Biome, BiomeFeatures};
+use crate::block::{Block, BlockRegistry};
+use crate::chunk::{BlockPos, ChunkColumn, ChunkPos, WorldBlockPos, COLUMN_HEIGHT, SECTION_SIZE};
+use crate::world::noise::{hash, Random};
+
+use super::structure::StructureTemplate;
+
+/// A block written by a feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockEdit {
+    pub block_pos: BlockPos,
+    pub block: Block,
+
+    /// Whether it replaces whatever is there, otherwise it only goes on air
+    pub overwrite: bool,
+}
+
+impl BlockEdit {
+    pub fn replaces(&self, old: Block) -> bool {
+        old != self.block && (self.overwrite || old == Block::AIR)
+    }
+
+    /// Write the block to its column if it can replace the one there,
+    /// returns whether it did
+    pub fn apply(&self, column: &mut ChunkColumn) -> bool {
+        match column.get_block(self.block_pos) {
+            Some(old) if self.replaces(old) => {
+                column.place_block(self.block_pos, self.block).is_some()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Blocks written by the features, by the column they fall on. A feature can
+/// go over the borders of its column, the blocks of the columns that aren't
+/// there yet wait here until they are
+#[derive(Debug, Default, PartialEq)]
+pub struct BlockEdits {
+    edits: HashMap<ChunkPos, Vec<BlockEdit>>,
+}
+
+impl BlockEdits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a block anywhere, out of the height of the world it's dropped
+    pub fn set(&mut self, pos: WorldBlockPos, block: Block, overwrite: bool) {
+        if let Some((chunk_pos, block_pos)) = pos.local() {
+            self.edits.entry(chunk_pos).or_default().push(BlockEdit {
+                block_pos,
+                block,
+                overwrite,
+            });
+        }
+    }
+
+    /// Add edits to a column, after the ones it already has
+    pub fn extend(&mut self, chunk_pos: ChunkPos, edits: impl IntoIterator<Item = BlockEdit>) {
+        self.edits.entry(chunk_pos).or_default().extend(edits);
+    }
+
+    /// Remove the edits of a column, in the order they were made
+    pub fn take(&mut self, chunk_pos: ChunkPos) -> Vec<BlockEdit> {
+        self.edits.remove(&chunk_pos).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Columns with edits waiting for them
+    pub fn columns(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.edits.keys().copied()
+    }
+
+    /// Every column with the edits waiting for it
+    pub fn iter(&self) -> impl Iterator<Item = (ChunkPos, &[BlockEdit])> + '_ {
+        self.edits.iter().map(|(chunk_pos, edits)| (*chunk_pos, edits.as_slice()))
+    }
+}
+
+impl IntoIterator for BlockEdits {
+    type Item = (ChunkPos, Vec<BlockEdit>);
+    type IntoIter = std::collections::hash_map::IntoIter<ChunkPos, Vec<BlockEdit>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.edits.into_iter()
+    }
+}
+
+/// Shape of the features placed on the terrain, how many depends on the
+/// biomes
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecorationConfig {
+    /// Shortest and tallest trunk of the trees
+    pub tree_height: [usize; 2],
+
+    /// Smallest and biggest radius of the boulders
+    pub boulder_radius: [f64; 2],
+
+    /// Names of the blocks of the features
+    pub log_block: String,
+    pub leaves_block: String,
+    pub boulder_block: String,
+
+    /// Names of the blocks the trees grow on
+    pub soil_blocks: Vec<String>,
+}
+
+impl Default for DecorationConfig {
+    fn default() -> Self {
+        Self {
+            tree_height: [4, 6],
+            boulder_radius: [1.0, 2.5],
+            log_block: "log".to_owned(),
+            leaves_block: "leaves".to_owned(),
+            boulder_block: "stone".to_owned(),
+            soil_blocks: vec!["grass".to_owned(), "dirt".to_owned()],
+        }
+    }
+}
+
+/// Places trees, boulders and structures on the surface of the columns, each
+/// column only depends on the seed, its position and its own blocks
+pub struct Decorator {
+    seed: u64,
+    config: DecorationConfig,
+    log: Block,
+    leaves: Block,
+    boulder: Block,
+    soil: Vec<Block>,
+
+    /// Nothing is placed on it
+    water: Block,
+
+    /// Templates the structures are picked from
+    structures: Vec<StructureTemplate>,
+}
+
+impl Decorator {
+    /// A decorator of the blocks of `registry`, fails if the config names
+    /// blocks that aren't in it
+    pub fn new(
+        seed: u64,
+        config: DecorationConfig,
+        registry: &BlockRegistry,
+        water: Block
+    ) -> Result<Self> {
+        let block = |name: &str| {
+            registry.by_name(name)
+                .ok_or_else(|| anyhow!("Unknown decoration block {:?}", name))
+        };
+
+        Ok(Self {
+            seed,
+            log: block(&config.log_block)?,
+            leaves: block(&config.leaves_block)?,
+            boulder: block(&config.boulder_block)?,
+            soil: config.soil_blocks.iter()
+                .map(|name| block(name))
+                .collect::<Result<_>>()?,
+            water,
+            structures: Vec::new(),
+            config,
+        })
+    }
+
+    pub fn set_structures(&mut self, structures: Vec<StructureTemplate>) {
+        self.structures = structures;
+    }
+
+    /// Height and block of the top of the ground at `x`, `z`, none if it's
+    /// under water or there's no ground
+    fn surface(&self, column: &ChunkColumn, x: usize, z: usize) -> Option<(usize, Block)> {
+        let (y, block) = (0..COLUMN_HEIGHT)
+            .rev()
+            .map(|y| (y, column.get_block(BlockPos::new(x, y, z)).unwrap()))
+            .find(|(_, block)| *block != Block::AIR)?;
+
+        (block != self.water).then_some((y, block))
+    }
+
+    /// Where a kind of feature goes on a column, `amount` is how many a
+    /// column of each biome gets on average. The same number of tries is
+    /// made on every column, the biome of each spot decides if it's kept
+    fn spots(
+        random: &mut Random,
+        column: &ChunkColumn,
+        amount: impl Fn(BiomeFeatures) -> f64
+    ) -> Vec<(usize, usize)> {
+        let most = Biome::ALL.iter()
+            .map(|biome| amount(biome.features()))
+            .fold(0.0, f64::max);
+        let tries = most.ceil() as usize;
+
+        let mut spots = Vec::new();
+        for _ in 0..tries {
+            let x = (random.next_u64() % SECTION_SIZE as u64) as usize;
+            let z = (random.next_u64() % SECTION_SIZE as u64) as usize;
+            let chance = amount(column.biome(x, z).features()) / tries as f64;
+            if random.next_f64() < chance {
+                spots.push((x, z));
+            }
+        }
+        spots
+    }
+
+    /// A trunk on top of `ground` with a round crown of leaves, only on air
+    fn tree(&self, random: &mut Random, ground: WorldBlockPos, edits: &mut BlockEdits) {
+        let [min, max] = self.config.tree_height;
+        let height = random.range(min as f64, max as f64 + 1.0) as i64;
+        for y in 1..=height {
+            edits.set(WorldBlockPos::new(ground.x, ground.y + y, ground.z), self.log, false);
+        }
+
+        // Two wide layers around the top of the trunk, the corners randomly
+        // cut, and two narrow ones over it
+        let top = ground.y + height;
+        for y in top - 1..=top + 2 {
+            let radius: i64 = if y < top + 1 { 2 } else { 1 };
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    let corner = dx.abs() == radius && dz.abs() == radius;
+                    let cut = if y == top + 2 {
+                        corner
+                    } else {
+                        corner && random.next_f64() < 0.5
+                    };
+                    if !cut {
+                        edits.set(
+                            WorldBlockPos::new(ground.x + dx, y, ground.z + dz),
+                            self.leaves,
+                            false
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// A squashed ball of rock half buried on `ground`, only on air
+    fn boulder(&self, random: &mut Random, ground: WorldBlockPos, edits: &mut BlockEdits) {
+        let [min, max] = self.config.boulder_radius;
+        let radius = random.range(min, max);
+        let reach = radius.ceil() as i64;
+        for dx in -reach..=reach {
+            for dy in -reach..=reach {
+                for dz in -reach..=reach {
+                    let distance = (dx * dx + dz * dz) as f64 + (dy as f64 * 1.4).powi(2);
+                    if distance <= radius * radius {
+                        edits.set(
+                            WorldBlockPos::new(ground.x + dx, ground.y + dy, ground.z + dz),
+                            self.boulder,
+                            false
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// One of the templates centered on `ground`, its bottom layer replacing
+    /// the surface
+    fn structure(&self, random: &mut Random, ground: WorldBlockPos, edits: &mut BlockEdits) {
+        if self.structures.is_empty() {
+            return;
+        }
+        let template = &self.structures[(random.next_u64() % self.structures.len() as u64) as usize];
+        let [size_x, _, size_z] = template.size();
+        let corner = WorldBlockPos::new(
+            ground.x - size_x as i64 / 2,
+            ground.y,
+            ground.z - size_z as i64 / 2
+        );
+        for (offset, block) in template.blocks() {
+            let pos = WorldBlockPos::new(
+                corner.x + offset.x as i64,
+                corner.y + offset.y as i64,
+                corner.z + offset.z as i64
+            );
+            edits.set(pos, *block, true);
+        }
+    }
+
+    /// Write the features of a column made of only terrain
+    pub fn decorate(&self, column: &ChunkColumn, edits: &mut BlockEdits) {
+        let chunk_pos = column.pos();
+        let mut random = Random::new(hash(
+            self.seed ^ 0x3c6ef372,
+            [chunk_pos.x as i64, 0, chunk_pos.z as i64]
+        ));
+        let ground = |x: usize, y: usize, z: usize| WorldBlockPos::new(
+            chunk_pos.x as i64 * SECTION_SIZE as i64 + x as i64,
+            y as i64,
+            chunk_pos.z as i64 * SECTION_SIZE as i64 + z as i64
+        );
+
+        // Every spot is picked before placing anything, so a feature doesn't
+        // change the ones after it
+        let trees = Self::spots(&mut random, column, |features| features.trees);
+        let boulders = Self::spots(&mut random, column, |features| features.boulders);
+        let structures = Self::spots(&mut random, column, |features| features.structures);
+
+        for (x, z) in trees {
+            match self.surface(column, x, z) {
+                Some((y, block)) if self.soil.contains(&block) => {
+                    self.tree(&mut random, ground(x, y, z), edits);
+                }
+                _ => {}
+            }
+        }
+        for (x, z) in boulders {
+            if let Some((y, _)) = self.surface(column, x, z) {
+                self.boulder(&mut random, ground(x, y, z), edits);
+            }
+        }
+        for (x, z) in structures {
+            if let Some((y, _)) = self.surface(column, x, z) {
+                self.structure(&mut random, ground(x, y, z), edits);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn edits_go_to_their_column() {
+        let stone = Block::from_id(2);
+        let mut edits = BlockEdits::new();
+        edits.set(WorldBlockPos::new(-1, 70, 16), stone, false);
+        edits.set(WorldBlockPos::new(3, 70, 4), stone, true);
+        edits.set(WorldBlockPos::new(3, COLUMN_HEIGHT as i64, 4), stone, true);
+        let mut columns: Vec<_> = edits.columns().collect();
+        columns.sort_by_key(|pos| (pos.x, pos.z));
+        assert_eq!(columns, vec![ChunkPos::new(-1, 1), ChunkPos::new(0, 0)]);
+
+        let taken = edits.take(ChunkPos::new(-1, 1));
+        assert_eq!(taken, vec![BlockEdit {
+            block_pos: BlockPos::new(15, 70, 0),
+            block: stone,
+            overwrite: false,
+        }]);
+        assert!(edits.take(ChunkPos::new(-1, 1)).is_empty());
+
+        // Only the ones that overwrite replace something else than air
+        let dirt = Block::from_id(1);
+        let mut column = ChunkColumn::new(ChunkPos::new(-1, 1));
+        column.place_block(BlockPos::new(15, 70, 0), dirt);
+        assert!(!taken[0].apply(&mut column));
+        assert!(BlockEdit { overwrite: true, ..taken[0] }.apply(&mut column));
+        assert_eq!(column.get_block(BlockPos::new(15, 70, 0)), Some(stone));
+        assert!(!BlockEdit { overwrite: true, ..taken[0] }.apply(&mut column));
+    }
+
+    #[test]
+    fn structures_cross_borders() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+        let water = registry.by_name("water").unwrap();
+        let template = StructureTemplate::parse(
+            "pillar",
+            "block S stone\nlayer\nSSS\nSSS\nSSS\nlayer\n\n S",
+            &registry
+        ).unwrap();
+        let mut decorator = Decorator::new(1, DecorationConfig::default(), &registry, water).unwrap();
+        decorator.set_structures(vec![template]);
+
+        // Centered on the corner of the column, the rest falls on the three
+        // columns around it
+        let mut edits = BlockEdits::new();
+        decorator.structure(&mut Random::new(1), WorldBlockPos::new(15, 70, 15), &mut edits);
+        let edit = |x, y, z| BlockEdit {
+            block_pos: BlockPos::new(x, y, z),
+            block: stone,
+            overwrite: true,
+        };
+        assert_eq!(edits.take(ChunkPos::new(1, 1)), vec![edit(0, 70, 0)]);
+        assert_eq!(edits.take(ChunkPos::new(1, 0)), vec![edit(0, 70, 14), edit(0, 70, 15)]);
+        assert_eq!(edits.take(ChunkPos::new(0, 1)), vec![edit(14, 70, 0), edit(15, 70, 0)]);
+        assert_eq!(
+            edits.take(ChunkPos::new(0, 0)),
+            vec![edit(14, 70, 14), edit(15, 70, 14), edit(14, 70, 15), edit(15, 70, 15), edit(15, 71, 15)]
+        );
+        assert!(edits.is_empty());
+    }
+}
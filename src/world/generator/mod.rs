@@ -0,0 +1,227 @@
+use anyhow::*;
+
+use crate::block::{Block, BlockRegistry};
+use crate::chunk::{BlockPos, ChunkColumn, ChunkPos, COLUMN_HEIGHT, SECTION_SIZE};
+
+mod carver;
+mod decorator;
+mod structure;
+mod terrain;
+
+pub use decorator::{BlockEdit, BlockEdits};
+pub use structure::StructureTemplate;
+pub use terrain::TerrainGenerator;
+
+/// Names of the generators `from_name` knows about
+pub const GENERATORS: [&str; 4] = ["terrain", "flat", "checkerboard", "void"];
+
+/// Makes the columns of a world that aren't saved, a stage at a time. A
+/// column must only depend on its position and the generator, so they can be
+/// made in any order and on any thread
+pub trait WorldGenerator: Send + Sync {
+    /// The shape of the ground
+    fn generate(&self, chunk_pos: ChunkPos) -> ChunkColumn;
+
+    /// Dig the caves of a column just generated
+    fn carve(&self, _column: &mut ChunkColumn) {}
+
+    /// Write the features of a column already carved, the ones that go over
+    /// its borders write to the columns around it too
+    fn decorate(&self, _column: &ChunkColumn, _edits: &mut BlockEdits) {}
+
+    /// Height of the first air block over the origin, where the camera
+    /// starts
+    fn spawn_height(&self) -> usize {
+        let column = self.generate(ChunkPos::new(0, 0));
+        (0..COLUMN_HEIGHT)
+            .rev()
+            .find(|y| column.get_block(BlockPos::new(0, *y, 0)) != Some(Block::AIR))
+            .map_or(0, |y| y + 1)
+    }
+}
+
+/// One of the `GENERATORS` by its name, all of them made of the default
+/// blocks. The terrain picks its structures from `structures`
+pub fn from_name(
+    name: &str,
+    seed: u64,
+    registry: &BlockRegistry,
+    structures: Vec<StructureTemplate>
+) -> Result<Box<dyn WorldGenerator>> {
+    Ok(match name {
+        "terrain" => Box::new(
+            TerrainGenerator::with_seed(seed, registry)?.with_structures(structures)
+        ),
+        "flat" => Box::new(FlatGenerator::with_default_layers(registry)?),
+        "checkerboard" => Box::new(CheckerboardGenerator::new(registry)),
+        "void" => Box::new(VoidGenerator),
+        _ => bail!("Unknown generator {:?}, expected one of {:?}", name, GENERATORS),
+    })
+}
+
+/// Every column the same stack of layers
+pub struct FlatGenerator {
+    /// Block of each layer and how many blocks tall it is, from the bottom
+    layers: Vec<(Block, usize)>,
+}
+
+impl FlatGenerator {
+    pub fn new(layers: Vec<(Block, usize)>) -> Self {
+        Self {
+            layers,
+        }
+    }
+
+    /// Stone with a few blocks of dirt and grass on top, up to the height the
+    /// terrain goes around
+    pub fn with_default_layers(registry: &BlockRegistry) -> Result<Self> {
+        let block = |name: &str| {
+            registry.by_name(name)
+                .ok_or_else(|| anyhow!("Unknown flat block {:?}", name))
+        };
+
+        Ok(Self::new(vec![
+            (block("stone")?, 60),
+            (block("dirt")?, 3),
+            (block("grass")?, 1),
+        ]))
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn generate(&self, chunk_pos: ChunkPos) -> ChunkColumn {
+        let mut column = ChunkColumn::new(chunk_pos);
+        let blocks = self.layers.iter()
+            .flat_map(|(block, height)| std::iter::repeat_n(*block, *height))
+            .take(COLUMN_HEIGHT);
+        for (y, block) in blocks.enumerate() {
+            for x in 0..SECTION_SIZE {
+                for z in 0..SECTION_SIZE {
+                    column.place_block(BlockPos::new(x, y, z), block);
+                }
+            }
+        }
+        column.compact();
+        column
+    }
+}
+
+/// Every block state of the registry floating on a square grid from the
+/// origin, with a block of air between them so all their faces can be seen
+pub struct CheckerboardGenerator {
+    blocks: Vec<Block>,
+
+    /// Blocks on each row of the grid
+    side: usize,
+}
+
+impl CheckerboardGenerator {
+    /// Height of the grid
+    const HEIGHT: usize = 64;
+
+    pub fn new(registry: &BlockRegistry) -> Self {
+        let blocks: Vec<_> = registry.states()
+            .filter(|block| *block != Block::AIR)
+            .collect();
+        let side = (blocks.len() as f32).sqrt().ceil() as usize;
+
+        Self {
+            blocks,
+            side,
+        }
+    }
+
+    /// The block on a grid cell, if there is one
+    fn block_at(&self, row: usize, cell: usize) -> Option<Block> {
+        if cell >= self.side {
+            return None;
+        }
+        self.blocks.get(row * self.side + cell).copied()
+    }
+}
+
+impl WorldGenerator for CheckerboardGenerator {
+    fn generate(&self, chunk_pos: ChunkPos) -> ChunkColumn {
+        let mut column = ChunkColumn::new(chunk_pos);
+        for x in 0..SECTION_SIZE {
+            for z in 0..SECTION_SIZE {
+                let world_x = chunk_pos.x as i64 * SECTION_SIZE as i64 + x as i64;
+                let world_z = chunk_pos.z as i64 * SECTION_SIZE as i64 + z as i64;
+                if world_x < 0 || world_z < 0 || world_x % 2 == 1 || world_z % 2 == 1 {
+                    continue;
+                }
+                let cell = (world_x / 2) as usize;
+                let row = (world_z / 2) as usize;
+                if let Some(block) = self.block_at(row, cell) {
+                    column.place_block(BlockPos::new(x, Self::HEIGHT, z), block);
+                }
+            }
+        }
+        column.compact();
+        column
+    }
+}
+
+/// Nothing but air, to build from scratch
+pub struct VoidGenerator;
+
+impl WorldGenerator for VoidGenerator {
+    fn generate(&self, chunk_pos: ChunkPos) -> ChunkColumn {
+        ChunkColumn::new(chunk_pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn flat_layers() {
+        let registry = BlockRegistry::with_default_blocks();
+        let generator = from_name("flat", 0, &registry, Vec::new()).unwrap();
+        let column = generator.generate(ChunkPos::new(-4, 9));
+        let block = |y| column.get_block(BlockPos::new(3, y, 12)).unwrap();
+        assert_eq!(block(0), registry.by_name("stone").unwrap());
+        assert_eq!(block(59), registry.by_name("stone").unwrap());
+        assert_eq!(block(62), registry.by_name("dirt").unwrap());
+        assert_eq!(block(63), registry.by_name("grass").unwrap());
+        assert_eq!(block(64), Block::AIR);
+        assert_eq!(generator.spawn_height(), 64);
+    }
+
+    #[test]
+    fn checkerboard_has_every_state() {
+        let registry = BlockRegistry::with_default_blocks();
+        let generator = from_name("checkerboard", 0, &registry, Vec::new()).unwrap();
+        let mut found = Vec::new();
+        for chunk_x in -1..2 {
+            for chunk_z in -1..2 {
+                let column = generator.generate(ChunkPos::new(chunk_x, chunk_z));
+                for y in 0..COLUMN_HEIGHT {
+                    for z in 0..SECTION_SIZE {
+                        for x in 0..SECTION_SIZE {
+                            let block = column.get_block(BlockPos::new(x, y, z)).unwrap();
+                            if block != Block::AIR {
+                                assert_eq!((chunk_x, chunk_z, y, x % 2, z % 2), (0, 0, 64, 0, 0));
+                                found.push(block);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        found.sort_by_key(|block| block.id());
+        let states: Vec<_> = registry.states().skip(1).collect();
+        assert_eq!(found, states);
+    }
+
+    #[test]
+    fn void_and_unknown_generators() {
+        let registry = BlockRegistry::with_default_blocks();
+        let generator = from_name("void", 0, &registry, Vec::new()).unwrap();
+        assert!(generator.generate(ChunkPos::new(1, 1)).sections().all(|section| section.is_empty()));
+        assert_eq!(generator.spawn_height(), 0);
+        assert!(from_name("caves", 0, &registry, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::*;
+
+use crate::block::{Block, BlockRegistry};
+use crate::chunk::BlockPos;
+
+/// A small prefab placed as is on the terrain, read from a text file like
+///
+/// ```text
+/// # A block for each character, the spaces leave the terrain as it was
+/// block S stone
+/// block L log[axis=x]
+/// block . air
+/// # The layers go from the bottom up, the rows along `z` and the characters
+/// # of a row along `x`, a blank line is a row of nothing
+/// layer
+/// SSS
+/// S.S
+/// layer
+/// LLL
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureTemplate {
+    name: String,
+
+    /// Blocks it spans on `x`, `y` and `z`
+    size: [usize; 3],
+
+    /// Every block it writes, from its lowest corner
+    blocks: Vec<(BlockPos, Block)>,
+}
+
+impl StructureTemplate {
+    /// Read a template written with the blocks of `registry`, the errors
+    /// point to the line that caused them
+    pub fn parse(name: &str, text: &str, registry: &BlockRegistry) -> Result<Self> {
+        let mut palette = HashMap::new();
+        let mut layers: Vec<Vec<&str>> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let context = || format!("Line {} of structure {}", number + 1, name);
+            if line.starts_with('#') {
+                continue;
+            }
+
+            // The blank lines are rows of nothing, the ones at the end of a
+            // layer are dropped later
+            if line.trim().is_empty() {
+                if let Some(layer) = layers.last_mut() {
+                    layer.push("");
+                }
+            } else if let Some(entry) = line.strip_prefix("block ") {
+                let Some((symbol, state)) = entry.split_once(' ') else {
+                    bail!("{}: expected a character and a block", context());
+                };
+                let mut symbols = symbol.chars();
+                let (Some(symbol), None) = (symbols.next(), symbols.next()) else {
+                    bail!("{}: expected a single character, got {:?}", context(), symbol);
+                };
+                ensure!(symbol != ' ', "{}: spaces can't be blocks", context());
+                let block = registry.parse(state).with_context(context)?;
+                ensure!(
+                    palette.insert(symbol, block).is_none(),
+                    "{}: {:?} used twice", context(), symbol
+                );
+            } else if line.trim() == "layer" {
+                layers.push(Vec::new());
+            } else {
+                let Some(layer) = layers.last_mut() else {
+                    bail!("{}: rows before the first layer", context());
+                };
+                for symbol in line.chars().filter(|symbol| *symbol != ' ') {
+                    ensure!(
+                        palette.contains_key(&symbol),
+                        "{}: unknown block {:?}", context(), symbol
+                    );
+                }
+                layer.push(line.trim_end());
+            }
+        }
+        ensure!(!layers.is_empty(), "Structure {} has no layers", name);
+        for rows in layers.iter_mut() {
+            while rows.last().is_some_and(|row| row.is_empty()) {
+                rows.pop();
+            }
+        }
+
+        let mut size = [0, layers.len(), 0];
+        let mut blocks = Vec::new();
+        for (y, rows) in layers.iter().enumerate() {
+            size[2] = size[2].max(rows.len());
+            for (z, row) in rows.iter().enumerate() {
+                size[0] = size[0].max(row.chars().count());
+                for (x, symbol) in row.chars().enumerate() {
+                    if let Some(block) = palette.get(&symbol) {
+                        blocks.push((BlockPos::new(x, y, z), *block));
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            size,
+            blocks,
+        })
+    }
+
+    /// Read a template file, named after the file
+    pub fn load(path: impl AsRef<Path>, registry: &BlockRegistry) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let name = path.file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+
+        Self::parse(name, &text, registry)
+    }
+
+    /// Every `.txt` template of a directory, sorted by name so the same files
+    /// always place the same structures
+    pub fn load_dir(dir: impl AsRef<Path>, registry: &BlockRegistry) -> Result<Vec<Self>> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?;
+        let mut templates = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "txt") {
+                continue;
+            }
+            templates.push(Self::load(&path, registry)?);
+        }
+        templates.sort_by(|a, b| a.name().cmp(b.name()));
+
+        Ok(templates)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    pub fn blocks(&self) -> &[(BlockPos, Block)] {
+        &self.blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn parse_templates() {
+        let registry = BlockRegistry::with_default_blocks();
+        let text = "\
+# A stone ring with a log on top
+block S stone
+block L log[axis=x]
+block . air
+
+layer
+SSS
+S.S
+SSS
+
+layer
+
+ L
+
+";
+        let template = StructureTemplate::parse("ring", text, &registry).unwrap();
+        assert_eq!(template.name(), "ring");
+        assert_eq!(template.size(), [3, 2, 3]);
+        let stone = registry.by_name("stone").unwrap();
+        let log = registry.parse("log[axis=x]").unwrap();
+        assert_eq!(template.blocks().len(), 10);
+        assert_eq!(template.blocks()[0], (BlockPos::new(0, 0, 0), stone));
+        assert_eq!(template.blocks()[4], (BlockPos::new(1, 0, 1), Block::AIR));
+        assert_eq!(template.blocks()[9], (BlockPos::new(1, 1, 1), log));
+
+        for (text, error) in [
+            ("layer\nS", "unknown block 'S'"),
+            ("block S stone\nS", "rows before the first layer"),
+            ("block S marble\nlayer\nS", "Unknown block \"marble\""),
+            ("block SS stone", "expected a single character"),
+            ("block S stone\nblock S dirt", "'S' used twice"),
+            ("block S stone", "has no layers"),
+        ] {
+            let e = StructureTemplate::parse("broken", text, &registry).unwrap_err();
+            assert!(format!("{:#}", e).contains(error), "{:#}", e);
+        }
+    }
+
+    #[test]
+    fn shipped_templates_load() {
+        let registry = BlockRegistry::with_default_blocks();
+        let templates = StructureTemplate::load_dir("assets/structures", &registry).unwrap();
+        assert!(!templates.is_empty());
+        assert!(templates.windows(2).all(|pair| pair[0].name() < pair[1].name()));
+    }
+}
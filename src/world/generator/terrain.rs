@@ -0,0 +1,477 @@
+use anyhow::*;
+
+use crate::biome::Biome;
+use crate::block::{Block, BlockRegistry};
+use crate::chunk::{BlockPos, ChunkColumn, ChunkPos, COLUMN_HEIGHT, SECTION_SIZE};
+use crate::world::noise::Fractal;
+
+use super::WorldGenerator;
+use super::carver::{CaveCarver, CaveConfig};
+use super::decorator::{BlockEdits, DecorationConfig, Decorator};
+use super::structure::StructureTemplate;
+
+/// Shape of the terrain, the same config and seed always give the same world.
+/// How high the ground goes and what it's covered with depends on the biomes
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainConfig {
+    /// The air below it is filled with water
+    pub sea_level: usize,
+
+    /// How far the 3D noise moves the surface, carving the overhangs
+    pub overhang: f64,
+
+    /// Blocks of soil under the surface block before the stone
+    pub soil_depth: usize,
+
+    /// Distance, in blocks, the heights of the biomes are blended over
+    pub biome_blend: i64,
+
+    pub height_noise: Fractal,
+    pub overhang_noise: Fractal,
+
+    /// Temperature and humidity the biomes are picked from
+    pub climate_noise: Fractal,
+
+    /// The caves dug after the terrain is made, none without it
+    pub caves: Option<CaveConfig>,
+
+    /// The trees, boulders and structures placed on the surface, none
+    /// without it
+    pub decoration: Option<DecorationConfig>,
+
+    /// Names of the blocks the terrain is made of under the biome layers
+    pub stone_block: String,
+    pub water_block: String,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            sea_level: 62,
+            overhang: 6.0,
+            soil_depth: 3,
+            biome_blend: 16,
+            height_noise: Fractal {
+                octaves: 5,
+                frequency: 1.0 / 256.0,
+                persistence: 0.5,
+            },
+            overhang_noise: Fractal {
+                octaves: 3,
+                frequency: 1.0 / 24.0,
+                persistence: 0.5,
+            },
+            climate_noise: Fractal {
+                octaves: 3,
+                frequency: 1.0 / 512.0,
+                persistence: 0.5,
+            },
+            caves: Some(CaveConfig::default()),
+            decoration: Some(DecorationConfig::default()),
+            stone_block: "stone".to_owned(),
+            water_block: "water".to_owned(),
+        }
+    }
+}
+
+/// Hills, overhangs and seas out of noise, each column only depends on the
+/// seed and its position
+pub struct TerrainGenerator {
+    seed: u64,
+    config: TerrainConfig,
+    stone: Block,
+    water: Block,
+
+    /// Surface and soil blocks of each biome, by biome id
+    biome_blocks: Vec<(Block, Block)>,
+
+    carver: Option<CaveCarver>,
+    decorator: Option<Decorator>,
+}
+
+impl TerrainGenerator {
+    /// Spacing, in blocks, of the points the biomes are sampled at to blend
+    /// their heights
+    const BLEND_STEP: i64 = 4;
+
+    /// A generator of the blocks of `registry`, fails if the config or the
+    /// biomes name blocks that aren't in it
+    pub fn new(seed: u64, config: TerrainConfig, registry: &BlockRegistry) -> Result<Self> {
+        let block = |name: &str| {
+            registry.by_name(name)
+                .ok_or_else(|| anyhow!("Unknown terrain block {:?}", name))
+        };
+        let biome_blocks = Biome::ALL.iter()
+            .map(|biome| {
+                let terrain = biome.terrain();
+                Ok((block(terrain.surface_block)?, block(terrain.soil_block)?))
+            })
+            .collect::<Result<_>>()?;
+
+        let water = block(&config.water_block)?;
+        let carver = config.caves.clone()
+            .map(|caves| CaveCarver::new(seed, caves, water));
+        let decorator = config.decoration.clone()
+            .map(|decoration| Decorator::new(seed, decoration, registry, water))
+            .transpose()?;
+
+        Ok(Self {
+            seed,
+            stone: block(&config.stone_block)?,
+            water,
+            biome_blocks,
+            carver,
+            decorator,
+            config,
+        })
+    }
+
+    /// Place structures picked from `structures`, without decoration there
+    /// are none
+    pub fn with_structures(mut self, structures: Vec<StructureTemplate>) -> Self {
+        if let Some(decorator) = self.decorator.as_mut() {
+            decorator.set_structures(structures);
+        }
+        self
+    }
+
+    /// The default terrain made of the default blocks
+    pub fn with_seed(seed: u64, registry: &BlockRegistry) -> Result<Self> {
+        Self::new(seed, TerrainConfig::default(), registry)
+    }
+
+    /// The biome of the climate at a world position
+    pub fn biome(&self, x: i64, z: i64) -> Biome {
+        // Seeds of their own so the climate doesn't follow the height, the
+        // noise rarely gets past a half so it's stretched to the whole range
+        let climate = |seed: u64| {
+            let noise = self.config.climate_noise.sample2(seed, x as f64, z as f64);
+            (noise * 2.0).clamp(-1.0, 1.0)
+        };
+        Biome::from_climate(climate(self.seed ^ 0x2545f491), climate(self.seed ^ 0x9e3779b9))
+    }
+
+    /// Base height and variation of the biomes around a world position,
+    /// weighted by how close they are. The biomes are sampled on a grid
+    /// aligned to the world, `biomes` has them for the grid points around
+    /// the column being generated
+    fn blended_height(
+        &self,
+        x: i64,
+        z: i64,
+        biomes: &impl Fn(i64, i64) -> Biome
+    ) -> (f64, f64) {
+        let blend = self.config.biome_blend;
+        let step = Self::BLEND_STEP;
+        let (mut base, mut variation, mut total) = (0.0, 0.0, 0.0);
+        for grid_x in (x - blend).div_euclid(step)..=(x + blend).div_euclid(step) {
+            for grid_z in (z - blend).div_euclid(step)..=(z + blend).div_euclid(step) {
+                let weight = |distance: i64| {
+                    (1.0 - distance.abs() as f64 / (blend + 1) as f64).max(0.0)
+                };
+                let weight = weight(grid_x * step - x) * weight(grid_z * step - z);
+                let terrain = biomes(grid_x, grid_z).terrain();
+                base += terrain.base_height * weight;
+                variation += terrain.height_variation * weight;
+                total += weight;
+            }
+        }
+
+        (base / total, variation / total)
+    }
+
+    /// Whether the block at a world position is ground, the overhang noise is
+    /// only sampled close to the surface
+    fn is_ground(&self, x: i64, y: usize, z: i64, height: f64) -> bool {
+        let depth = height - y as f64;
+        let overhang = self.config.overhang;
+        if depth > overhang {
+            return true;
+        }
+        if depth < -overhang {
+            return false;
+        }
+
+        // Another seed so the overhangs don't follow the height
+        let seed = self.seed ^ 0x5bd1e995;
+        let noise = self.config.overhang_noise.sample3(seed, x as f64, y as f64, z as f64);
+        y == 0 || depth + noise * overhang > 0.0
+    }
+}
+
+impl WorldGenerator for TerrainGenerator {
+    fn generate(&self, chunk_pos: ChunkPos) -> ChunkColumn {
+        let mut column = ChunkColumn::new(chunk_pos);
+        let chunk_x = chunk_pos.x as i64 * SECTION_SIZE as i64;
+        let chunk_z = chunk_pos.z as i64 * SECTION_SIZE as i64;
+
+        // The grid points every column of the chunk blends, sampled once
+        let step = Self::BLEND_STEP;
+        let blend = self.config.biome_blend;
+        let grid_min = ((chunk_x - blend).div_euclid(step), (chunk_z - blend).div_euclid(step));
+        let grid_max = (
+            (chunk_x + SECTION_SIZE as i64 + blend).div_euclid(step),
+            (chunk_z + SECTION_SIZE as i64 + blend).div_euclid(step)
+        );
+        let grid_width = (grid_max.0 - grid_min.0 + 1) as usize;
+        let mut grid = Vec::new();
+        for grid_z in grid_min.1..=grid_max.1 {
+            for grid_x in grid_min.0..=grid_max.0 {
+                grid.push(self.biome(grid_x * step, grid_z * step));
+            }
+        }
+        let grid_biome = |grid_x: i64, grid_z: i64| {
+            let (x, z) = ((grid_x - grid_min.0) as usize, (grid_z - grid_min.1) as usize);
+            grid[z * grid_width + x]
+        };
+
+        for x in 0..SECTION_SIZE {
+            for z in 0..SECTION_SIZE {
+                let (world_x, world_z) = (chunk_x + x as i64, chunk_z + z as i64);
+                let biome = self.biome(world_x, world_z);
+                column.set_biome(x, z, biome);
+                let (surface, soil) = self.biome_blocks[biome.id() as usize];
+
+                let (base_height, variation) = self.blended_height(world_x, world_z, &grid_biome);
+                let noise = self.config.height_noise
+                    .sample2(self.seed, world_x as f64, world_z as f64);
+                let height = (base_height + noise * variation)
+                    .clamp(1.0, (COLUMN_HEIGHT - 1) as f64);
+                let top = ((height + self.config.overhang).ceil() as usize)
+                    .max(self.config.sea_level)
+                    .min(COLUMN_HEIGHT - 1);
+
+                // From the top down, counting the ground blocks since the
+                // last air for the layers
+                let mut depth = None;
+                for y in (0..=top).rev() {
+                    let block = if self.is_ground(world_x, y, world_z, height) {
+                        let layer = depth.map_or(0, |depth| depth + 1);
+                        depth = Some(layer);
+                        if layer == 0 && y + 1 >= self.config.sea_level {
+                            surface
+                        } else if layer <= self.config.soil_depth {
+                            soil
+                        } else {
+                            self.stone
+                        }
+                    } else {
+                        depth = None;
+                        if y < self.config.sea_level {
+                            self.water
+                        } else {
+                            continue;
+                        }
+                    };
+                    column.place_block(BlockPos::new(x, y, z), block);
+                }
+            }
+        }
+        column.compact();
+        column
+    }
+
+    fn carve(&self, column: &mut ChunkColumn) {
+        if let Some(carver) = &self.carver {
+            carver.carve(column);
+        }
+    }
+
+    fn decorate(&self, column: &ChunkColumn, edits: &mut BlockEdits) {
+        if let Some(decorator) = &self.decorator {
+            decorator.decorate(column, edits);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    /// Checksum of every block of the column
+    fn column_hash(column: &ChunkColumn) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        for y in 0..COLUMN_HEIGHT {
+            for z in 0..SECTION_SIZE {
+                for x in 0..SECTION_SIZE {
+                    let block = column.get_block(BlockPos::new(x, y, z)).unwrap();
+                    hasher.update(&block.id().to_le_bytes());
+                }
+            }
+        }
+        hasher.finalize()
+    }
+
+    /// A column with its caves, like the world gets it
+    fn carved(generator: &TerrainGenerator, chunk_pos: ChunkPos) -> ChunkColumn {
+        let mut column = generator.generate(chunk_pos);
+        generator.carve(&mut column);
+        column
+    }
+
+    #[test]
+    fn generation_is_reproducible() {
+        let registry = BlockRegistry::with_default_blocks();
+        let generator = TerrainGenerator::with_seed(42, &registry).unwrap();
+        let other_seed = TerrainGenerator::with_seed(43, &registry).unwrap();
+        for chunk_pos in [ChunkPos::new(0, 0), ChunkPos::new(-3, 7)] {
+            let hash = column_hash(&carved(&generator, chunk_pos));
+            assert_eq!(hash, column_hash(&carved(&generator, chunk_pos)));
+            assert_ne!(hash, column_hash(&carved(&other_seed, chunk_pos)));
+        }
+        assert_ne!(
+            column_hash(&carved(&generator, ChunkPos::new(0, 0))),
+            column_hash(&carved(&generator, ChunkPos::new(1, 0)))
+        );
+
+        // Pinned, a change here changes every world already generated
+        assert_eq!(column_hash(&carved(&generator, ChunkPos::new(0, 0))), 2426310266);
+    }
+
+    /// Surface height, block under it and biome of every `x`, `z` of a
+    /// column of a terrain without overhangs
+    fn surfaces(column: &ChunkColumn, water: Block) -> Vec<(usize, Biome)> {
+        let mut surfaces = Vec::new();
+        for z in 0..SECTION_SIZE {
+            for x in 0..SECTION_SIZE {
+                let ground = (0..COLUMN_HEIGHT)
+                    .position(|y| {
+                        let block = column.get_block(BlockPos::new(x, y, z)).unwrap();
+                        block == Block::AIR || block == water
+                    })
+                    .unwrap();
+                surfaces.push((ground, column.biome(x, z)));
+            }
+        }
+        surfaces
+    }
+
+    #[test]
+    fn layers() {
+        let registry = BlockRegistry::with_default_blocks();
+        let block = |name| registry.by_name(name).unwrap();
+        let config = TerrainConfig {
+            overhang: 0.0,
+            caves: None,
+            ..TerrainConfig::default()
+        };
+        let generator = TerrainGenerator::new(7, config.clone(), &registry).unwrap();
+
+        // Without overhangs the ground is solid up to the surface, covered by
+        // the layers of the biome and the sea
+        let mut wet = 0;
+        for chunk_pos in [ChunkPos::new(-2, -5), ChunkPos::new(-5, -1), ChunkPos::new(-104, -44)] {
+            let column = generator.generate(chunk_pos);
+            for x in 0..SECTION_SIZE {
+                for z in 0..SECTION_SIZE {
+                    let blocks: Vec<_> = (0..COLUMN_HEIGHT)
+                        .map(|y| column.get_block(BlockPos::new(x, y, z)).unwrap())
+                        .collect();
+                    let ground = blocks.iter()
+                        .position(|b| *b == Block::AIR || *b == block("water"))
+                        .unwrap();
+                    let top = ground - 1;
+                    let terrain = column.biome(x, z).terrain();
+                    let soil = block(terrain.soil_block);
+                    if ground >= config.sea_level {
+                        assert_eq!(blocks[top], block(terrain.surface_block));
+                    } else {
+                        wet += 1;
+                        assert_eq!(blocks[top], soil);
+                        assert!(blocks[ground..config.sea_level].iter().all(|b| *b == block("water")));
+                    }
+                    assert!(blocks[top - 3..top].iter().all(|b| *b == soil));
+                    assert!(blocks[..top - 3].iter().all(|b| *b == block("stone")));
+                    assert!(blocks[config.sea_level.max(ground)..].iter().all(|b| *b == Block::AIR));
+                }
+            }
+        }
+        assert!(wet > 0 && wet < 3 * SECTION_SIZE * SECTION_SIZE);
+        assert_eq!(generator.biome(-104 * 16, -44 * 16), Biome::Ocean);
+
+        assert!(TerrainGenerator::new(
+            7,
+            TerrainConfig {
+                water_block: "lava".to_owned(),
+                ..TerrainConfig::default()
+            },
+            &registry
+        ).is_err());
+    }
+
+    #[test]
+    fn biome_borders_are_smooth() {
+        let registry = BlockRegistry::with_default_blocks();
+        let config = TerrainConfig {
+            overhang: 0.0,
+            caves: None,
+            ..TerrainConfig::default()
+        };
+        let generator = TerrainGenerator::new(7, config, &registry).unwrap();
+        let water = registry.by_name("water").unwrap();
+
+        // A row of columns from the mountains to the forest, the biomes
+        // change from a block to the next but the height doesn't jump
+        let mut row = Vec::new();
+        for chunk_x in -119..-114 {
+            let surfaces = surfaces(&generator.generate(ChunkPos::new(chunk_x, 0)), water);
+            row.extend_from_slice(&surfaces[..SECTION_SIZE]);
+        }
+        assert_eq!(row.first().unwrap().1, Biome::Mountains);
+        assert_eq!(row.last().unwrap().1, Biome::Forest);
+        for pair in row.windows(2) {
+            assert!(pair[0].0.abs_diff(pair[1].0) <= 3, "{:?}", pair);
+        }
+
+        // Each biome has its own tint, stored with the blocks
+        let column = generator.generate(ChunkPos::new(-118, 0));
+        let biomes = column.biomes();
+        assert_eq!(biomes.len(), SECTION_SIZE * SECTION_SIZE);
+        assert_eq!(biomes[0], generator.biome(-118 * 16, 0));
+    }
+
+    #[test]
+    fn forests_have_trees() {
+        let registry = BlockRegistry::with_default_blocks();
+        let block = |name| registry.by_name(name).unwrap();
+        let generator = TerrainGenerator::with_seed(7, &registry).unwrap();
+        let chunk_pos = ChunkPos::new(-115, 0);
+        let column = carved(&generator, chunk_pos);
+        assert_eq!(column.biome(8, 8), Biome::Forest);
+
+        let mut edits = BlockEdits::new();
+        generator.decorate(&column, &mut edits);
+        let mut again = BlockEdits::new();
+        generator.decorate(&column, &mut again);
+        assert_eq!(edits, again);
+
+        // The trunks grow from the grass, the leaves of the trees on the
+        // border go over it
+        let own = edits.take(chunk_pos);
+        let grounds: Vec<_> = own.iter()
+            .filter(|edit| edit.block == block("log"))
+            .map(|edit| {
+                let pos = edit.block_pos;
+                column.get_block(BlockPos::new(pos.x, pos.y - 1, pos.z)).unwrap()
+            })
+            .filter(|below| *below != Block::AIR)
+            .collect();
+        assert!(grounds.len() > 3);
+        assert!(grounds.iter().all(|ground| *ground == block("grass") || *ground == block("dirt")));
+        let mut spilled: Vec<_> = edits.columns().collect();
+        spilled.sort_by_key(|pos| (pos.x, pos.z));
+        assert_eq!(spilled, vec![ChunkPos::new(-116, 0), ChunkPos::new(-114, 0)]);
+        assert!(edits.take(ChunkPos::new(-114, 0)).iter().all(|edit| edit.block == block("leaves")));
+
+        // Nothing without decoration
+        let config = TerrainConfig {
+            decoration: None,
+            ..TerrainConfig::default()
+        };
+        let generator = TerrainGenerator::new(7, config, &registry).unwrap();
+        let mut edits = BlockEdits::new();
+        generator.decorate(&column, &mut edits);
+        assert!(edits.is_empty());
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::block::Block;
+use crate::chunk::{
+    BlockPos, ChunkPos, Face, Light, SectionPos, WorldBlockPos, COLUMN_HEIGHT,
+    MAX_LIGHT, SECTION_SIZE
+};
+
+use super::{touched_sections, World};
+
+/// The two kinds of light, spread independently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    /// Comes down from the top of the world without getting weaker, and
+    /// spreads from there like block light
+    Sky,
+
+    /// Emitted by blocks like glowstone
+    Block,
+}
+
+impl World {
+    /// Light at a block, above the world everything is lit by the sky. `None`
+    /// on the columns that aren't loaded
+    pub fn light(&self, pos: WorldBlockPos) -> Option<Light> {
+        if pos.y >= COLUMN_HEIGHT as i64 {
+            return Some(Light::SKY);
+        }
+        let (chunk_pos, block_pos) = pos.local()?;
+        self.column(chunk_pos)?.light(block_pos)
+    }
+
+    fn channel_light(&self, channel: Channel, pos: WorldBlockPos) -> Option<u8> {
+        let light = self.light(pos)?;
+        Some(match channel {
+            Channel::Sky => light.sky,
+            Channel::Block => light.block,
+        })
+    }
+
+    /// Change the light of a block, remembering the sections that will have
+    /// to be remeshed. False if the block can't hold light, like the ones out
+    /// of the world
+    fn set_channel_light(
+        &mut self,
+        channel: Channel,
+        pos: WorldBlockPos,
+        level: u8,
+        changed: &mut HashSet<SectionPos>
+    ) -> bool {
+        let Some((chunk_pos, block_pos)) = pos.local() else {
+            return false;
+        };
+        let Some(column) = self.column_mut(chunk_pos) else {
+            return false;
+        };
+        match channel {
+            Channel::Sky => column.set_sky_light(block_pos, level),
+            Channel::Block => column.set_block_light(block_pos, level),
+        };
+        changed.extend(touched_sections(chunk_pos, block_pos));
+
+        true
+    }
+
+    /// Whether the light can't get into a block, the missing columns and the
+    /// bottom of the world stop it too
+    fn blocks_light(&self, pos: WorldBlockPos) -> bool {
+        if pos.y >= COLUMN_HEIGHT as i64 {
+            return false;
+        }
+        self.get_block(pos).is_none_or(|block| self.registry.is_full_cube(block))
+    }
+
+    fn emission(&self, channel: Channel, pos: WorldBlockPos) -> u8 {
+        if channel == Channel::Sky {
+            return 0;
+        }
+        self.get_block(pos).map_or(0, |block| self.registry.get(block).light_emission)
+    }
+
+    /// Flood fill the light, first darkening from the `decrease` blocks,
+    /// which hold the level they had before being set to 0, and then
+    /// brightening from the `increase` ones and the edges of the darkened
+    /// area
+    fn spread(
+        &mut self,
+        channel: Channel,
+        mut increase: VecDeque<WorldBlockPos>,
+        mut decrease: VecDeque<(WorldBlockPos, u8)>,
+        changed: &mut HashSet<SectionPos>
+    ) {
+        while let Some((pos, level)) = decrease.pop_front() {
+            for face in Face::ALL {
+                let next = pos.offset(face);
+                let Some(light) = self.channel_light(channel, next) else {
+                    continue;
+                };
+                if light == 0 {
+                    continue;
+                }
+
+                // Anything dimmer was lit through `pos`, anything brighter
+                // has another source and has to fill the darkened blocks
+                let falls = channel == Channel::Sky && face == Face::Down
+                    && level == MAX_LIGHT;
+                if (light < level || (falls && light == MAX_LIGHT))
+                    && self.set_channel_light(channel, next, 0, changed)
+                {
+                    decrease.push_back((next, light));
+                    let emission = self.emission(channel, next);
+                    if emission > 0 {
+                        self.set_channel_light(channel, next, emission, changed);
+                        increase.push_back(next);
+                    }
+                } else {
+                    increase.push_back(next);
+                }
+            }
+        }
+
+        while let Some(pos) = increase.pop_front() {
+            let Some(level) = self.channel_light(channel, pos) else {
+                continue;
+            };
+            for face in Face::ALL {
+                let next = pos.offset(face);
+                if next.y >= COLUMN_HEIGHT as i64 || self.blocks_light(next) {
+                    continue;
+                }
+
+                let new_level = if channel == Channel::Sky && face == Face::Down
+                    && level == MAX_LIGHT
+                {
+                    MAX_LIGHT
+                } else {
+                    level.saturating_sub(1)
+                };
+                if self.channel_light(channel, next).is_some_and(|light| light < new_level) {
+                    self.set_channel_light(channel, next, new_level, changed);
+                    increase.push_back(next);
+                }
+            }
+        }
+    }
+
+    /// Update the light after the block at `pos` changed from `old`, returns
+    /// the sections whose light changed
+    pub(super) fn relight_block(
+        &mut self,
+        pos: WorldBlockPos,
+        old: Block
+    ) -> HashSet<SectionPos> {
+        let mut changed = HashSet::new();
+        let old_opaque = self.registry.is_full_cube(old);
+        let new_opaque = self.blocks_light(pos);
+
+        for channel in [Channel::Sky, Channel::Block] {
+            let old_emission = match channel {
+                Channel::Sky => 0,
+                Channel::Block => self.registry.get(old).light_emission,
+            };
+            let new_emission = self.emission(channel, pos);
+            if old_opaque == new_opaque && old_emission == new_emission {
+                continue;
+            }
+
+            // Remove the light that was here and let the neighbors fill the
+            // block again if it lets the light through
+            let level = self.channel_light(channel, pos).unwrap_or(0);
+            self.set_channel_light(channel, pos, 0, &mut changed);
+            let mut increase = VecDeque::new();
+            if new_emission > 0 {
+                self.set_channel_light(channel, pos, new_emission, &mut changed);
+                increase.push_back(pos);
+            }
+            self.spread(channel, increase, VecDeque::from([(pos, level)]), &mut changed);
+        }
+
+        changed
+    }
+
+    /// Compute the light of freshly loaded columns, letting the light of the
+    /// columns around them in and out. Returns the sections whose light
+    /// changed
+    pub(super) fn light_columns(&mut self, chunk_positions: &[ChunkPos]) -> HashSet<SectionPos> {
+        let registry = self.registry.clone();
+        for chunk_pos in chunk_positions {
+            if let Some(column) = self.column_mut(*chunk_pos) {
+                column.reset_light(|block| registry.is_full_cube(block));
+            }
+        }
+
+        let size = SECTION_SIZE as i64;
+        let height = |world: &World, x: i64, z: i64| {
+            let (chunk_pos, block_pos) = WorldBlockPos::new(x, 0, z).local()?;
+            Some(world.column(chunk_pos)?.height(block_pos.x, block_pos.z) as i64)
+        };
+        let horizontal = [Face::Front, Face::Back, Face::Left, Face::Right];
+
+        let mut changed = HashSet::new();
+        let mut sky = VecDeque::new();
+        let mut block = VecDeque::new();
+        for chunk_pos in chunk_positions {
+            let Some(column) = self.column(*chunk_pos) else {
+                continue;
+            };
+            let (x0, z0) = (chunk_pos.x as i64 * size, chunk_pos.z as i64 * size);
+
+            // The direct sky light spreads sideways below the neighbors that
+            // are taller
+            for x in x0..x0 + size {
+                for z in z0..z0 + size {
+                    let own = height(self, x, z).unwrap();
+                    for face in horizontal {
+                        let next = WorldBlockPos::new(x, 0, z).offset(face);
+                        if let Some(neighbor) = height(self, next.x, next.z) {
+                            sky.extend((own..neighbor).map(|y| WorldBlockPos::new(x, y, z)));
+                        }
+                    }
+                }
+            }
+
+            let mut sources = Vec::new();
+            for section in column.sections().filter(|section| !section.is_empty()) {
+                let y0 = section.pos().y as i64 * size;
+                for (x, y, z) in (0..size).flat_map(|x| {
+                    (0..size).flat_map(move |y| (0..size).map(move |z| (x, y, z)))
+                }) {
+                    let block_pos = BlockPos::new(x as usize, y as usize, z as usize);
+                    let emission = registry.get(section.get_block(block_pos).unwrap())
+                        .light_emission;
+                    if emission > 0 {
+                        sources.push((WorldBlockPos::new(x0 + x, y0 + y, z0 + z), emission));
+                    }
+                }
+            }
+            for (pos, emission) in sources {
+                self.set_channel_light(Channel::Block, pos, emission, &mut changed);
+                block.push_back(pos);
+            }
+
+            // Light from the columns already loaded gets in through the border,
+            // only where it would brighten the block next to it as most of
+            // the border is lit the same on both sides
+            for face in horizontal {
+                let [dx, _, dz] = face.normal();
+                let neighbor_pos = ChunkPos::new(chunk_pos.x + dx, chunk_pos.z + dz);
+                if chunk_positions.contains(&neighbor_pos) {
+                    continue;
+                }
+                let (Some(own), Some(neighbor)) = (self.column(*chunk_pos), self.column(neighbor_pos))
+                else {
+                    continue;
+                };
+                let last = SECTION_SIZE - 1;
+                for i in 0..SECTION_SIZE {
+                    let (inside, outside) = match face {
+                        Face::Front => ([i, 0], [i, last]),
+                        Face::Back => ([i, last], [i, 0]),
+                        Face::Left => ([0, i], [last, i]),
+                        _ => ([last, i], [0, i]),
+                    };
+                    for y in 0..COLUMN_HEIGHT {
+                        let light = neighbor.light(BlockPos::new(outside[0], y, outside[1])).unwrap();
+                        let next = own.light(BlockPos::new(inside[0], y, inside[1])).unwrap();
+                        let pos = neighbor_pos.block(BlockPos::new(outside[0], y, outside[1]));
+                        if light.sky > next.sky + 1 {
+                            sky.push_back(pos);
+                        }
+                        if light.block > next.block + 1 {
+                            block.push_back(pos);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.spread(Channel::Sky, sky, VecDeque::new(), &mut changed);
+        self.spread(Channel::Block, block, VecDeque::new(), &mut changed);
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+    use crate::chunk::ChunkColumn;
+    use crate::world::tests::{empty_world, insert_column};
+
+    /// A world of 3x3 columns with a stone floor of 10 blocks and a sealed
+    /// room dug on the middle one
+    fn cave_world() -> World {
+        let mut world = empty_world();
+        let stone = world.registry().by_name("stone").unwrap();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                let mut column = ChunkColumn::new(ChunkPos::new(x, z));
+                for x in 0..16 {
+                    for y in 0..10 {
+                        for z in 0..16 {
+                            column.place_block(BlockPos::new(x, y, z), stone);
+                        }
+                    }
+                }
+                insert_column(&mut world, column);
+            }
+        }
+        world.generate_columns(usize::MAX);
+
+        for x in 2..10 {
+            for y in 3..6 {
+                for z in 2..10 {
+                    world.place_block(ChunkPos::new(0, 0), BlockPos::new(x, y, z), Block::AIR);
+                }
+            }
+        }
+        world.dirty_sections(usize::MAX);
+
+        world
+    }
+
+    #[test]
+    fn sky_light_and_caves() {
+        let world = cave_world();
+        assert_eq!(world.light(WorldBlockPos::new(5, 10, 5)), Some(Light::SKY));
+        assert_eq!(world.light(WorldBlockPos::new(-10, 200, 30)), Some(Light::SKY));
+        assert_eq!(world.light(WorldBlockPos::new(5, 300, 5)), Some(Light::SKY));
+        // The room is sealed so no light gets in
+        assert_eq!(world.light(WorldBlockPos::new(5, 4, 5)), Some(Light::new(0, 0)));
+        assert_eq!(world.light(WorldBlockPos::new(100, 4, 5)), None);
+    }
+
+    #[test]
+    fn opening_and_closing_a_hole() {
+        let mut world = cave_world();
+        let stone = world.registry().by_name("stone").unwrap();
+        for y in 6..10 {
+            world.place_block(ChunkPos::new(0, 0), BlockPos::new(5, y, 5), Block::AIR);
+        }
+
+        // Straight down the hole the sky light doesn't get weaker, then it
+        // fades away from it
+        assert_eq!(world.light(WorldBlockPos::new(5, 3, 5)).unwrap().sky, 15);
+        assert_eq!(world.light(WorldBlockPos::new(6, 3, 5)).unwrap().sky, 14);
+        assert_eq!(world.light(WorldBlockPos::new(7, 4, 6)).unwrap().sky, 12);
+        assert_eq!(world.light(WorldBlockPos::new(9, 5, 9)).unwrap().sky, 7);
+
+        // Dimmer sections have to be remeshed
+        assert!(world.dirty_sections(usize::MAX).contains(&SectionPos::new(0, 0, 0)));
+
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(5, 9, 5), stone);
+        assert_eq!(world.light(WorldBlockPos::new(5, 8, 5)).unwrap().sky, 0);
+        assert_eq!(world.light(WorldBlockPos::new(5, 3, 5)).unwrap().sky, 0);
+        assert_eq!(world.light(WorldBlockPos::new(9, 5, 9)).unwrap().sky, 0);
+        assert_eq!(world.light(WorldBlockPos::new(5, 10, 5)).unwrap().sky, 15);
+    }
+
+    #[test]
+    fn light_sources() {
+        let mut world = cave_world();
+        let glowstone = world.registry().by_name("glowstone").unwrap();
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(2, 3, 2), glowstone);
+
+        assert_eq!(world.light(WorldBlockPos::new(2, 3, 2)).unwrap().block, 15);
+        assert_eq!(world.light(WorldBlockPos::new(3, 3, 2)).unwrap().block, 14);
+        assert_eq!(world.light(WorldBlockPos::new(5, 4, 4)).unwrap().block, 9);
+        // Doesn't go through the walls
+        assert_eq!(world.light(WorldBlockPos::new(2, 3, 1)).unwrap().block, 0);
+        assert_eq!(world.light(WorldBlockPos::new(5, 4, 5)).unwrap().sky, 0);
+
+        // Two sources, removing one keeps the other lit
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(9, 5, 9), glowstone);
+        assert_eq!(world.light(WorldBlockPos::new(8, 5, 9)).unwrap().block, 14);
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(2, 3, 2), Block::AIR);
+        assert_eq!(world.light(WorldBlockPos::new(2, 3, 2)).unwrap().block, 0);
+        assert_eq!(world.light(WorldBlockPos::new(5, 4, 5)).unwrap().block, 6);
+        assert_eq!(world.light(WorldBlockPos::new(8, 5, 9)).unwrap().block, 14);
+    }
+
+    #[test]
+    fn light_crosses_columns() {
+        let mut world = cave_world();
+        let glowstone = world.registry().by_name("glowstone").unwrap();
+        // Tunnel from the room to the column on the left
+        for x in 0..2 {
+            world.place_block(ChunkPos::new(0, 0), BlockPos::new(x, 4, 5), Block::AIR);
+        }
+        for x in 12..16 {
+            world.place_block(ChunkPos::new(-1, 0), BlockPos::new(x, 4, 5), Block::AIR);
+        }
+        world.dirty_sections(usize::MAX);
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(2, 4, 5), glowstone);
+
+        assert_eq!(world.light(WorldBlockPos::new(-1, 4, 5)).unwrap().block, 12);
+        assert_eq!(world.light(WorldBlockPos::new(-4, 4, 5)).unwrap().block, 9);
+        assert!(world.dirty_sections(usize::MAX).contains(&SectionPos::new(-1, 0, 0)));
+
+        // Columns loaded later get the light too
+        world.unload_column(ChunkPos::new(-1, 0)).unwrap();
+        assert_eq!(world.light(WorldBlockPos::new(-1, 4, 5)), None);
+        let stone = world.registry().by_name("stone").unwrap();
+        let mut column = ChunkColumn::new(ChunkPos::new(-1, 0));
+        for x in 0..16 {
+            for y in 0..10 {
+                for z in 0..16 {
+                    column.place_block(BlockPos::new(x, y, z), stone);
+                }
+            }
+        }
+        for x in 12..16 {
+            column.place_block(BlockPos::new(x, 4, 5), Block::AIR);
+        }
+        insert_column(&mut world, column);
+        world.generate_columns(usize::MAX);
+        assert_eq!(world.light(WorldBlockPos::new(-1, 4, 5)).unwrap().block, 12);
+        assert_eq!(world.light(WorldBlockPos::new(-4, 4, 5)).unwrap().block, 9);
+        assert_eq!(world.light(WorldBlockPos::new(-4, 10, 5)).unwrap().sky, 15);
+        assert_eq!(world.light(WorldBlockPos::new(-4, 9, 5)).unwrap().sky, 0);
+    }
+}
@@ -0,0 +1,826 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use anyhow::*;
+use cgmath::{Point3, Vector3};
+
+use crate::block::{Block, BlockRegistry};
+use crate::chunk::{
+    BlockPos, ChunkColumn, ChunkNeighborhood, ChunkPos, Section, SectionPos,
+    WorldBlockPos, SECTION_SIZE
+};
+use crate::raycast::{raycast, RayHit};
+use crate::region::RegionStorage;
+use crate::workers::WorkerPool;
+
+pub mod generator;
+mod light;
+mod noise;
+mod pipeline;
+
+pub use generator::{BlockEdits, WorldGenerator};
+pub use pipeline::ChunkStatus;
+
+use pipeline::StageOutput;
+
+/// Offsets of the columns around a column, the ones on the diagonals are
+/// needed for the ambient occlusion of the corners
+const NEIGHBORS: [(i32, i32); 8] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1,  0),          (1,  0),
+    (-1,  1), (0,  1), (1,  1),
+];
+
+/// Offsets of the 26 sections around a section
+fn section_neighbors() -> impl Iterator<Item = (i32, i32, i32)> {
+    (-1..=1)
+        .flat_map(|dy| (-1..=1).flat_map(move |dz| (-1..=1).map(move |dx| (dx, dy, dz))))
+        .filter(|offset| *offset != (0, 0, 0))
+}
+
+/// Sections a block touches, its own one and the neighbors it's on the border
+/// of. Those see the block when meshed, to hide their faces, for the ambient
+/// occlusion and the light, on an edge or a corner even the diagonal ones
+fn touched_sections(
+    chunk_pos: ChunkPos,
+    block_pos: BlockPos
+) -> impl Iterator<Item = SectionPos> {
+    let section_pos = chunk_pos.section((block_pos.y / SECTION_SIZE) as i32);
+    let side = |coord: usize| match coord % SECTION_SIZE {
+        0 => -1,
+        local if local == SECTION_SIZE - 1 => 1,
+        _ => 0,
+    };
+    let sides = [side(block_pos.x), side(block_pos.y), side(block_pos.z)];
+    std::iter::once(section_pos).chain(
+        section_neighbors()
+            .filter(move |(dx, dy, dz)| {
+                [*dx, *dy, *dz].iter()
+                    .zip(sides)
+                    .all(|(offset, side)| *offset == 0 || *offset == side)
+            })
+            .map(move |(dx, dy, dz)| section_pos.offset(dx, dy, dz))
+    )
+}
+
+pub struct World {
+    registry: Arc<BlockRegistry>,
+    generator: Arc<dyn WorldGenerator>,
+
+    /// The lit columns, the ones that make the world
+    columns: HashMap<ChunkPos, ChunkColumn>,
+
+    /// Columns still going through the stages before the light
+    generating: HashMap<ChunkPos, ChunkColumn>,
+
+    /// Stage every column asked for got to, and the one it has to get to.
+    /// The requested columns go up to meshed, the ones around them as far
+    /// as those need
+    statuses: HashMap<ChunkPos, ChunkStatus>,
+    targets: HashMap<ChunkPos, ChunkStatus>,
+
+    /// Columns that didn't get to their target yet, in the order they were
+    /// asked for
+    queue: Vec<ChunkPos>,
+
+    /// Threads running the stages that only read their own column, without
+    /// them the stages run on the thread that generates the columns
+    workers: Option<WorkerPool<ChunkPos, StageOutput>>,
+
+    /// Stage each column sent to the workers is going through and the id of
+    /// its job, the column is out of `generating` until it comes back
+    in_flight: HashMap<ChunkPos, (ChunkStatus, u64)>,
+
+    /// Column the area of requested columns is around, `None` until the
+    /// first `set_center`
+    center: Option<ChunkPos>,
+
+    /// Radius, in columns, of the square of columns requested around the
+    /// center
+    render_distance: i32,
+
+    /// Meshed columns unloaded since the renderer last took them, their
+    /// sections have to stop being drawn
+    unloaded: Vec<ChunkPos>,
+
+    /// Columns unloaded since the world was made
+    unloaded_count: usize,
+
+    /// Sections waiting to be remeshed, in the order they changed, `dirty`
+    /// has the same ones to not queue any twice
+    dirty_queue: VecDeque<SectionPos>,
+    dirty: HashSet<SectionPos>,
+
+    /// Where the columns are saved, the world only lives in memory without it
+    storage: Option<RegionStorage>,
+
+    /// Loaded columns changed since they were loaded or saved
+    modified: HashSet<ChunkPos>,
+
+    /// Blocks of the features that went over the border of their column, for
+    /// the columns that aren't loaded yet. They only live in memory, the ones
+    /// of the columns never loaded are lost on exit
+    pending_edits: BlockEdits,
+}
+
+impl World {
+    /// Radius, in columns, of the area requested around the center when not
+    /// told otherwise
+    pub const RENDER_DISTANCE: i32 = 8;
+
+    /// A world made of the blocks of `registry`, saved to the region files of
+    /// `storage`. The columns that aren't there yet or can't be read are
+    /// made by `generator`, nothing is requested until `set_center`
+    pub fn with_storage(
+        registry: BlockRegistry,
+        generator: Box<dyn WorldGenerator>,
+        storage: Option<RegionStorage>
+    ) -> Self {
+        Self {
+            registry: Arc::new(registry),
+            generator: Arc::from(generator),
+            columns: HashMap::new(),
+            generating: HashMap::new(),
+            statuses: HashMap::new(),
+            targets: HashMap::new(),
+            queue: Vec::new(),
+            workers: None,
+            in_flight: HashMap::new(),
+            center: None,
+            render_distance: Self::RENDER_DISTANCE,
+            unloaded: Vec::new(),
+            unloaded_count: 0,
+            dirty_queue: VecDeque::new(),
+            dirty: HashSet::new(),
+            storage,
+            modified: HashSet::new(),
+            pending_edits: BlockEdits::new(),
+        }
+    }
+
+    /// Request the columns up to `distance` columns away from the center
+    pub fn with_render_distance(mut self, distance: i32) -> Self {
+        self.render_distance = distance;
+        self
+    }
+
+    /// Run the generation stages that only read their own column on
+    /// `threads` threads, the closest to the center first
+    pub fn with_workers(mut self, threads: usize) -> Self {
+        self.workers = Some(WorkerPool::new("generation", threads));
+        self
+    }
+
+    /// The block types the chunks of this world are made of
+    pub fn registry(&self) -> &Arc<BlockRegistry> {
+        &self.registry
+    }
+
+    /// What the columns that aren't saved are made by
+    pub fn generator(&self) -> &dyn WorldGenerator {
+        self.generator.as_ref()
+    }
+
+    pub fn column(&self, chunk_pos: ChunkPos) -> Option<&ChunkColumn> {
+        self.columns.get(&chunk_pos)
+    }
+
+    fn column_mut(&mut self, chunk_pos: ChunkPos) -> Option<&mut ChunkColumn> {
+        self.columns.get_mut(&chunk_pos)
+    }
+
+    /// Block at a position in the world, `None` if its column isn't loaded or
+    /// it's out of the world
+    pub fn get_block(&self, pos: WorldBlockPos) -> Option<Block> {
+        let (chunk_pos, block_pos) = pos.local()?;
+        self.column(chunk_pos)?.get_block(block_pos)
+    }
+
+    /// Replace a block at a position in the world, see `place_block`
+    pub fn set_block(&mut self, pos: WorldBlockPos, block: Block) -> Option<()> {
+        let (chunk_pos, block_pos) = pos.local()?;
+        self.place_block(chunk_pos, block_pos, block)
+    }
+
+    /// The first block that is drawn along a ray, water and plants included.
+    /// The unloaded columns are seen through
+    pub fn raycast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32
+    ) -> Option<RayHit> {
+        raycast(origin, direction, max_distance, |pos| {
+            self.get_block(pos).is_some_and(|block| self.registry.is_visible(block))
+        })
+    }
+
+    pub fn section(&self, section_pos: SectionPos) -> Option<&Section> {
+        self.column(section_pos.column())?.section(section_pos.y)
+    }
+
+    /// A loaded section with all its loaded neighbors, ready to be meshed
+    pub fn neighborhood(
+        &self,
+        section_pos: SectionPos
+    ) -> Option<ChunkNeighborhood<'_, SECTION_SIZE, SECTION_SIZE>> {
+        let column = self.column(section_pos.column())?;
+        let mut neighborhood = ChunkNeighborhood::new(column.section(section_pos.y)?)
+            .with_biomes(column.biomes());
+        for (dx, dy, dz) in section_neighbors() {
+            neighborhood = neighborhood.with_neighbor(
+                dx, dy, dz,
+                self.section(section_pos.offset(dx, dy, dz))
+            );
+        }
+
+        Some(neighborhood)
+    }
+
+    /// Replace a block of a loaded column, `block_pos.y` goes over the whole
+    /// height of the column. The section, the neighbors that touch that
+    /// block and the ones whose light changed will have to be remeshed
+    pub fn place_block(
+        &mut self,
+        chunk_pos: ChunkPos,
+        block_pos: BlockPos,
+        block: Block
+    ) -> Option<()> {
+        let registry = self.registry.clone();
+        let column = self.column_mut(chunk_pos)?;
+        let old = column.get_block(block_pos)?;
+        column.place_block(block_pos, block)?;
+        let top = column.height(block_pos.x, block_pos.z).max(block_pos.y + 1);
+        column.update_height(block_pos.x, block_pos.z, top, |block| registry.is_full_cube(block));
+        self.modified.insert(chunk_pos);
+
+        // Even if it's empty now, so its old mesh gets removed
+        self.mark_dirty(chunk_pos.section((block_pos.y / SECTION_SIZE) as i32));
+        for section_pos in touched_sections(chunk_pos, block_pos) {
+            self.schedule_update(section_pos);
+        }
+
+        let mut changed: Vec<SectionPos> = self.relight_block(chunk_pos.block(block_pos), old)
+            .into_iter()
+            .collect();
+        changed.sort_by_key(|pos| (pos.y, pos.z, pos.x));
+        for section_pos in changed {
+            self.schedule_update(section_pos);
+        }
+
+        Some(())
+    }
+
+    /// Queue a section of a meshed column to be remeshed, the rest are
+    /// meshed whole once the columns around them are ready
+    pub fn mark_dirty(&mut self, section_pos: SectionPos) {
+        if self.status(section_pos.column()) != Some(ChunkStatus::Meshed) {
+            return;
+        }
+        if self.dirty.insert(section_pos) {
+            self.dirty_queue.push_back(section_pos);
+        }
+    }
+
+    /// Queue a loaded section to be remeshed, the empty ones have nothing to
+    /// mesh
+    fn schedule_update(&mut self, section_pos: SectionPos) {
+        if self.section(section_pos).is_some_and(|section| !section.is_empty()) {
+            self.mark_dirty(section_pos);
+        }
+    }
+
+    /// Take up to `budget` of the sections changed since they were last
+    /// taken, the oldest changes first. The rest stay queued for the next
+    /// calls
+    pub fn dirty_sections(&mut self, budget: usize) -> Vec<SectionPos> {
+        let count = budget.min(self.dirty_queue.len());
+        let sections: Vec<SectionPos> = self.dirty_queue.drain(..count).collect();
+        for section_pos in &sections {
+            self.dirty.remove(section_pos);
+        }
+
+        sections
+    }
+
+    /// Write the modified columns to the storage, with the blocks of the
+    /// features waiting for the columns that aren't decorated yet
+    pub fn save(&mut self) -> Result<()> {
+        let Some(storage) = self.storage.as_mut() else {
+            return Ok(());
+        };
+        // The decorated columns that aren't lit yet are complete too, their
+        // features may be in the lit ones already
+        let decorated = self.generating.values()
+            .filter(|column| self.statuses.get(&column.pos()) == Some(&ChunkStatus::Decorated));
+        let modified = self.columns.values()
+            .chain(decorated)
+            .filter(|column| self.modified.contains(&column.pos()));
+        storage.save_columns(modified, &self.registry)?;
+        storage.save_edits(&self.pending_edits, &self.registry)?;
+        self.modified.clear();
+
+        Ok(())
+    }
+
+    /// Remove a column from the world whatever its status, saving it first
+    /// if it was modified. It has to be asked for again to come back
+    pub fn unload_column(&mut self, chunk_pos: ChunkPos) -> Result<()> {
+        self.unload_columns([chunk_pos])
+    }
+
+    /// Remove columns like `unload_column`, the modified ones are saved
+    /// together so each region file is written once
+    pub fn unload_columns(&mut self, positions: impl IntoIterator<Item = ChunkPos>) -> Result<()> {
+        let modified: Vec<ChunkColumn> = positions.into_iter()
+            .filter_map(|chunk_pos| self.remove_column(chunk_pos))
+            .collect();
+        if let (Some(storage), false) = (self.storage.as_mut(), modified.is_empty()) {
+            storage.save_columns(&modified, &self.registry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Forget everything about a column, returns it if it was modified and
+    /// has to be saved
+    fn remove_column(&mut self, chunk_pos: ChunkPos) -> Option<ChunkColumn> {
+        let status = self.statuses.remove(&chunk_pos)?;
+        self.targets.remove(&chunk_pos);
+        self.queue.retain(|pos| *pos != chunk_pos);
+        self.dirty_queue.retain(|pos| pos.column() != chunk_pos);
+        self.dirty.retain(|pos| pos.column() != chunk_pos);
+        if self.in_flight.remove(&chunk_pos).is_some() {
+            if let Some(workers) = self.workers.as_ref() {
+                workers.cancel(&chunk_pos);
+            }
+        }
+        let column = self.columns.remove(&chunk_pos)
+            .or_else(|| self.generating.remove(&chunk_pos));
+        if status == ChunkStatus::Meshed {
+            self.unloaded.push(chunk_pos);
+        }
+        self.unloaded_count += 1;
+
+        // Only the decorated columns can be modified, the ones before get
+        // the edits that reach them once they are
+        let modified = self.modified.remove(&chunk_pos);
+        column.filter(|_| modified)
+    }
+
+    /// Take the meshed columns unloaded since the last call
+    pub fn unloaded_columns(&mut self) -> Vec<ChunkPos> {
+        std::mem::take(&mut self.unloaded)
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &Section> + '_ {
+        self.columns.values().flat_map(|column| column.sections())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+    use crate::chunk::Face;
+    use crate::region::tests::test_dir;
+
+    pub fn empty_world() -> World {
+        World {
+            registry: Arc::new(BlockRegistry::with_default_blocks()),
+            generator: Arc::new(generator::VoidGenerator),
+            columns: HashMap::new(),
+            generating: HashMap::new(),
+            statuses: HashMap::new(),
+            targets: HashMap::new(),
+            queue: Vec::new(),
+            workers: None,
+            in_flight: HashMap::new(),
+            center: None,
+            render_distance: World::RENDER_DISTANCE,
+            unloaded: Vec::new(),
+            unloaded_count: 0,
+            dirty_queue: VecDeque::new(),
+            dirty: HashSet::new(),
+            storage: None,
+            modified: HashSet::new(),
+            pending_edits: BlockEdits::new(),
+        }
+    }
+
+    /// Add a column made by hand, to be lit and meshed
+    pub fn insert_column(world: &mut World, column: ChunkColumn) {
+        let chunk_pos = column.pos();
+        world.add_decorated_column(column);
+        world.request_column(chunk_pos);
+    }
+
+    /// A column with a stone floor on its first section
+    pub fn floor_column(registry: &BlockRegistry, chunk_pos: ChunkPos) -> ChunkColumn {
+        let stone = registry.by_name("stone").unwrap();
+        let mut column = ChunkColumn::new(chunk_pos);
+        for x in 0..16 {
+            for z in 0..16 {
+                column.place_block(BlockPos::new(x, 0, z), stone);
+            }
+        }
+        column
+    }
+
+    #[test]
+    fn border_changes_update_neighbors() {
+        let mut world = empty_world();
+        let dirt = world.registry().by_name("dirt").unwrap();
+        for (x, z) in [(0, 0), (1, 0), (2, 0), (2, 1)] {
+            let column = floor_column(world.registry(), ChunkPos::new(x, z));
+            insert_column(&mut world, column);
+        }
+        // Only the floor sections have something to render
+        world.generate_columns(usize::MAX);
+        assert_eq!(
+            world.dirty_sections(usize::MAX),
+            vec![
+                SectionPos::new(0, 0, 0),
+                SectionPos::new(1, 0, 0),
+                SectionPos::new(2, 0, 0),
+                SectionPos::new(2, 0, 1)
+            ]
+        );
+        assert_eq!(world.dirty_sections(usize::MAX), vec![]);
+
+        world.place_block(ChunkPos::new(1, 0), BlockPos::new(5, 3, 5), dirt);
+        assert_eq!(world.dirty_sections(usize::MAX), vec![SectionPos::new(1, 0, 0)]);
+
+        world.place_block(ChunkPos::new(1, 0), BlockPos::new(15, 3, 7), dirt);
+        assert_eq!(
+            world.dirty_sections(usize::MAX),
+            vec![SectionPos::new(1, 0, 0), SectionPos::new(2, 0, 0)]
+        );
+
+        // Corners also touch the diagonal section
+        world.place_block(ChunkPos::new(1, 0), BlockPos::new(15, 3, 15), dirt);
+        assert_eq!(
+            world.dirty_sections(usize::MAX),
+            vec![
+                SectionPos::new(1, 0, 0),
+                SectionPos::new(2, 0, 0),
+                SectionPos::new(2, 0, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn dirty_sections_budget() {
+        let mut world = empty_world();
+        let dirt = world.registry().by_name("dirt").unwrap();
+        let column = floor_column(world.registry(), ChunkPos::new(0, 0));
+        insert_column(&mut world, column);
+        world.generate_columns(usize::MAX);
+        world.dirty_sections(usize::MAX);
+
+        for y in [20, 40, 60] {
+            world.place_block(ChunkPos::new(0, 0), BlockPos::new(5, y, 5), dirt);
+        }
+        // Changed again before being taken, it keeps its place. The shadow of
+        // the first block reaches the floor
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(6, 20, 5), dirt);
+        assert_eq!(
+            world.dirty_sections(2),
+            vec![SectionPos::new(0, 1, 0), SectionPos::new(0, 0, 0)]
+        );
+        assert_eq!(
+            world.dirty_sections(2),
+            vec![SectionPos::new(0, 2, 0), SectionPos::new(0, 3, 0)]
+        );
+        assert_eq!(world.dirty_sections(2), vec![]);
+    }
+
+    #[test]
+    fn vertical_borders_update_sections() {
+        let mut world = empty_world();
+        let dirt = world.registry().by_name("dirt").unwrap();
+        let column = floor_column(world.registry(), ChunkPos::new(0, 0));
+        insert_column(&mut world, column);
+        world.generate_columns(usize::MAX);
+        world.dirty_sections(usize::MAX);
+
+        // The first block of a new section, the one below has to hide the
+        // face it had looking up
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(5, 16, 5), dirt);
+        assert_eq!(
+            world.dirty_sections(usize::MAX),
+            vec![SectionPos::new(0, 1, 0), SectionPos::new(0, 0, 0)]
+        );
+        assert!(!world.section(SectionPos::new(0, 1, 0)).unwrap().is_empty());
+
+        // Empty sections are left alone, the shadow it casts reaches the
+        // block placed before
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(5, 208, 5), dirt);
+        assert_eq!(
+            world.dirty_sections(usize::MAX),
+            vec![SectionPos::new(0, 13, 0), SectionPos::new(0, 1, 0)]
+        );
+        assert_eq!(world.place_block(ChunkPos::new(0, 0), BlockPos::new(0, 256, 0), dirt), None);
+    }
+
+    #[test]
+    fn raycast_across_columns() {
+        let mut world = empty_world();
+        for x in -2..=0 {
+            let column = floor_column(world.registry(), ChunkPos::new(x, -1));
+            insert_column(&mut world, column);
+        }
+        world.generate_columns(usize::MAX);
+        let stone = world.registry().by_name("stone").unwrap();
+        world.place_block(ChunkPos::new(-2, -1), BlockPos::new(15, 1, 3), stone);
+
+        // Looking down at the floor
+        let hit = world.raycast(
+            Point3::new(-20.0, 10.0, -3.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            20.0
+        ).unwrap();
+        assert_eq!((hit.pos, hit.face, hit.distance), (WorldBlockPos::new(-20, 0, -3), Face::Up, 9.5));
+
+        // Along the floor from a column to the other, going over the block
+        // in the way
+        let hit = world.raycast(
+            Point3::new(4.0, 1.0, -13.0),
+            Vector3::new(-1.0, 0.0, 0.0),
+            30.0
+        ).unwrap();
+        assert_eq!((hit.pos, hit.face), (WorldBlockPos::new(-17, 1, -13), Face::Right));
+        assert!(world.raycast(
+            Point3::new(4.0, 2.0, -13.0),
+            Vector3::new(-1.0, 0.0, 0.0),
+            30.0
+        ).is_none());
+    }
+
+    #[test]
+    fn raycast_hits_water_and_plants() {
+        let mut world = empty_world();
+        let column = floor_column(world.registry(), ChunkPos::new(0, 0));
+        insert_column(&mut world, column);
+        world.generate_columns(usize::MAX);
+        let water = world.registry().by_name("water").unwrap();
+        let flower = world.registry().by_name("flower").unwrap();
+        for y in 1..4 {
+            world.place_block(ChunkPos::new(0, 0), BlockPos::new(5, y, 5), water);
+        }
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(5, 4, 5), flower);
+
+        // The flower on top is the first, then the water under it
+        let down = |world: &World| world.raycast(
+            Point3::new(5.0, 10.0, 5.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            20.0
+        ).unwrap();
+        let hit = down(&world);
+        assert_eq!((hit.pos, hit.face), (WorldBlockPos::new(5, 4, 5), Face::Up));
+        world.set_block(hit.pos, Block::AIR).unwrap();
+        let hit = down(&world);
+        assert_eq!((hit.pos, hit.face), (WorldBlockPos::new(5, 3, 5), Face::Up));
+    }
+
+    #[test]
+    fn breaking_the_targeted_block() {
+        let mut world = empty_world();
+        let column = floor_column(world.registry(), ChunkPos::new(-1, -1));
+        insert_column(&mut world, column);
+        world.generate_columns(usize::MAX);
+
+        let hit = world.raycast(
+            Point3::new(-16.0, 5.0, -1.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            8.0
+        ).unwrap();
+        assert_eq!(hit.pos, WorldBlockPos::new(-16, 0, -1));
+        world.set_block(hit.pos, Block::AIR).unwrap();
+        assert_eq!(world.get_block(WorldBlockPos::new(-16, 0, -1)), Some(Block::AIR));
+        assert_eq!(world.dirty_sections(usize::MAX), vec![SectionPos::new(-1, 0, -1)]);
+    }
+
+    #[test]
+    fn world_coordinates() {
+        let mut world = empty_world();
+        for (x, z) in [(-1, -1), (0, -1), (-1, 0), (0, 0)] {
+            let column = floor_column(world.registry(), ChunkPos::new(x, z));
+            insert_column(&mut world, column);
+        }
+        world.generate_columns(usize::MAX);
+        let dirt = world.registry().by_name("dirt").unwrap();
+
+        // The blocks around the origin are each on a different column
+        let cases = [
+            (WorldBlockPos::new(-1, 20, -1), ChunkPos::new(-1, -1), BlockPos::new(15, 20, 15)),
+            (WorldBlockPos::new(0, 20, -1), ChunkPos::new(0, -1), BlockPos::new(0, 20, 15)),
+            (WorldBlockPos::new(-1, 20, 0), ChunkPos::new(-1, 0), BlockPos::new(15, 20, 0)),
+            (WorldBlockPos::new(15, 20, 15), ChunkPos::new(0, 0), BlockPos::new(15, 20, 15)),
+            (WorldBlockPos::new(-16, 20, -16), ChunkPos::new(-1, -1), BlockPos::new(0, 20, 0)),
+        ];
+        for (pos, chunk_pos, block_pos) in cases {
+            assert_eq!(world.get_block(pos), Some(Block::AIR));
+            world.set_block(pos, dirt).unwrap();
+            assert_eq!(world.get_block(pos), Some(dirt));
+            assert_eq!(
+                world.column(chunk_pos).unwrap().get_block(block_pos),
+                Some(dirt)
+            );
+        }
+
+        // Out of the world or of the loaded columns
+        assert_eq!(world.get_block(WorldBlockPos::new(0, -1, 0)), None);
+        assert_eq!(world.get_block(WorldBlockPos::new(0, 256, 0)), None);
+        assert_eq!(world.get_block(WorldBlockPos::new(-33, 0, 0)), None);
+        assert_eq!(world.set_block(WorldBlockPos::new(32, 0, 0), dirt), None);
+        assert_eq!(world.set_block(WorldBlockPos::new(0, -1, 0), dirt), None);
+    }
+
+    #[test]
+    fn modified_columns_are_saved() {
+        let dir = test_dir("world");
+        let world_with_storage = || {
+            let registry = BlockRegistry::with_default_blocks();
+            let generator = generator::from_name("flat", 1, &registry, Vec::new()).unwrap();
+            World::with_storage(registry, generator, Some(RegionStorage::new(&dir)))
+        };
+        let mut world = world_with_storage();
+        world.set_center(ChunkPos::new(0, 0));
+        world.generate_columns(usize::MAX);
+        let stone = world.registry().by_name("stone").unwrap();
+
+        // Nothing changed, nothing to write
+        world.save().unwrap();
+        assert!(!dir.exists());
+
+        world.place_block(ChunkPos::new(0, 0), BlockPos::new(3, 100, 3), stone);
+        world.place_block(ChunkPos::new(-1, 2), BlockPos::new(1, 0, 0), Block::AIR);
+        world.save().unwrap();
+        world.place_block(ChunkPos::new(1, 1), BlockPos::new(8, 200, 8), stone);
+        world.unload_column(ChunkPos::new(1, 1)).unwrap();
+        assert!(world.column(ChunkPos::new(1, 1)).is_none());
+
+        let mut world = world_with_storage();
+        world.set_center(ChunkPos::new(0, 0));
+        world.generate_columns(usize::MAX);
+        let block = |world: &World, chunk_pos, block_pos| {
+            world.column(chunk_pos).unwrap().get_block(block_pos).unwrap()
+        };
+        assert_eq!(block(&world, ChunkPos::new(0, 0), BlockPos::new(3, 100, 3)), stone);
+        assert_eq!(block(&world, ChunkPos::new(-1, 2), BlockPos::new(1, 0, 0)), Block::AIR);
+        assert_eq!(block(&world, ChunkPos::new(1, 1), BlockPos::new(8, 200, 8)), stone);
+        // The rest is generated as always
+        let generated = world.generator().generate(ChunkPos::new(-1, 2));
+        assert_eq!(
+            block(&world, ChunkPos::new(-1, 2), BlockPos::new(2, 0, 0)),
+            generated.get_block(BlockPos::new(2, 0, 0)).unwrap()
+        );
+        assert_ne!(generated.get_block(BlockPos::new(1, 0, 0)), Some(Block::AIR));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn columns_leaving_the_area_are_saved() {
+        let dir = test_dir("leaving");
+        let world_with_storage = || {
+            let registry = BlockRegistry::with_default_blocks();
+            let generator = generator::from_name("flat", 1, &registry, Vec::new()).unwrap();
+            World::with_storage(registry, generator, Some(RegionStorage::new(&dir)))
+                .with_render_distance(1)
+        };
+        let mut world = world_with_storage();
+        world.set_center(ChunkPos::new(0, 0));
+        world.generate_columns(usize::MAX);
+        let stone = world.registry().by_name("stone").unwrap();
+        let changed = [ChunkPos::new(-1, 0), ChunkPos::new(0, 0), ChunkPos::new(1, 1)];
+        for chunk_pos in changed {
+            world.place_block(chunk_pos, BlockPos::new(2, 100, 2), stone);
+        }
+
+        // All of them are written in the same move, without `save`
+        world.set_center(ChunkPos::new(40, 0));
+        assert!(changed.iter().all(|chunk_pos| world.status(*chunk_pos).is_none()));
+        let mut storage = RegionStorage::new(&dir);
+        for chunk_pos in changed {
+            let column = storage.load_column(chunk_pos, world.registry()).unwrap().unwrap();
+            assert_eq!(column.get_block(BlockPos::new(2, 100, 2)), Some(stone));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Columns of air with a block on their first corner and another on the
+    /// corner of the column before them on `x`
+    struct SpillGenerator(Block);
+
+    impl WorldGenerator for SpillGenerator {
+        fn generate(&self, chunk_pos: ChunkPos) -> ChunkColumn {
+            ChunkColumn::new(chunk_pos)
+        }
+
+        fn decorate(&self, column: &ChunkColumn, edits: &mut BlockEdits) {
+            let corner = column.pos().block(BlockPos::new(0, 70, 0));
+            edits.set(corner, self.0, false);
+            edits.set(WorldBlockPos::new(corner.x - 1, corner.y, corner.z), self.0, false);
+        }
+    }
+
+    #[test]
+    fn waiting_features_are_saved() {
+        let dir = test_dir("waiting");
+        let world_with_storage = || {
+            let registry = BlockRegistry::with_default_blocks();
+            let stone = registry.by_name("stone").unwrap();
+            let storage = Some(RegionStorage::new(&dir));
+            World::with_storage(registry, Box::new(SpillGenerator(stone)), storage)
+                .with_render_distance(0)
+        };
+        let mut world = world_with_storage();
+        world.set_center(ChunkPos::new(0, 0));
+        world.generate_columns(usize::MAX);
+        let stone = world.registry().by_name("stone").unwrap();
+
+        // The column before the last decorated one isn't there yet, its
+        // block waits for it when the world is closed
+        assert_eq!(world.status(ChunkPos::new(-2, 0)), Some(ChunkStatus::Decorated));
+        assert_eq!(world.status(ChunkPos::new(-3, 0)), None);
+        world.save().unwrap();
+
+        // The decorated one is read whole and the feature still gets to the
+        // one made after it
+        let mut world = world_with_storage();
+        world.set_center(ChunkPos::new(0, 0));
+        world.request_column(ChunkPos::new(-3, 0));
+        world.generate_columns(usize::MAX);
+        assert_eq!(world.get_block(WorldBlockPos::new(-3 * 16 + 15, 70, 0)), Some(stone));
+        assert!(!world.pending_edits.columns().any(|pos| pos == ChunkPos::new(-3, 0)));
+
+        // Once delivered they aren't stored anymore, the ones of the new
+        // last column are
+        world.save().unwrap();
+        assert_eq!(world.status(ChunkPos::new(-5, 0)), Some(ChunkStatus::Decorated));
+        let mut storage = RegionStorage::new(&dir);
+        assert!(storage.take_edits(ChunkPos::new(-3, 0), world.registry()).unwrap().is_empty());
+        assert!(!storage.take_edits(ChunkPos::new(-6, 0), world.registry()).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn features_cross_borders() {
+        let registry = BlockRegistry::with_default_blocks();
+        let stone = registry.by_name("stone").unwrap();
+        let mut world = World::with_storage(registry, Box::new(SpillGenerator(stone)), None);
+        world.set_center(ChunkPos::new(0, 0));
+        world.generate_columns(usize::MAX);
+        let block = |world: &World, x, z| {
+            world.get_block(WorldBlockPos::new(x, 70, z)).unwrap()
+        };
+
+        // The requested columns are meshed, the ones around are lit and the
+        // next ones decorated so the lit ones have all their features. Each
+        // gets its own block and the one of the column after it, whichever
+        // was generated first
+        for chunk_x in -9..=9i64 {
+            for chunk_z in -9..=9i64 {
+                let chunk_pos = ChunkPos::new(chunk_x as i32, chunk_z as i32);
+                let inside = chunk_x.abs() <= 8 && chunk_z.abs() <= 8;
+                let status = if inside { ChunkStatus::Meshed } else { ChunkStatus::Lit };
+                assert_eq!(world.status(chunk_pos), Some(status));
+                assert_eq!(block(&world, chunk_x * 16, chunk_z * 16), stone);
+                assert_eq!(block(&world, chunk_x * 16 + 15, chunk_z * 16), stone);
+            }
+        }
+        assert_eq!(world.status(ChunkPos::new(10, -10)), Some(ChunkStatus::Decorated));
+        assert_eq!(world.status(ChunkPos::new(10, -11)), None);
+
+        // The blocks of the last ones wait for the columns they fall on
+        let mut pending: Vec<_> = world.pending_edits.columns().collect();
+        pending.sort_by_key(|pos| pos.z);
+        assert_eq!(pending, (-10..=10).map(|z| ChunkPos::new(-11, z)).collect::<Vec<_>>());
+        assert!(world.modified.contains(&ChunkPos::new(3, -2)));
+
+        // The ones that reach a meshed column are placed right away
+        world.dirty_sections(usize::MAX);
+        let mut edits = BlockEdits::new();
+        edits.set(WorldBlockPos::new(7 * 16 + 15, 70, 0), Block::AIR, true);
+        world.add_edits(edits);
+        assert_eq!(block(&world, 7 * 16 + 15, 0), Block::AIR);
+        assert_eq!(
+            world.dirty_sections(usize::MAX),
+            vec![
+                SectionPos::new(7, 4, 0), SectionPos::new(7, 4, -1),
+                SectionPos::new(8, 4, -1), SectionPos::new(8, 4, 0)
+            ]
+        );
+
+        // And on the others when they are made
+        world.request_column(ChunkPos::new(-11, 0));
+        world.generate_columns(usize::MAX);
+        assert_eq!(block(&world, -11 * 16 + 15, 0), stone);
+        assert!(!world.pending_edits.columns().any(|pos| pos == ChunkPos::new(-11, 0)));
+        assert!(world.pending_edits.columns().any(|pos| pos == ChunkPos::new(-14, 0)));
+    }
+}
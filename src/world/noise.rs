@@ -0,0 +1,238 @@
+/// Scramble the bits of a number, a few of these turn a lattice point into
+/// pseudo random bits (splitmix64 finalizer)
+fn mix(mut bits: u64) -> u64 {
+    bits = (bits ^ (bits >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    bits = (bits ^ (bits >> 27)).wrapping_mul(0x94d049bb133111eb);
+    bits ^ (bits >> 31)
+}
+
+/// Pseudo random bits of a lattice point, always the same for the same seed
+/// and point
+pub fn hash(seed: u64, point: [i64; 3]) -> u64 {
+    point.iter().fold(mix(seed ^ 0x9e3779b97f4a7c15), |bits, coord| {
+        mix(bits ^ (*coord as u64).wrapping_mul(0x9e3779b97f4a7c15))
+    })
+}
+
+/// Smooth step with zero first and second derivatives on 0 and 1, so the
+/// lattice can't be seen
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Dot product of the offset to a lattice point with the pseudo random
+/// gradient of that point, one of the 8 directions of the square
+fn gradient2(seed: u64, point: [i64; 2], x: f64, z: f64) -> f64 {
+    match hash(seed, [point[0], 0, point[1]]) & 7 {
+        0 => x + z,
+        1 => x - z,
+        2 => -x + z,
+        3 => -x - z,
+        4 => x,
+        5 => -x,
+        6 => z,
+        _ => -z,
+    }
+}
+
+/// Same with one of the 12 directions to the edges of the cube
+fn gradient3(seed: u64, point: [i64; 3], x: f64, y: f64, z: f64) -> f64 {
+    match hash(seed, point) % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+/// Gradient noise on a plane, smooth and roughly between -1 and 1 with
+/// features about a unit apart
+pub fn noise2(seed: u64, x: f64, z: f64) -> f64 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (fx, fz) = (x - x0, z - z0);
+    let (x0, z0) = (x0 as i64, z0 as i64);
+    let corner = |dx: i64, dz: i64| {
+        gradient2(seed, [x0 + dx, z0 + dz], fx - dx as f64, fz - dz as f64)
+    };
+
+    let (u, v) = (fade(fx), fade(fz));
+    let value = lerp(
+        v,
+        lerp(u, corner(0, 0), corner(1, 0)),
+        lerp(u, corner(0, 1), corner(1, 1)),
+    );
+
+    // The diagonal gradients reach a bit further than 1 unscaled
+    value * std::f64::consts::FRAC_1_SQRT_2 * 2.0
+}
+
+/// Gradient noise on a volume, see `noise2`
+pub fn noise3(seed: u64, x: f64, y: f64, z: f64) -> f64 {
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+    let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+    let corner = |dx: i64, dy: i64, dz: i64| {
+        gradient3(
+            seed,
+            [x0 + dx, y0 + dy, z0 + dz],
+            fx - dx as f64, fy - dy as f64, fz - dz as f64
+        )
+    };
+
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+            lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+        ),
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+            lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+        ),
+    )
+}
+
+/// Sequence of pseudo random numbers, the same seed always gives the same
+/// sequence
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        mix(self.state)
+    }
+
+    /// From 0 to 1, 1 excluded
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// From `min` to `max`, `max` excluded
+    pub fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + self.next_f64() * (max - min)
+    }
+}
+
+/// Layers of noise, each octave with twice the frequency and `persistence`
+/// times the amplitude of the previous one, for detail at every scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fractal {
+    pub octaves: u32,
+
+    /// Of the first octave, in features per block
+    pub frequency: f64,
+
+    pub persistence: f64,
+}
+
+impl Fractal {
+    /// Add the octaves up, each with its own seed, scaled back to about -1
+    /// to 1
+    fn sum(&self, mut octave: impl FnMut(u64, f64) -> f64) -> f64 {
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut value = 0.0;
+        for i in 0..self.octaves {
+            let frequency = self.frequency * (1u64 << i) as f64;
+            value += octave(i as u64, frequency) * amplitude;
+            total += amplitude;
+            amplitude *= self.persistence;
+        }
+
+        value / total
+    }
+
+    pub fn sample2(&self, seed: u64, x: f64, z: f64) -> f64 {
+        self.sum(|octave, frequency| {
+            noise2(seed.wrapping_add(octave), x * frequency, z * frequency)
+        })
+    }
+
+    pub fn sample3(&self, seed: u64, x: f64, y: f64, z: f64) -> f64 {
+        self.sum(|octave, frequency| {
+            noise3(seed.wrapping_add(octave), x * frequency, y * frequency, z * frequency)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use super::*;
+
+    #[test]
+    fn noise_is_smooth_and_seeded() {
+        // Zero on the lattice points
+        assert_eq!(noise2(7, 3.0, -5.0), 0.0);
+        assert_eq!(noise3(7, 3.0, 4.0, -5.0), 0.0);
+
+        let samples = |seed| {
+            (0..200)
+                .map(|i| noise2(seed, i as f64 * 0.37, i as f64 * 0.11 - 20.0))
+                .collect::<Vec<_>>()
+        };
+        let values = samples(7);
+        assert_eq!(values, samples(7));
+        assert_ne!(values, samples(8));
+        assert!(values.iter().all(|value| (-1.0..=1.0).contains(value)));
+        assert!(values.iter().any(|value| value.abs() > 0.2));
+
+        // Close points have close values
+        for i in 0..100 {
+            let (x, y, z) = (i as f64 * 0.173, i as f64 * -0.291, i as f64 * 0.057);
+            let step = 0.01;
+            assert!((noise3(1, x, y, z) - noise3(1, x + step, y, z)).abs() < 0.05);
+            assert!((noise2(1, x, z) - noise2(1, x, z + step)).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn random_sequences() {
+        let numbers = |seed| {
+            let mut random = Random::new(seed);
+            (0..100).map(|_| random.next_f64()).collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(3), numbers(3));
+        assert_ne!(numbers(3), numbers(4));
+        assert!(numbers(3).iter().all(|number| (0.0..1.0).contains(number)));
+        assert_ne!(hash(1, [2, 3, 4]), hash(1, [2, 3, 5]));
+    }
+
+    #[test]
+    fn fractal_range() {
+        let fractal = Fractal {
+            octaves: 4,
+            frequency: 1.0 / 32.0,
+            persistence: 0.5,
+        };
+        for i in 0..500 {
+            let (x, y, z) = (i as f64 * 3.7, i as f64 * 0.9, i as f64 * -5.3);
+            assert!((-1.0..=1.0).contains(&fractal.sample2(3, x, z)));
+            assert!((-1.0..=1.0).contains(&fractal.sample3(3, x, y, z)));
+        }
+    }
+}
@@ -0,0 +1,683 @@
+use crate::chunk::{ChunkColumn, ChunkPos, SectionPos};
+
+use super::{BlockEdits, World, WorldGenerator, NEIGHBORS};
+
+/// How far a column went through the generation, in order. A column can only
+/// move to the next status once the columns around it got to the one the
+/// stage needs, see `neighbors_needed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChunkStatus {
+    /// Asked for, nothing made yet
+    Empty,
+
+    /// The shape of the ground, or read whole from the storage
+    Terrain,
+
+    /// With the caves dug out
+    Carved,
+
+    /// With its own features placed and the ones of the columns around so
+    /// far, the rest are written to it as they come
+    Decorated,
+
+    /// Part of the world with its light spread, its blocks won't change
+    /// anymore but by hand
+    Lit,
+
+    /// Handed to the renderer, its sections are remeshed when they change
+    Meshed,
+}
+
+impl ChunkStatus {
+    pub const ALL: [ChunkStatus; 6] = [
+        ChunkStatus::Empty,
+        ChunkStatus::Terrain,
+        ChunkStatus::Carved,
+        ChunkStatus::Decorated,
+        ChunkStatus::Lit,
+        ChunkStatus::Meshed,
+    ];
+
+    pub fn next(self) -> Option<Self> {
+        Self::ALL.get(self as usize + 1).copied()
+    }
+
+    /// Status the 8 columns around must have to move a column to this one.
+    /// The light needs the features of the columns around, the only ones
+    /// that write to it, and the meshes the light and blocks of the columns
+    /// around. The rest of the stages only read their own column
+    pub fn neighbors_needed(self) -> Option<Self> {
+        match self {
+            ChunkStatus::Lit => Some(ChunkStatus::Decorated),
+            ChunkStatus::Meshed => Some(ChunkStatus::Lit),
+            _ => None,
+        }
+    }
+}
+
+/// How many columns are in each state of loading, see `World::counters`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoadCounters {
+    /// Lit columns, the ones that make the world
+    pub loaded: usize,
+
+    /// Columns that didn't get to the status they were asked for yet
+    pub pending: usize,
+
+    /// Columns unloaded since the world was made
+    pub unloaded: usize,
+}
+
+/// A column taken through a stage that only reads itself, with the blocks
+/// its features wrote to the columns around
+pub(super) struct StageOutput {
+    column: ChunkColumn,
+    edits: BlockEdits,
+}
+
+/// What `advance` did with a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Progress {
+    /// Its neighbors or the workers aren't done with it
+    Waiting,
+
+    /// Its next stage went to the workers
+    Sent,
+
+    /// Its next stage is done
+    Done,
+}
+
+/// Run a stage that only reads its own column, `column` is the one the last
+/// stage left, `None` before the terrain
+fn run_stage(
+    generator: &dyn WorldGenerator,
+    chunk_pos: ChunkPos,
+    status: ChunkStatus,
+    column: Option<ChunkColumn>
+) -> StageOutput {
+    let mut edits = BlockEdits::new();
+    let column = match (status, column) {
+        (ChunkStatus::Terrain, _) => generator.generate(chunk_pos),
+        (ChunkStatus::Carved, Some(mut column)) => {
+            generator.carve(&mut column);
+            column
+        }
+        (ChunkStatus::Decorated, Some(mut column)) => {
+            generator.decorate(&column, &mut edits);
+            for edit in edits.take(chunk_pos) {
+                edit.apply(&mut column);
+            }
+            column.compact();
+            column
+        }
+        (status, _) => unreachable!("{:?} isn't a stage of a column alone", status),
+    };
+
+    StageOutput {
+        column,
+        edits,
+    }
+}
+
+impl World {
+    /// Rings of columns the requested ones need around them, lit and then
+    /// decorated
+    const SUPPORT_RINGS: i32 = 2;
+
+    /// Columns past the ones needed around the area that are kept loaded,
+    /// so moving back and forth over a border doesn't load and unload the
+    /// same columns
+    const UNLOAD_MARGIN: i32 = 2;
+
+    /// Status of a column, `None` if it wasn't asked for
+    pub fn status(&self, chunk_pos: ChunkPos) -> Option<ChunkStatus> {
+        self.statuses.get(&chunk_pos).copied()
+    }
+
+    /// Every column asked for and its status
+    pub fn statuses(&self) -> impl Iterator<Item = (ChunkPos, ChunkStatus)> + '_ {
+        self.statuses.iter().map(|(chunk_pos, status)| (*chunk_pos, *status))
+    }
+
+    /// Ask for a column to be made and meshed, the columns around it are made
+    /// as far as it needs. Nothing happens until `generate_columns`
+    pub fn request_column(&mut self, chunk_pos: ChunkPos) {
+        self.raise_target(chunk_pos, ChunkStatus::Meshed);
+    }
+
+    /// Make sure a column gets to at least `status`
+    fn raise_target(&mut self, chunk_pos: ChunkPos, status: ChunkStatus) {
+        let target = self.targets.entry(chunk_pos).or_insert(status);
+        *target = status.max(*target);
+        let current = *self.statuses.entry(chunk_pos).or_insert(ChunkStatus::Empty);
+        if current < status && !self.queue.contains(&chunk_pos) {
+            self.queue.push(chunk_pos);
+        }
+    }
+
+    /// Move the columns whose neighbors are ready through the next stage,
+    /// going over them in the order they were asked for until nothing else
+    /// can be done or `budget` stages are done. With workers the stages of a
+    /// column alone only count once they come back, they're sent without
+    /// limit. Returns how many were done
+    pub fn generate_columns(&mut self, budget: usize) -> usize {
+        let mut done = self.receive_stages();
+        loop {
+            let mut progress = false;
+            let queue = self.queue.clone();
+            for chunk_pos in queue.iter().copied() {
+                if done >= budget {
+                    break;
+                }
+                match self.advance(chunk_pos) {
+                    Progress::Waiting => {}
+                    Progress::Sent => progress = true,
+                    Progress::Done => {
+                        progress = true;
+                        done += 1;
+                    }
+                }
+            }
+
+            // The columns asked for by the ones that weren't ready may let
+            // them go on
+            let asked = self.queue.len() > queue.len();
+            self.queue.retain(|chunk_pos| {
+                self.statuses.get(chunk_pos) < self.targets.get(chunk_pos)
+            });
+            if (!progress && !asked) || done >= budget {
+                return done;
+            }
+        }
+    }
+
+    /// Move the area of requested columns around `center`, like the column
+    /// the camera is on, the workers take the closest columns first. The
+    /// columns that leave it stop going through the stages and are unloaded
+    /// once they are `UNLOAD_MARGIN` columns past the ones it needs around
+    pub fn set_center(&mut self, center: ChunkPos) {
+        if self.center == Some(center) {
+            return;
+        }
+        self.center = Some(center);
+        if let Some(workers) = self.workers.as_ref() {
+            workers.set_center([center.x, 0, center.z]);
+        }
+
+        let distance = self.render_distance;
+        let away = |chunk_pos: ChunkPos| {
+            (chunk_pos.x - center.x).abs().max((chunk_pos.z - center.z).abs())
+        };
+        let mut far = Vec::new();
+        for (chunk_pos, status) in self.statuses.iter() {
+            let away = away(*chunk_pos);
+            if away > distance + Self::SUPPORT_RINGS + Self::UNLOAD_MARGIN {
+                far.push(*chunk_pos);
+            } else if away > distance {
+                // The ones still needed around the area are asked for again
+                // by the columns that need them
+                self.targets.insert(*chunk_pos, *status);
+            }
+        }
+        far.sort_by_key(|pos| (pos.z, pos.x));
+        if let Err(e) = self.unload_columns(far) {
+            log::warn!("{:#}, columns unloaded without saving", e);
+        }
+        self.queue.retain(|chunk_pos| {
+            self.statuses.get(chunk_pos) < self.targets.get(chunk_pos)
+        });
+
+        // The closest first, without workers they go in the order asked for
+        let mut area: Vec<ChunkPos> = (-distance..=distance)
+            .flat_map(|dz| (-distance..=distance).map(move |dx| (dx, dz)))
+            .map(|(dx, dz)| ChunkPos::new(center.x + dx, center.z + dz))
+            .collect();
+        area.sort_by_key(|chunk_pos| away(*chunk_pos));
+        for chunk_pos in area {
+            self.request_column(chunk_pos);
+        }
+    }
+
+    /// How many columns are loaded, still being made and were unloaded
+    pub fn counters(&self) -> LoadCounters {
+        LoadCounters {
+            loaded: self.columns.len(),
+            pending: self.queue.len(),
+            unloaded: self.unloaded_count,
+        }
+    }
+
+    /// Columns on their way through the workers
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Run the next stage of a column if it has to and the columns around
+    /// are ready for it, asking for them if they aren't there
+    fn advance(&mut self, chunk_pos: ChunkPos) -> Progress {
+        let (Some(status), Some(target)) = (self.status(chunk_pos), self.targets.get(&chunk_pos))
+        else {
+            return Progress::Waiting;
+        };
+        if status >= *target || self.in_flight.contains_key(&chunk_pos) {
+            return Progress::Waiting;
+        }
+        let Some(next) = status.next() else {
+            return Progress::Waiting;
+        };
+
+        if let Some(needed) = next.neighbors_needed() {
+            let mut ready = true;
+            for (dx, dz) in NEIGHBORS {
+                let neighbor_pos = ChunkPos::new(chunk_pos.x + dx, chunk_pos.z + dz);
+                self.raise_target(neighbor_pos, needed);
+                ready &= self.status(neighbor_pos).is_some_and(|status| status >= needed);
+            }
+            if !ready {
+                return Progress::Waiting;
+            }
+        }
+
+        match next {
+            ChunkStatus::Empty => unreachable!(),
+            ChunkStatus::Terrain if self.load_column(chunk_pos) => {}
+            ChunkStatus::Terrain | ChunkStatus::Carved | ChunkStatus::Decorated => {
+                return self.start_stage(chunk_pos, next);
+            }
+            ChunkStatus::Lit => self.light_column(chunk_pos),
+            ChunkStatus::Meshed => {
+                self.statuses.insert(chunk_pos, ChunkStatus::Meshed);
+                let sections: Vec<SectionPos> = self.column(chunk_pos).unwrap()
+                    .sections()
+                    .filter(|section| !section.is_empty())
+                    .map(|section| section.pos())
+                    .collect();
+                for section_pos in sections {
+                    self.mark_dirty(section_pos);
+                }
+            }
+        }
+
+        Progress::Done
+    }
+
+    /// Read a column from the storage, the saved ones are already decorated.
+    /// `false` if it has to be generated
+    fn load_column(&mut self, chunk_pos: ChunkPos) -> bool {
+        self.load_edits(chunk_pos);
+        let saved = self.storage.as_mut()
+            .map(|storage| storage.load_column(chunk_pos, &self.registry))
+            .transpose()
+            .unwrap_or_else(|e| {
+                log::warn!("{:#}, generating it again", e);
+                None
+            })
+            .flatten();
+        let Some(column) = saved else {
+            return false;
+        };
+        self.add_decorated_column(column);
+
+        true
+    }
+
+    /// Move the edits the storage kept for a column to the ones waiting for
+    /// it, before the ones that came meanwhile
+    fn load_edits(&mut self, chunk_pos: ChunkPos) {
+        let Some(storage) = self.storage.as_mut() else {
+            return;
+        };
+        match storage.take_edits(chunk_pos, &self.registry) {
+            Result::Ok(stored) if !stored.is_empty() => {
+                let newer = self.pending_edits.take(chunk_pos);
+                self.pending_edits.extend(chunk_pos, stored.into_iter().chain(newer));
+            }
+            Result::Ok(_) => {}
+            Err(e) => log::warn!("{:#}, the features reaching it are cut", e),
+        }
+    }
+
+    /// Take a column through a stage that only reads itself, on the workers
+    /// if there are
+    fn start_stage(&mut self, chunk_pos: ChunkPos, status: ChunkStatus) -> Progress {
+        let generator = self.generator.clone();
+        let column = self.generating.remove(&chunk_pos);
+        let run = move || run_stage(generator.as_ref(), chunk_pos, status, column);
+        match self.workers.as_ref() {
+            Some(workers) => {
+                let id = workers.spawn(chunk_pos, [chunk_pos.x, 0, chunk_pos.z], run);
+                self.in_flight.insert(chunk_pos, (status, id));
+                Progress::Sent
+            }
+            None => {
+                self.finish_stage(chunk_pos, status, run());
+                Progress::Done
+            }
+        }
+    }
+
+    /// Take the stages the workers are done with, the ones of columns
+    /// unloaded meanwhile are dropped. A column whose stage failed took its
+    /// blocks with it, it's made again from the start. Returns how many were
+    /// taken
+    fn receive_stages(&mut self) -> usize {
+        let Some(workers) = self.workers.as_ref() else {
+            return 0;
+        };
+        let mut done = 0;
+        for (chunk_pos, id, output) in workers.finished() {
+            let Some((status, _)) = self.in_flight.get(&chunk_pos).filter(|(_, sent)| *sent == id)
+            else {
+                continue;
+            };
+            let status = *status;
+            self.in_flight.remove(&chunk_pos);
+            match output {
+                Result::Ok(output) => {
+                    self.finish_stage(chunk_pos, status, output);
+                    done += 1;
+                }
+                Err(e) => {
+                    log::warn!("{:#}, column {:?} is made again", e, chunk_pos);
+                    self.statuses.insert(chunk_pos, ChunkStatus::Empty);
+                    if !self.queue.contains(&chunk_pos) {
+                        self.queue.push(chunk_pos);
+                    }
+                }
+            }
+        }
+
+        done
+    }
+
+    /// Put back a column taken through a stage, the decorated ones with the
+    /// blocks of the features that reached them meanwhile
+    fn finish_stage(&mut self, chunk_pos: ChunkPos, status: ChunkStatus, output: StageOutput) {
+        let StageOutput { mut column, edits } = output;
+        if status == ChunkStatus::Decorated {
+            self.apply_pending_edits(&mut column);
+        }
+        self.generating.insert(chunk_pos, column);
+        self.statuses.insert(chunk_pos, status);
+
+        // A column that wrote to others is saved with them, so its features
+        // aren't written again when it's generated next time
+        if !edits.is_empty() {
+            self.modified.insert(chunk_pos);
+        }
+        self.add_edits(edits);
+    }
+
+    /// Add a column made whole, like the ones read from the storage, only the
+    /// features of the columns around are left
+    pub(super) fn add_decorated_column(&mut self, mut column: ChunkColumn) {
+        let chunk_pos = column.pos();
+        self.apply_pending_edits(&mut column);
+        self.generating.insert(chunk_pos, column);
+        self.statuses.insert(chunk_pos, ChunkStatus::Decorated);
+    }
+
+    /// Write the blocks of features to the columns that are decorated, the
+    /// rest wait for their columns to be. Until then the columns can be
+    /// unloaded without losing them, and they are saved with the world
+    pub(super) fn add_edits(&mut self, edits: BlockEdits) {
+        for (chunk_pos, edits) in edits {
+            let status = self.status(chunk_pos);
+            if status.is_none_or(|status| status < ChunkStatus::Decorated) {
+                // The ones saved before go first
+                self.load_edits(chunk_pos);
+                self.pending_edits.extend(chunk_pos, edits);
+                continue;
+            }
+            self.modified.insert(chunk_pos);
+
+            // Only the features that reach further than the columns around
+            // get to the lit ones
+            if let Some(column) = self.generating.get_mut(&chunk_pos) {
+                for edit in edits {
+                    edit.apply(column);
+                }
+                column.compact();
+                continue;
+            }
+            for edit in edits {
+                let old = self.column(chunk_pos).and_then(|column| column.get_block(edit.block_pos));
+                if old.is_some_and(|old| edit.replaces(old)) {
+                    self.place_block(chunk_pos, edit.block_pos, edit.block);
+                }
+            }
+        }
+    }
+
+    /// Write the edits waiting for a column, it becomes modified if there
+    /// were any
+    fn apply_pending_edits(&mut self, column: &mut ChunkColumn) {
+        let edits = self.pending_edits.take(column.pos());
+        if edits.is_empty() {
+            return;
+        }
+        for edit in edits {
+            edit.apply(column);
+        }
+        column.compact();
+        self.modified.insert(column.pos());
+    }
+
+    /// Add a column to the world and spread its light, the light of the lit
+    /// columns around may change
+    fn light_column(&mut self, chunk_pos: ChunkPos) {
+        let column = self.generating.remove(&chunk_pos).unwrap();
+        self.columns.insert(chunk_pos, column);
+        self.statuses.insert(chunk_pos, ChunkStatus::Lit);
+
+        let mut changed: Vec<SectionPos> = self.light_columns(&[chunk_pos])
+            .into_iter()
+            .filter(|section_pos| section_pos.column() != chunk_pos)
+            .collect();
+        changed.sort_by_key(|pos| (pos.y, pos.z, pos.x));
+        for section_pos in changed {
+            self.schedule_update(section_pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    use pretty_assertions::assert_eq;
+    use super::*;
+    use crate::block::Block;
+    use crate::chunk::{BlockPos, WorldBlockPos};
+    use crate::world::generator::TerrainGenerator;
+    use crate::world::tests::{empty_world, floor_column, insert_column};
+
+    #[test]
+    fn statuses_follow_the_neighbors() {
+        let mut world = empty_world();
+        world.request_column(ChunkPos::new(0, 0));
+        assert_eq!(world.status(ChunkPos::new(0, 0)), Some(ChunkStatus::Empty));
+        assert_eq!(world.status(ChunkPos::new(1, 0)), None);
+
+        // The stages that only read their own column go right away
+        assert_eq!(world.generate_columns(3), 3);
+        assert_eq!(world.status(ChunkPos::new(0, 0)), Some(ChunkStatus::Decorated));
+
+        // To be lit it needs the columns around decorated, and to be meshed
+        // lit, which need the ones around them decorated
+        assert_eq!(world.generate_columns(usize::MAX), 8 * 3 + 9 + 16 * 3 + 1);
+        let status = |x, z| world.status(ChunkPos::new(x, z));
+        assert_eq!(status(0, 0), Some(ChunkStatus::Meshed));
+        assert_eq!(status(1, -1), Some(ChunkStatus::Lit));
+        assert_eq!(status(-2, 2), Some(ChunkStatus::Decorated));
+        assert_eq!(status(0, 3), None);
+        assert_eq!(world.statuses().count(), 25);
+        assert_eq!(world.generate_columns(usize::MAX), 0);
+
+        // Only the lit ones are part of the world
+        assert!(world.column(ChunkPos::new(1, 1)).is_some());
+        assert!(world.column(ChunkPos::new(2, 1)).is_none());
+        assert_eq!(world.get_block(WorldBlockPos::new(-16, 0, 0)), Some(Block::AIR));
+        assert_eq!(world.get_block(WorldBlockPos::new(-17, 0, 0)), None);
+    }
+
+    #[test]
+    fn meshes_wait_for_the_neighbors() {
+        let mut world = empty_world();
+        let column = floor_column(world.registry(), ChunkPos::new(0, 0));
+        insert_column(&mut world, column);
+        let column = floor_column(world.registry(), ChunkPos::new(1, 0));
+        insert_column(&mut world, column);
+
+        // The column on the right isn't lit when the first one is, its
+        // borders would be meshed before knowing what's next to them
+        world.generate_columns(2);
+        assert_eq!(world.status(ChunkPos::new(0, 0)), Some(ChunkStatus::Decorated));
+        world.generate_columns(usize::MAX);
+        assert_eq!(
+            world.dirty_sections(usize::MAX),
+            vec![SectionPos::new(0, 0, 0), SectionPos::new(1, 0, 0)]
+        );
+
+        // The light of the columns lit later changes the meshed ones, but
+        // the columns that aren't meshed yet are left alone
+        let glowstone = world.registry().by_name("glowstone").unwrap();
+        let mut column = floor_column(world.registry(), ChunkPos::new(2, 0));
+        column.place_block(BlockPos::new(0, 1, 0), glowstone);
+        world.unload_column(ChunkPos::new(2, 0)).unwrap();
+        insert_column(&mut world, column);
+        world.generate_columns(usize::MAX);
+        assert_eq!(world.status(ChunkPos::new(2, 0)), Some(ChunkStatus::Meshed));
+        assert_eq!(
+            world.dirty_sections(usize::MAX),
+            vec![SectionPos::new(1, 0, 0), SectionPos::new(2, 0, 0)]
+        );
+        assert_eq!(world.light(WorldBlockPos::new(31, 1, 0)).unwrap().block, 14);
+    }
+
+    #[test]
+    fn workers_make_the_same_columns() {
+        let terrain = || {
+            let mut world = empty_world();
+            world.generator = Arc::new(TerrainGenerator::with_seed(7, world.registry()).unwrap());
+            world.request_column(ChunkPos::new(0, 0));
+            world
+        };
+        let mut alone = terrain();
+        alone.generate_columns(usize::MAX);
+        let mut world = terrain().with_workers(2);
+
+        // The stages of the columns alone come back on later calls, the rest
+        // run once they do
+        assert_eq!(world.generate_columns(usize::MAX), 0);
+        assert_eq!(world.in_flight(), 1);
+        let start = Instant::now();
+        while world.status(ChunkPos::new(0, 0)) != Some(ChunkStatus::Meshed) {
+            assert!(start.elapsed() < Duration::from_secs(60), "The workers got stuck");
+            world.generate_columns(usize::MAX);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(world.in_flight(), 0);
+        assert_eq!(world.statuses().count(), alone.statuses().count());
+        for (chunk_pos, status) in alone.statuses() {
+            assert_eq!(world.status(chunk_pos), Some(status));
+        }
+
+        for chunk_pos in NEIGHBORS.iter().chain(&[(0, 0)]).map(|(x, z)| ChunkPos::new(*x, *z)) {
+            let (a, b) = (alone.column(chunk_pos).unwrap(), world.column(chunk_pos).unwrap());
+            for (a, b) in a.sections().zip(b.sections()) {
+                for block_pos in (0..16 * 16 * 16).map(|i| BlockPos::new(i % 16, i / 256, i / 16 % 16)) {
+                    assert_eq!(a.get_block(block_pos), b.get_block(block_pos), "{:?}", a.pos());
+                    assert_eq!(a.light(block_pos), b.light(block_pos), "{:?}", a.pos());
+                }
+            }
+        }
+    }
+
+    /// Floors whose first carving of the origin panics
+    struct FlakyGenerator {
+        registry: Arc<crate::block::BlockRegistry>,
+        panicked: AtomicBool,
+    }
+
+    impl WorldGenerator for FlakyGenerator {
+        fn generate(&self, chunk_pos: ChunkPos) -> ChunkColumn {
+            floor_column(&self.registry, chunk_pos)
+        }
+
+        fn carve(&self, column: &mut ChunkColumn) {
+            if column.pos() == ChunkPos::new(0, 0) && !self.panicked.swap(true, Ordering::SeqCst) {
+                panic!("Carving failed");
+            }
+        }
+    }
+
+    #[test]
+    fn failed_stages_are_made_again() {
+        let mut world = empty_world().with_workers(1);
+        world.generator = Arc::new(FlakyGenerator {
+            registry: world.registry().clone(),
+            panicked: AtomicBool::new(false),
+        });
+        world.request_column(ChunkPos::new(0, 0));
+
+        let start = Instant::now();
+        while world.status(ChunkPos::new(0, 0)) != Some(ChunkStatus::Meshed) {
+            assert!(start.elapsed() < Duration::from_secs(60), "The failed column got stuck");
+            world.generate_columns(usize::MAX);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(world.in_flight(), 0);
+        let stone = world.registry().by_name("stone").unwrap();
+        assert_eq!(world.get_block(WorldBlockPos::new(3, 0, 3)), Some(stone));
+    }
+
+    #[test]
+    fn columns_follow_the_center() {
+        let mut world = empty_world().with_render_distance(2);
+        world.set_center(ChunkPos::new(0, 0));
+        world.generate_columns(usize::MAX);
+        assert_eq!(world.statuses().count(), 9 * 9);
+        assert_eq!(world.counters(), LoadCounters {
+            loaded: 7 * 7,
+            pending: 0,
+            unloaded: 0,
+        });
+
+        // Only the columns past the margin are unloaded, the meshed ones
+        // that left the area stay as they are
+        world.set_center(ChunkPos::new(3, 0));
+        assert_eq!(world.counters().unloaded, 9);
+        assert_eq!(world.status(ChunkPos::new(-4, 0)), None);
+        assert_eq!(world.status(ChunkPos::new(-2, 0)), Some(ChunkStatus::Meshed));
+        assert_eq!(world.status(ChunkPos::new(-3, 0)), Some(ChunkStatus::Lit));
+        assert!(world.unloaded_columns().is_empty());
+        world.generate_columns(usize::MAX);
+        assert_eq!(world.status(ChunkPos::new(5, 2)), Some(ChunkStatus::Meshed));
+        assert_eq!(world.status(ChunkPos::new(-3, 0)), Some(ChunkStatus::Lit));
+
+        // Far away the old ones go, the meshed ones for the renderer to drop
+        // their sections
+        world.dirty_sections(usize::MAX);
+        world.set_center(ChunkPos::new(10, 0));
+        assert_eq!(world.counters(), LoadCounters {
+            loaded: 3 * 7,
+            pending: 5 * 5,
+            unloaded: 9 + 7 * 9,
+        });
+        let mut unloaded = world.unloaded_columns();
+        unloaded.sort_by_key(|pos| (pos.x, pos.z));
+        let meshed: Vec<_> = (-2..=3)
+            .flat_map(|x| (-2..=2).map(move |z| ChunkPos::new(x, z)))
+            .collect();
+        assert_eq!(unloaded, meshed);
+        assert!(world.column(ChunkPos::new(3, 0)).is_none());
+        world.generate_columns(usize::MAX);
+        assert_eq!(world.counters().pending, 0);
+        assert_eq!(world.status(ChunkPos::new(8, -2)), Some(ChunkStatus::Meshed));
+        assert!(world.dirty_sections(usize::MAX).iter().all(|pos| pos.x >= 4));
+    }
+}